@@ -1,33 +1,200 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token_interface::{Mint, TokenAccount};
+use anchor_lang::solana_program::bpf_loader_upgradeable;
+use anchor_lang::solana_program::instruction::{get_stack_height, Instruction, TRANSACTION_LEVEL_STACK_HEIGHT};
+use anchor_lang::solana_program::keccak;
+use anchor_lang::solana_program::program::set_return_data;
+use anchor_lang::solana_program::program_option::COption;
+use anchor_lang::solana_program::sysvar::{
+    self,
+    instructions::{load_current_index_checked, load_instruction_at_checked},
+};
+use anchor_lang::system_program;
+use anchor_spl::associated_token::{self, get_associated_token_address_with_program_id, AssociatedToken};
+use anchor_spl::token::spl_token;
+use anchor_spl::token_2022::spl_token_2022;
+use anchor_spl::token_2022::spl_token_2022::extension::{
+    interest_bearing_mint::InterestBearingConfig, BaseStateWithExtensions, StateWithExtensions,
+};
+use anchor_spl::token_2022::spl_token_2022::instruction::TokenInstruction;
+use anchor_spl::token_interface::{approve, Approve, Mint, TokenAccount, TokenInterface};
+use spl_tlv_account_resolution::{account::ExtraAccountMeta, seeds::Seed, state::ExtraAccountMetaList};
+use spl_transfer_hook_interface::instruction::ExecuteInstruction;
 
 declare_id!("9WBmvVwg9LqodhDrh1FVLqxf4cZ22qNvQ4qEX88fewST");
 
-/// Fee tier thresholds (in tokens)
+/// Default fee tier thresholds (in tokens)
 pub const TIER_1_THRESHOLD: u64 = 100_000_000; // 0.1 token (assuming 9 decimals)
 pub const TIER_2_THRESHOLD: u64 = 1_000_000_000; // 1 token
 pub const TIER_3_THRESHOLD: u64 = 10_000_000_000; // 10 tokens
 
-/// Fee basis points per tier (1 bp = 0.01%)
+/// Default fee basis points per tier (1 bp = 0.01%)
 pub const TIER_1_FEE_BPS: u16 = 100; // 1%
 pub const TIER_2_FEE_BPS: u16 = 50;  // 0.5%
 pub const TIER_3_FEE_BPS: u16 = 25;  // 0.25%
 pub const TIER_4_FEE_BPS: u16 = 10;  // 0.1% (over tier 3)
 
-/// Loyalty reward thresholds
+/// Number of amount-based fee tiers
+pub const FEE_TIER_COUNT: usize = 4;
+/// Number of thresholds separating the fee tiers
+pub const FEE_TIER_THRESHOLD_COUNT: usize = FEE_TIER_COUNT - 1;
+
+/// Default loyalty reward thresholds
 pub const LOYALTY_BRONZE: u64 = 10;  // 10 transfers
 pub const LOYALTY_SILVER: u64 = 50;  // 50 transfers
 pub const LOYALTY_GOLD: u64 = 100;   // 100 transfers
 
+/// Default loyalty discounts (bps of the transfer amount)
+pub const LOYALTY_BRONZE_DISCOUNT_BPS: u16 = 10; // 0.1%
+pub const LOYALTY_SILVER_DISCOUNT_BPS: u16 = 25; // 0.25%
+pub const LOYALTY_GOLD_DISCOUNT_BPS: u16 = 50;   // 0.5%
+
+/// Maximum number of configurable loyalty tiers
+pub const MAX_LOYALTY_TIERS: usize = 8;
+
+/// Loyalty tier benefit bits in `HookConfig::tier_benefits`; a tier with
+/// no bits set only grants its discount
+pub const TIER_BENEFIT_COOLDOWN_EXEMPT: u8 = 1 << 0; // skips the transfer cooldown
+pub const TIER_BENEFIT_UNCAPPED: u8 = 1 << 1;        // skips `max_transfers_per_user`
+pub const TIER_BENEFIT_ALL: u8 = TIER_BENEFIT_COOLDOWN_EXEMPT | TIER_BENEFIT_UNCAPPED;
+
+/// Size of the per-user counterparty Bloom filter (256 bits)
+pub const COUNTERPARTY_FILTER_BYTES: usize = 32;
+/// Bits set per destination in the counterparty filter
+pub const COUNTERPARTY_FILTER_HASHES: usize = 3;
+
+/// Capacity of the revenue-sharing fee collector list
+pub const MAX_FEE_COLLECTORS: usize = 8;
+
+/// Capacity of the known DeFi program-owner list
+pub const MAX_DEFI_OWNERS: usize = 8;
+
+/// Capacity of the authorized minter list
+pub const MAX_AUTHORIZED_MINTERS: usize = 8;
+
+/// Length of an `HourlyStats` bucket
+pub const HOUR_SECONDS: i64 = 3_600;
+
+/// Volume units `HookConfig::points_rate` is quoted per
+pub const POINTS_RATE_SCALE: u64 = 1_000_000;
+
+/// Milestone thresholds per counter
+pub const MAX_MILESTONES: usize = 4;
+/// Counters a `Milestone` event can report
+pub const MILESTONE_TRANSFERS: u8 = 0;
+pub const MILESTONE_VOLUME: u8 = 1;
+
+/// Why a transfer's loyalty tier sits below the one its count earns
+pub const TIER_GATE_NONE: u8 = 0;
+pub const TIER_GATE_COUNTERPARTIES: u8 = 1;
+
+/// SPL Memo program ids (v2 and the legacy v1) accepted by `require_memo`
+pub const MEMO_PROGRAM_ID: Pubkey = anchor_lang::solana_program::pubkey!("MemoSq4gqABAXKb96qnH8TysNcWxMyWCqXgDLGmfcHr");
+pub const MEMO_V1_PROGRAM_ID: Pubkey = anchor_lang::solana_program::pubkey!("Memo1UhkJRfHyvLMcVucJwxXeuD728EqVDDwQDxFMNo");
+
+/// Length of the per-user rolling window
+pub const DAILY_WINDOW_SECONDS: i64 = 86_400;
+
+/// Which side of a transfer owes the fee
+pub const FEE_PAYER_SENDER: u8 = 0;
+pub const FEE_PAYER_RECEIVER: u8 = 1;
+pub const FEE_PAYER_SPLIT: u8 = 2;
+
+/// How the holding-duration discount combines with the volume discount
+pub const HOLDING_DISCOUNT_OFF: u8 = 0;
+pub const HOLDING_DISCOUNT_STACK: u8 = 1;
+pub const HOLDING_DISCOUNT_REPLACE: u8 = 2;
+
+/// How the final fee is rounded to `fee_granularity`
+pub const FEE_ROUND_NEAREST: u8 = 0;
+pub const FEE_ROUND_DOWN: u8 = 1;
+pub const FEE_ROUND_UP: u8 = 2;
+
+/// `msg!` verbosity levels; structured `emit!` events are always sent
+pub const LOG_NONE: u8 = 0;
+pub const LOG_ERRORS: u8 = 1;
+pub const LOG_INFO: u8 = 2;
+pub const LOG_DEBUG: u8 = 3;
+
+/// Fixed-point scale for the rewards-per-volume accumulator
+pub const REWARD_PRECISION: u128 = 1_000_000_000_000;
+
+/// Offsets in `UserState` account data (discriminator included) of the
+/// fields the extra account metas read as seeds; all earlier fields are
+/// fixed-size
+pub const USER_STATE_RECEIPT_NONCE_OFFSET: u8 = 175;
+pub const USER_STATE_LINKED_ROOT_OFFSET: u8 = 247;
+
+/// Accounts `transfer_hook` takes after the validation account, every one
+/// listed in the extra account metas (see `extra_account_metas`)
+pub const EXTRA_ACCOUNT_META_COUNT: usize = 16;
+
+/// Layout version and length of the `export_user_state` blob
+pub const USER_STATE_EXPORT_VERSION: u8 = 1;
+pub const USER_STATE_EXPORT_LEN: usize = 115;
+
+/// Flag bits of the exported user state
+pub const EXPORT_FLAG_BLACKLISTED: u8 = 1 << 0;
+pub const EXPORT_FLAG_WHITELISTED: u8 = 1 << 1;
+pub const EXPORT_FLAG_LOYALTY_OPT_OUT: u8 = 1 << 2;
+
+/// Fixed-point scale of the average fee returned by `get_average_fee_bps`
+pub const AVERAGE_FEE_BPS_PRECISION: u128 = 1_000_000;
+
+/// Number of decimal-magnitude buckets in the user volume histogram;
+/// bucket `i` holds lifetime volumes with `i + 1` digits (u64 has at most 20)
+pub const VOLUME_BUCKET_COUNT: usize = 20;
+
+/// Length of the optional per-mint ASCII label
+pub const LABEL_LEN: usize = 16;
+
+/// `msg!` only when the config's `log_level` is at least `$level`,
+/// prefixed with `[label] ` when the mint has a label
+macro_rules! log_at {
+    ($config:expr, $level:expr, $($arg:tt)+) => {
+        if $config.log_level >= $level {
+            if $config.label[0] == 0 {
+                msg!($($arg)+);
+            } else {
+                msg!("[{}] {}", label_str(&$config.label), format_args!($($arg)+));
+            }
+        }
+    };
+}
+
 #[program]
 pub mod versa_transfer_hook {
     use super::*;
 
-    /// Initialize the transfer hook configuration
+    /// Initialize the transfer hook configuration.
+    /// Native (wrapped SOL) mints are rejected: they cannot carry a transfer
+    /// hook, and lamport fee deposits into WSOL accounts would need `sync_native`.
+    /// `label` is an optional printable-ASCII tag, zero-padded (all zeros =
+    /// none), that prefixes log lines and is carried in the main events so
+    /// operators running many mints can tell them apart.
     pub fn initialize(
         ctx: Context<Initialize>,
         fee_collector: Pubkey,
+        label: [u8; LABEL_LEN],
+        validate_collector: bool,
     ) -> Result<()> {
+        require!(
+            !is_native_mint(&ctx.accounts.mint.key()),
+            ErrorCode::InvalidFeeConfig
+        );
+        require!(is_valid_label(&label), ErrorCode::InvalidLabel);
+        // Optionally fail fast on a collector without a token account for
+        // this mint, rather than on the first transfer that pays it
+        if validate_collector {
+            let mint = ctx.accounts.mint.to_account_info();
+            let expected = get_associated_token_address_with_program_id(&fee_collector, mint.key, mint.owner);
+            require!(
+                ctx.accounts.fee_collector_token.as_ref().is_some_and(|token| {
+                    token.key() == expected && token.mint == *mint.key && token.owner == fee_collector
+                }),
+                ErrorCode::InvalidFeeCollectorAccount
+            );
+        }
+
         let config = &mut ctx.accounts.hook_config;
         config.authority = ctx.accounts.authority.key();
         config.fee_collector = fee_collector;
@@ -35,306 +202,9299 @@ pub mod versa_transfer_hook {
         config.total_transfers = 0;
         config.total_volume = 0;
         config.total_fees_collected = 0;
+        config.total_discounts_given = 0;
+        apply_schedule(config, &default_schedule());
+        config.tier_fees_enabled = [true; FEE_TIER_COUNT];
+        config.log_level = LOG_INFO;
+        config.max_fee_collectors = MAX_FEE_COLLECTORS as u8;
+        config.fee_collector_count = 1;
+        config.fee_collectors[0] = fee_collector;
+        config.fee_collector_weights[0] = 10000;
+        config.label = label;
+        refresh_schedule_hash(config, ctx.accounts.mint.key())?;
         
-        msg!("✅ Versa Transfer Hook initialized!");
-        msg!("Authority: {}", config.authority);
-        msg!("Fee Collector: {}", config.fee_collector);
+        log_at!(config, LOG_INFO, "✅ Versa Transfer Hook initialized!");
+        log_at!(config, LOG_INFO, "Authority: {}", config.authority);
+        log_at!(config, LOG_INFO, "Fee Collector: {}", config.fee_collector);
         
         Ok(())
     }
 
-    /// Initialize extra account meta list for the transfer hook
+    /// Initialize extra account meta list for the transfer hook from the
+    /// features currently enabled in the config (see `extra_account_metas`)
     pub fn initialize_extra_account_meta_list(
-        _ctx: Context<InitializeExtraAccountMetaList>,
+        ctx: Context<InitializeExtraAccountMetaList>,
     ) -> Result<()> {
+        let metas = extra_account_metas(&ctx.accounts.hook_config)?;
+        let mut data = ctx.accounts.extra_account_meta_list.try_borrow_mut_data()?;
+        ExtraAccountMetaList::init::<ExecuteInstruction>(&mut data, &metas)?;
+        ctx.accounts.hook_config.metas_version = metas_version(&ctx.accounts.hook_config);
+
         msg!("✅ Extra account meta list initialized!");
         Ok(())
     }
 
+    /// Admin: Rewrite the extra account meta list to exactly the accounts
+    /// the currently enabled features need, and record its `metas_version`.
+    /// Run it after any config change that adds or drops an extra account:
+    /// strict-pair whitelisting, required memos, SOL fees (or a new SOL fee
+    /// collector), the required reentrancy guard or a minimum stake. Until
+    /// then every transfer fails with `ExtraAccountMetasStale` rather than
+    /// on whichever account happens to be missing. Lists written before
+    /// versioning (version 0) are not checked until first synced.
+    pub fn sync_extra_account_metas(ctx: Context<SyncExtraAccountMetas>) -> Result<()> {
+        let metas = extra_account_metas(&ctx.accounts.hook_config)?;
+        let mut data = ctx.accounts.extra_account_meta_list.try_borrow_mut_data()?;
+        ExtraAccountMetaList::update::<ExecuteInstruction>(&mut data, &metas)?;
+
+        let config = &mut ctx.accounts.hook_config;
+        config.metas_version = metas_version(config);
+
+        log_at!(config, LOG_INFO, "🧭 Extra account metas synced (version {})", config.metas_version);
+        Ok(())
+    }
+
     /// Main transfer hook logic - executes on every transfer
     pub fn transfer_hook(
         ctx: Context<TransferHook>,
         amount: u64,
     ) -> Result<()> {
+        // A meta list out of step with the config resolves the wrong
+        // accounts: fail with a clear error instead of somewhere further in
+        if metas_out_of_sync(&ctx.accounts.hook_config) {
+            return reject_transfer(
+                &ctx.accounts.hook_config,
+                ctx.accounts.mint.key(),
+                ctx.accounts.owner.key(),
+                amount,
+                ErrorCode::ExtraAccountMetasStale,
+            );
+        }
+
+        // Without the owner's user state only the mint-wide rules can run.
+        // The state is loaded by hand (the hook cannot create it) and
+        // written back before returning.
+        let user_state_info = ctx.accounts.user_state.to_account_info();
+        let Some(mut loaded_state) = load_transfer_user_state(&user_state_info)? else {
+            return stateless_transfer_hook(ctx, amount);
+        };
+        let user_state = &mut loaded_state;
         let config = &mut ctx.accounts.hook_config;
-        let user_state = &mut ctx.accounts.user_state;
+        let mint = ctx.accounts.mint.key();
+        let owner = ctx.accounts.owner.key();
+        // Without the clock (and `require_clock` off) time reads as 0 and
+        // every time-based rule below is skipped; see `set_require_clock`
+        let clock = resolve_clock(config, Clock::get())?;
+        let clock_available = clock.is_some();
+        if !clock_available {
+            log_at!(config, LOG_DEBUG, "Clock unavailable, skipping time-based checks");
+        }
+        let Clock { slot, unix_timestamp: now, .. } = clock.unwrap_or_default();
+
+        // Defense in depth against nested invocations: lock the guard and
+        // persist it right away, so a nested hook sees it; it is cleared
+        // before returning, and any failure reverts the lock with the rest
+        if let Some(guard) = ctx.accounts.reentrancy_guard.as_mut() {
+            if guard.locked {
+                return reject_transfer(config, mint, owner, amount, ErrorCode::ReentrancyDetected);
+            }
+            guard.locked = true;
+            guard.exit(&crate::ID)?;
+        } else if config.require_reentrancy_guard {
+            return reject_transfer(config, mint, owner, amount, ErrorCode::ReentrancyGuardMissing);
+        }
+
+        // Check if hook is paused; a scheduled pause lapses at `auto_unpause_at`.
+        // Optionally the authority and fee collector may still move tokens.
+        let paused = config.is_paused && (config.auto_unpause_at == 0 || now < config.auto_unpause_at);
+        let pause_bypassed = paused
+            && config.allow_admin_during_pause
+            && [ctx.accounts.source_token.owner, ctx.accounts.destination_token.owner]
+                .iter()
+                .any(|side| *side == config.authority || is_fee_collector(config, side));
+        if paused && !pause_bypassed {
+            return reject_transfer(config, mint, owner, amount, ErrorCode::HookPaused);
+        }
+
+        // Check whitelist/blacklist; a linked account also inherits its
+        // root's blacklist, so the root's state must be passed
+        let root_blacklisted = if user_state.linked_root == Pubkey::default() {
+            false
+        } else {
+            match ctx.accounts.linked_root_state.as_ref().map(|root| read_user_state(root, &mint)) {
+                Some(Ok(root)) if root.owner == user_state.linked_root => root.is_blacklisted,
+                _ => return reject_transfer(config, mint, owner, amount, ErrorCode::LinkedRootStateMissing),
+            }
+        };
+        if user_state.is_blacklisted || root_blacklisted {
+            return reject_transfer(config, mint, owner, amount, ErrorCode::UserBlacklisted);
+        }
+
+        // Permissioned tokens: the sender must be whitelisted, and with
+        // strict pairs so must the receiver (a missing state fails closed)
+        if config.whitelist_mode {
+            if !user_state.is_whitelisted {
+                return reject_transfer(config, mint, owner, amount, ErrorCode::NotWhitelisted);
+            }
+            if config.strict_pairs
+                && !ctx
+                    .accounts
+                    .destination_user_state
+                    .as_ref()
+                    .is_some_and(|destination| destination.is_whitelisted)
+            {
+                return reject_transfer(config, mint, owner, amount, ErrorCode::ReceiverNotWhitelisted);
+            }
+        }
+
+        // Directional blocks: the sender's outgoing side, and the receiver's
+        // incoming side when their user state is passed
+        if user_state.block_outgoing {
+            return reject_transfer(config, mint, owner, amount, ErrorCode::OutgoingBlocked);
+        }
+        if ctx
+            .accounts
+            .destination_user_state
+            .as_ref()
+            .is_some_and(|destination| destination.block_incoming)
+        {
+            return reject_transfer(config, mint, owner, amount, ErrorCode::IncomingBlocked);
+        }
+
+        // Optionally keep the fee collector out of hooked transfers, so fees
+        // are never charged on fees and the collector cannot self-deal.
+        // When receivers pay, transfers to the collector are fee payments
+        let fee_payment = config.fee_payer_mode != FEE_PAYER_SENDER
+            && is_fee_collector(config, &ctx.accounts.destination_token.owner)
+            && !is_fee_collector(config, &ctx.accounts.source_token.owner);
+        if config.block_collector_transfers
+            && !fee_payment
+            && (is_fee_collector(config, &ctx.accounts.source_token.owner)
+                || is_fee_collector(config, &ctx.accounts.destination_token.owner))
+        {
+            return reject_transfer(config, mint, owner, amount, ErrorCode::CollectorTransferBlocked);
+        }
+
+        // Compliance: require an SPL Memo instruction in the same transaction
+        if config.require_memo
+            && !ctx
+                .accounts
+                .instructions_sysvar
+                .as_ref()
+                .is_some_and(|sysvar| has_memo_instruction(sysvar))
+        {
+            return reject_transfer(config, mint, owner, amount, ErrorCode::MemoRequired);
+        }
+
+        // Guard against a substituted user state
+        require_keys_eq!(user_state.owner, owner, ErrorCode::OwnerMismatch);
+
+        // Transfers to or from the mint authority (e.g. treasury distribution)
+        // can be fee-free and left out of all statistics; a mint without an
+        // authority never matches
+        let mint_authority = ctx.accounts.mint.mint_authority;
+        let mint_authority_transfer = mint_authority
+            == COption::Some(ctx.accounts.source_token.owner)
+            || mint_authority == COption::Some(ctx.accounts.destination_token.owner);
+        let authority_exempt = config.exempt_mint_authority && mint_authority_transfer;
+
+        // Anti-accumulation cap on the receiving account. The hook runs after
+        // Token-2022 has moved the tokens, so the destination balance already
+        // includes `amount`; a self-transfer leaves it unchanged.
+        if !authority_exempt
+            && ctx.accounts.source_token.key() != ctx.accounts.destination_token.key()
+            && exceeds_max_balance(config, ctx.accounts.destination_token.amount)
+        {
+            return reject_transfer(config, mint, owner, amount, ErrorCode::MaxBalanceExceeded);
+        }
 
-        // Check if hook is paused
-        require!(!config.is_paused, ErrorCode::HookPaused);
+        // Stake-gated transferability: the sender must hold `min_stake`
+        if !authority_exempt && !meets_min_stake(config, ctx.accounts.stake_account.as_deref(), &owner) {
+            return reject_transfer(config, mint, owner, amount, ErrorCode::InsufficientStake);
+        }
+
+        // Stamp the first transfer of a (pre-created) user state
+        if clock_available && (user_state.transfer_count == 0 || user_state.first_transfer_timestamp == 0) {
+            user_state.first_transfer_timestamp = now;
+        }
+
+        // Enforce minimum account age
+        if clock_available
+            && config.min_account_age_seconds > 0
+            && now.saturating_sub(user_state.user_created_at) < config.min_account_age_seconds
+        {
+            return reject_transfer(config, mint, owner, amount, ErrorCode::AccountTooNew);
+        }
+
+        // Reset loyalty progress after a long gap, or flag it during grace;
+        // the resulting tier's benefits apply to the checks below
+        // Users opted out of loyalty always price at the None tier
+        let decay_pending = clock_available && apply_loyalty_decay(config, user_state, now);
+        let loyalty_tier = if user_state.loyalty_opt_out {
+            0
+        } else {
+            cached_loyalty_tier(config, user_state, now)
+        };
+        let benefits = tier_benefits(config, loyalty_tier);
+        let tier_gate = if !user_state.loyalty_opt_out
+            && loyalty_tier < get_loyalty_tier(config, loyalty_count(user_state))
+        {
+            TIER_GATE_COUNTERPARTIES
+        } else {
+            TIER_GATE_NONE
+        };
 
-        // Check whitelist/blacklist
-        if user_state.is_blacklisted {
-            return err!(ErrorCode::UserBlacklisted);
+        // Enforce the lifetime transfer cap (checked before the count is incremented)
+        if config.max_transfers_per_user > 0
+            && benefits & TIER_BENEFIT_UNCAPPED == 0
+            && user_state.transfer_count >= config.max_transfers_per_user
+        {
+            return reject_transfer(config, mint, owner, amount, ErrorCode::TransferCountExceeded);
         }
 
-        // Initialize user state if first transfer
-        if user_state.transfer_count == 0 {
-            user_state.owner = ctx.accounts.owner.key();
-            user_state.first_transfer_timestamp = Clock::get()?.unix_timestamp;
+        // Throttle back-to-back transfers unless the account is exempt;
+        // the cooldown escalates with the user's rapid-transfer streak
+        if clock_available
+            && config.cooldown_seconds > 0
+            && !user_state.is_cooldown_exempt
+            && benefits & TIER_BENEFIT_COOLDOWN_EXEMPT == 0
+            && user_state.transfer_count > 0
+        {
+            let gap = now.saturating_sub(user_state.last_transfer_timestamp);
+            if gap < effective_cooldown(config, user_state.rapid_streak) {
+                return reject_transfer(config, mint, owner, amount, ErrorCode::CooldownActive);
+            }
+            update_rapid_streak(config, user_state, gap);
         }
 
-        // Calculate dynamic fee based on amount
-        let fee_bps = calculate_fee_tier(amount);
-        let fee_amount = (amount as u128)
-            .checked_mul(fee_bps as u128)
-            .unwrap()
-            .checked_div(10000)
-            .unwrap() as u64;
+        // Anti-sandwich spacing, counted in slots rather than seconds
+        if clock_available && violates_slot_spacing(config, user_state, slot) {
+            return reject_transfer(config, mint, owner, amount, ErrorCode::SlotSpacingViolation);
+        }
+
+        // Roll the user's daily window over once it has elapsed
+        if clock_available {
+            roll_daily_window(user_state, now);
+        }
+
+        // Fan-out limit: distinct destination owners within the daily window
+        if clock_available
+            && config.max_dests_per_window > 0
+            && record_counterparty(&mut user_state.window_dest_filter, &ctx.accounts.destination_token.owner)
+        {
+            user_state.window_dest_count = user_state.window_dest_count.saturating_add(1);
+            if user_state.window_dest_count > config.max_dests_per_window {
+                return reject_transfer(config, mint, owner, amount, ErrorCode::TooManyDestinations);
+            }
+        }
+
+        // No fee at all, before any other fee rule, for exempt authority
+        // transfers, transfers into a listed sink, fee payments and during
+        // the onboarding window; only the authority exemption also skips
+        // statistics
+        let onboarding_free = clock_available && in_onboarding_window(config, user_state, now);
+        let sink_destination = is_fee_sink(config, ctx.accounts.fee_sink.as_ref());
+        let fee_waived = authority_exempt || sink_destination || fee_payment || onboarding_free;
+
+        // The first `free_allowance_per_period` of volume in the window is fee-free;
+        // a transfer straddling the allowance is charged only above it
+        let free_amount = if fee_waived {
+            0
+        } else {
+            amount.min(
+                config
+                    .free_allowance_per_period
+                    .saturating_sub(user_state.free_used_this_window),
+            )
+        };
+        let chargeable_amount = amount - free_amount;
+
+        // Select the tier on the displayed amount for scaling mints
+        let priced_amount = if clock_available && config.use_scaled_amount {
+            scaled_amount(&ctx.accounts.mint.to_account_info(), amount, now)
+        } else {
+            amount
+        };
+        // Optionally select it on the user's volume within the aggregation
+        // window instead, so an order filled in chunks prices like its total
+        let priced_amount = if clock_available {
+            aggregate_priced_amount(config, user_state, priced_amount, now)
+        } else {
+            priced_amount
+        };
+
+        // Calculate dynamic fee based on amount, unless waived for the tier.
+        // The first-transfer cliff replaces the tier bps (even a waived one)
+        // and carries no loyalty discount; the free allowance still applies.
+        let fee_tier = get_fee_tier(config, priced_amount);
+        let first_transfer_cliff = !fee_waived
+            && user_state.transfer_count == 0
+            && config.first_transfer_fee_bps > 0;
+        // Transfers into or out of a listed program-derived owner (a pool or
+        // vault) are priced at the DeFi rate in place of the tier bps
+        let defi_fee = !fee_waived
+            && !first_transfer_cliff
+            && (is_defi_owner(config, &ctx.accounts.source_token.owner)
+                || is_defi_owner(config, &ctx.accounts.destination_token.owner));
+        // A governance-declared event's rate replaces the tier (or DeFi) rate
+        // and its discount replaces every other discount while it runs
+        let event_active = !fee_waived
+            && !first_transfer_cliff
+            && clock_available
+            && is_event_running(&config.active_event, now);
+        let tier_fee_bps = if fee_waived {
+            0
+        } else if first_transfer_cliff {
+            config.first_transfer_fee_bps
+        } else if event_active {
+            config.active_event.fee_bps
+        } else if defi_fee {
+            config.defi_fee_bps
+        } else if config.tier_fees_enabled[fee_tier] {
+            calculate_fee_tier(config, priced_amount)
+        } else {
+            0
+        };
+        // Early after launch the rate ramps down from `launch_fee_bps`
+        let tier_fee_bps = if fee_waived || first_transfer_cliff || event_active || !clock_available {
+            tier_fee_bps
+        } else {
+            launch_ramp_bps(config, tier_fee_bps, now)
+        };
+
+        // Nudge the tier (or DeFi) rate by the supply change over the
+        // current observation window: up while expanding, down while contracting
+        let supply_adjustment_bps = if !fee_waived && !first_transfer_cliff {
+            if clock_available {
+                observe_supply(config, ctx.accounts.mint.supply, now);
+            }
+            supply_adjustment_bps(config, ctx.accounts.mint.supply)
+        } else {
+            0
+        };
+        let tier_fee_bps = apply_supply_adjustment(tier_fee_bps, supply_adjustment_bps);
+
+        // Larger holders pay a balance-based component on top. The hook sees
+        // the post-transfer balance, so add `amount` back for the holding.
+        let balance_fee_bps = if !fee_waived && !first_transfer_cliff {
+            balance_component_bps(config, ctx.accounts.source_token.amount.saturating_add(amount))
+        } else {
+            0
+        };
+        let amount_fee_bps = tier_fee_bps;
+        let tier_fee_bps = tier_fee_bps.saturating_add(balance_fee_bps).min(10000);
+
+        // Surcharge a transfer that sets a new all-time high, capped at 100%
+        let new_high_surcharge = !fee_waived
+            && config.surcharge_new_highs
+            && amount > config.largest_transfer;
+        let fee_bps = if new_high_surcharge {
+            tier_fee_bps.saturating_add(config.new_high_surcharge_bps).min(10000)
+        } else {
+            tier_fee_bps
+        };
 
         // Apply loyalty discount
-        let loyalty_tier = get_loyalty_tier(user_state.transfer_count);
-        let discount_bps = match loyalty_tier {
-            LoyaltyTier::Bronze => 10,  // 0.1% discount
-            LoyaltyTier::Silver => 25,  // 0.25% discount
-            LoyaltyTier::Gold => 50,    // 0.5% discount
-            LoyaltyTier::None => 0,
+        // Stack the discount of a code redeemed with `redeem_discount_code`.
+        // It waits for a transfer it can apply to and lapses at the code's
+        // expiry rather than failing the transfer
+        let mut discount_code_applied = false;
+        let mut code_discount_bps = 0;
+        if user_state.pending_code_discount_bps > 0 {
+            let expires_at = user_state.pending_code_expires_at;
+            if expires_at != 0 && clock_available && now >= expires_at {
+                user_state.pending_code_discount_bps = 0;
+            } else if !first_transfer_cliff
+                && !fee_waived
+                && !event_active
+                && (expires_at == 0 || clock_available)
+            {
+                code_discount_bps = user_state.pending_code_discount_bps;
+                user_state.pending_code_discount_bps = 0;
+                discount_code_applied = true;
+            }
+        }
+
+        let holding_bps = if clock_available {
+            holding_discount_bps(config, user_state, now)
+        } else {
+            0
         };
-        
-        let final_fee = fee_amount.saturating_sub(
-            (amount as u128)
-                .checked_mul(discount_bps)
-                .unwrap()
-                .checked_div(10000)
-                .unwrap() as u64
+        let volume_discount_bps = if user_state.loyalty_opt_out {
+            0
+        } else if config.holding_discount_mode == HOLDING_DISCOUNT_REPLACE {
+            holding_bps
+        } else if config.continuous_discount {
+            continuous_discount_bps(config, user_state.total_volume).saturating_add(holding_bps)
+        } else {
+            loyalty_discount_bps(config, loyalty_tier).saturating_add(holding_bps)
+        };
+        // Sweeping a small balance out entirely earns its own discount
+        let dust_consolidation = !fee_waived
+            && !first_transfer_cliff
+            && is_dust_consolidation(config, amount, ctx.accounts.source_token.amount);
+        let dust_discount_bps = if dust_consolidation {
+            config.dust_consolidation_discount_bps
+        } else {
+            0
+        };
+        let discount_bps = if first_transfer_cliff {
+            0
+        } else if event_active {
+            clamp_total_discount(config, fee_bps, config.active_event.discount_bps)
+        } else {
+            clamp_total_discount(
+                config,
+                fee_bps,
+                volume_discount_bps
+                    .saturating_add(code_discount_bps)
+                    .saturating_add(dust_discount_bps),
+            )
+        };
+
+        let applied_max_fee = effective_max_fee(config.max_fee, user_state.user_max_fee);
+        let FeeBreakdown {
+            base_fee: fee_amount,
+            discount: discount_amount,
+            final_fee,
+        } = finalize_fee(
+            config,
+            compute_final_fee(chargeable_amount, fee_bps, discount_bps)?,
+            amount,
+            applied_max_fee,
         );
 
-        // Update user statistics
-        user_state.transfer_count = user_state.transfer_count.saturating_add(1);
-        user_state.total_volume = user_state.total_volume.saturating_add(amount);
-        user_state.last_transfer_timestamp = Clock::get()?.unix_timestamp;
+        // Owners who opted in pay (part of) the fee out of rebate credit
+        let rebate_applied = if user_state.use_rebate && !fee_waived {
+            if clock_available {
+                forfeit_expired_rebate(user_state, mint, owner, now);
+            }
+            apply_rebate(user_state, final_fee, clock_available)
+        } else {
+            0
+        };
+        let final_fee = final_fee - rebate_applied;
+
+        // Slippage-style guard against a fee change landing after the quote
+        let max_acceptable_fee = user_state.max_acceptable_fee;
+        if max_acceptable_fee > 0 && final_fee > max_acceptable_fee {
+            return reject_transfer(config, mint, owner, amount, ErrorCode::FeeExceedsMax);
+        }
+
+        // Second-token denomination: a balance gate, not a charge. The
+        // sender must hold the converted fee, but nothing is debited
+        let fee_in_fee_token = if config.fee_mint != Pubkey::default() {
+            fee_in_fee_token(config, final_fee, amount, ctx.accounts.mint.decimals)
+        } else {
+            0
+        };
+        if fee_in_fee_token > 0 {
+            let fee_token_account = ctx
+                .accounts
+                .fee_token_account
+                .as_ref()
+                .filter(|account| account.mint == config.fee_mint && account.owner == owner)
+                .ok_or(error!(ErrorCode::InvalidFeeTokenAccount))?;
+            require!(
+                fee_token_account.amount >= fee_in_fee_token,
+                ErrorCode::FeeTokenBalanceGateFailed
+            );
+        }
+
+        // SOL fees: the flat fee plus the token fee converted at the
+        // admin-set rate. Token-2022 invokes the hook with the owner demoted
+        // to a non-signer, so the fee is debited from the owner's prefunded,
+        // program-owned escrow rather than transferred from the owner.
+        let sol_fee = if config.fee_in_sol && !fee_waived {
+            sol_fee(config, final_fee, ctx.accounts.mint.decimals)
+        } else {
+            0
+        };
+        if sol_fee > 0 {
+            let collector = ctx
+                .accounts
+                .sol_fee_collector
+                .as_ref()
+                .filter(|account| account.key() == config.sol_fee_collector)
+                .ok_or(error!(ErrorCode::InvalidSolFeeCollector))?;
+            let escrow_info = ctx
+                .accounts
+                .sol_fee_escrow
+                .as_ref()
+                .ok_or(error!(ErrorCode::SolFeeEscrowMissing))?
+                .to_account_info();
+            let rent = Rent::get()?;
+            require!(
+                escrow_info.lamports()
+                    >= sol_fee.saturating_add(rent.minimum_balance(escrow_info.data_len())),
+                ErrorCode::InsufficientSolBalance
+            );
+            require!(
+                collector.lamports().saturating_add(sol_fee) >= rent.minimum_balance(collector.data_len()),
+                ErrorCode::SolFeeCollectorNotRentExempt
+            );
+
+            **escrow_info.try_borrow_mut_lamports()? -= sol_fee;
+            **collector.to_account_info().try_borrow_mut_lamports()? += sol_fee;
+        }
+
+        // Split the fee between the paying sides. The sender's share is
+        // accounting only (a balance gate); the receiver's share must be
+        // paid to the collector by the next top-level instruction, and this
+        // transfer must be a top-level instruction of its own, so that one
+        // payment cannot settle several transfers
+        let receiver_fee = match config.fee_payer_mode {
+            FEE_PAYER_RECEIVER => final_fee,
+            FEE_PAYER_SPLIT => bps_to_fee(final_fee, config.receiver_fee_share_bps)
+                .ok_or(error!(ErrorCode::ArithmeticOverflow))?,
+            _ => 0,
+        };
+        let sender_fee = final_fee - receiver_fee;
+        if config.fee_payer_mode != FEE_PAYER_SENDER {
+            require!(
+                ctx.accounts.source_token.amount >= sender_fee,
+                ErrorCode::FeeBalanceGateFailed
+            );
+            if receiver_fee > 0 {
+                let receiver_fee_account = ctx
+                    .accounts
+                    .receiver_fee_account
+                    .as_ref()
+                    .ok_or(error!(ErrorCode::ReceiverFeeAccountMissing))?;
+                let sysvar = ctx
+                    .accounts
+                    .instructions_sysvar
+                    .as_ref()
+                    .ok_or(error!(ErrorCode::ReceiverFeeUnpaid))?;
+                let collector_account = get_associated_token_address_with_program_id(
+                    &config.fee_collector,
+                    &mint,
+                    &spl_token_2022::ID,
+                );
+                let sent_directly = paired_transfer_amount(
+                    sysvar,
+                    0,
+                    &mint,
+                    &ctx.accounts.source_token.key(),
+                    &ctx.accounts.destination_token.key(),
+                )? == Some(amount);
+                let paid = paired_transfer_amount(sysvar, 1, &mint, &receiver_fee_account.key(), &collector_account)?;
+                require!(
+                    sent_directly && paid.is_some_and(|paid| paid >= receiver_fee),
+                    ErrorCode::ReceiverFeeUnpaid
+                );
+            }
+        }
+
+        let minter_transfer = is_authorized_minter(config, &ctx.accounts.source_token.owner);
+        let mut tier_up = None;
+        let (transfers_before, volume_before) = (user_state.transfer_count, user_state.total_volume);
+        if !authority_exempt {
+            // Settle pending rewards before the user's volume changes
+            settle_rewards(config, user_state)?;
+
+            // Update user statistics. Opted-out users keep their loyalty
+            // count frozen, and may also keep volume and counterparties private.
+            // Distributions from an authorized minter are not organic
+            // activity: they earn no loyalty and record no user stats.
+            let track_stats = !user_state.stats_opt_out && !minter_transfer;
+            let previous_volume = (user_state.transfer_count > 0).then_some(user_state.total_volume);
+            let previous_tier = user_loyalty_tier(config, user_state);
+            user_state.transfer_count = user_state.transfer_count.saturating_add(1);
+            if user_state.loyalty_opt_out || minter_transfer {
+                user_state.loyalty_count_base = user_state.loyalty_count_base.saturating_add(1);
+            }
+            if let Some(new_tier) = detect_tier_up(config, user_state, previous_tier) {
+                // A bonus needs the clock to start its validity period
+                let bonus = if config.tier_up_bonus && clock_available {
+                    config.tier_up_bonus_amount
+                } else {
+                    0
+                };
+                credit_rebate(config, user_state, mint, owner, bonus, now);
+                tier_up = Some((previous_tier, new_tier, bonus));
+            }
+            if track_stats {
+                user_state.total_volume = user_state.total_volume.saturating_add(amount);
+                user_state.total_fees_paid = user_state.total_fees_paid.saturating_add(sender_fee);
+            }
+            user_state.window_volume = user_state.window_volume.saturating_add(amount);
+            user_state.free_used_this_window = user_state.free_used_this_window.saturating_add(free_amount);
+            if clock_available {
+                user_state.last_transfer_timestamp = now;
+                user_state.last_transfer_slot = slot;
+            }
+            if track_stats
+                && config.track_counterparties
+                && record_counterparty(
+                    &mut user_state.counterparty_filter,
+                    &ctx.accounts.destination_token.owner,
+                )
+            {
+                user_state.distinct_dest_estimate = user_state.distinct_dest_estimate.saturating_add(1);
+            }
+
+            record_global_transfer(config, amount, fee_tier, final_fee, discount_amount, sol_fee);
+            record_recount_flow(config, Some((&*user_state, track_stats)), amount);
+            if clock_available {
+                track_volume_spike(config, mint, amount, now);
+                record_hourly_stats(config, mint, amount, final_fee, now);
+            }
+            if track_stats {
+                record_volume_bucket(config, previous_volume, user_state.total_volume);
+            }
+
+            // Route the rewards share of the fee to volume-weighted holders
+            if track_stats {
+                add_reward_volume(config, user_state, amount)?;
+            }
+            // In escrow mode the fee stays reclaimable for a while and its
+            // shares are routed on release; the owner's next transfer
+            // releases any escrow still open, so only one is ever pending
+            release_escrow(config, user_state)?;
+            if clock_available && config.fee_escrow_seconds > 0 && final_fee > 0 {
+                user_state.escrowed_fee = final_fee;
+                user_state.escrow_expires_at = now.saturating_add(config.fee_escrow_seconds);
+                config.total_fees_escrowed = config.total_fees_escrowed.saturating_add(final_fee);
+            } else {
+                route_fee_shares(config, final_fee)?;
+            }
+        }
 
-        // Update global statistics
-        config.total_transfers = config.total_transfers.saturating_add(1);
-        config.total_volume = config.total_volume.saturating_add(amount);
-        config.total_fees_collected = config.total_fees_collected.saturating_add(final_fee);
+        // Write the opt-in receipt at the user's next receipt nonce
+        // Fill the receipt prepared (with `prepare_receipt`) at the user's
+        // current nonce
+        if config.issue_receipts {
+            let receipt_info = ctx
+                .accounts
+                .fee_receipt
+                .as_ref()
+                .filter(|account| {
+                    account.owner == &crate::ID
+                        && account.key() == receipt_address(&owner, &mint, user_state.receipt_nonce)
+                })
+                .ok_or(error!(ErrorCode::ReceiptAccountMissing))?;
+            let receipt = FeeReceipt {
+                owner,
+                mint,
+                nonce: user_state.receipt_nonce,
+                amount,
+                fee: final_fee,
+                fee_tier: fee_tier as u8,
+                timestamp: now,
+            };
+            let mut receipt_data = receipt_info.try_borrow_mut_data()?;
+            receipt.try_serialize(&mut &mut receipt_data[..])?;
+            user_state.receipt_nonce = user_state.receipt_nonce.saturating_add(1);
+        }
 
         // Log transfer details
-        msg!("🎯 Transfer Hook Executed!");
-        msg!("Amount: {}", amount);
-        msg!("Base Fee ({}bps): {}", fee_bps, fee_amount);
-        msg!("Loyalty Tier: {:?}", loyalty_tier);
-        msg!("Final Fee: {}", final_fee);
-        msg!("User Transfers: {}", user_state.transfer_count);
-        msg!("Global Transfers: {}", config.total_transfers);
+        log_at!(config, LOG_INFO, "🎯 Transfer Hook Executed!");
+        log_at!(config, LOG_INFO, "Amount: {}", amount);
+        log_at!(config, LOG_INFO, "Base Fee ({}bps): {}", fee_bps, fee_amount);
+        log_at!(config, LOG_INFO, "Loyalty Tier: {}", loyalty_tier);
+        log_at!(config, LOG_INFO, "Final Fee: {}", final_fee);
+        log_at!(config, LOG_INFO, "User Transfers: {}", user_state.transfer_count);
+        log_at!(config, LOG_INFO, "Global Transfers: {}", config.total_transfers);
+
+        store_user_state(&user_state_info, user_state)?;
+        if let Some(guard) = ctx.accounts.reentrancy_guard.as_mut() {
+            guard.locked = false;
+        }
+
+        let audit_flag = is_audit_sample(config.audit_sample_rate, slot, now, &owner, amount);
+
+        let use_cpi_events = config.use_cpi_events;
+        let event = TransferExecuted {
+            mint,
+            owner,
+            amount,
+            priced_amount,
+            fee_tier: fee_tier as u8,
+            fee_bps,
+            base_fee: fee_amount,
+            loyalty_tier,
+            discount: discount_amount,
+            free_amount,
+            first_transfer_cliff,
+            final_fee,
+            sender_fee,
+            receiver_fee,
+            cooldown_exempt: user_state.is_cooldown_exempt,
+            decay_pending,
+            new_high_surcharge,
+            mint_authority_transfer,
+            discount_code_applied,
+            max_acceptable_fee,
+            fee_in_fee_token,
+            pause_bypassed,
+            onboarding_free,
+            sol_fee,
+            loyalty_opt_out: user_state.loyalty_opt_out,
+            defi_fee,
+            label: config.label,
+            supply_adjustment_bps,
+            audit_flag,
+            total_discount_bps: discount_bps,
+            dust_consolidation,
+            sink_destination,
+            applied_max_fee,
+            amount_fee_bps,
+            balance_fee_bps,
+            minter_transfer,
+            rebate_applied,
+            tier_gate,
+            event_active,
+            timestamp: now,
+        };
+
+        if use_cpi_events {
+            emit_cpi!(event);
+        } else {
+            emit!(event);
+        }
+
+        if let Some((previous_tier, new_tier, bonus)) = tier_up.filter(|_| config.tier_up_bonus) {
+            let event = TierUp {
+                mint,
+                owner,
+                previous_tier,
+                new_tier,
+                bonus,
+                rebate_balance: user_state.rebate_balance,
+            };
+            if use_cpi_events {
+                emit_cpi!(event);
+            } else {
+                emit!(event);
+            }
+        }
+
+        // One `Milestone` per configured threshold this transfer crossed
+        for (counter, thresholds, before, after) in [
+            (MILESTONE_TRANSFERS, config.milestone_transfers, transfers_before, user_state.transfer_count),
+            (MILESTONE_VOLUME, config.milestone_volumes, volume_before, user_state.total_volume),
+        ] {
+            for threshold in crossed_milestones(&thresholds, before, after) {
+                let event = Milestone {
+                    mint,
+                    owner,
+                    counter,
+                    threshold,
+                    value: after,
+                    timestamp: now,
+                };
+                if use_cpi_events {
+                    emit_cpi!(event);
+                } else {
+                    emit!(event);
+                }
+            }
+        }
 
         Ok(())
     }
 
-    /// Admin: Pause the hook
-    pub fn set_pause(ctx: Context<AdminAction>, paused: bool) -> Result<()> {
+    /// Admin: Pause the hook, optionally only until the `auto_unpause_at`
+    /// timestamp (0 = until manually unpaused). Unpausing clears any schedule.
+    pub fn set_pause(ctx: Context<AdminAction>, paused: bool, auto_unpause_at: i64) -> Result<()> {
+        let auto_unpause_at = if paused { auto_unpause_at } else { 0 };
+        require!(
+            auto_unpause_at == 0 || auto_unpause_at > Clock::get()?.unix_timestamp,
+            ErrorCode::InvalidFeeConfig
+        );
+
         let config = &mut ctx.accounts.hook_config;
         config.is_paused = paused;
+        config.auto_unpause_at = auto_unpause_at;
+        config.auto_paused = false;
+        if !paused {
+            // Start the breaker afresh, or the window that tripped it
+            // would trip it again on the next transfer
+            config.spike_window_volume = 0;
+        }
         
-        msg!("🛑 Hook pause status: {}", paused);
+        log_at!(config, LOG_INFO, "🛑 Hook pause status: {} (auto unpause at {})", paused, auto_unpause_at);
+
+        emit!(PauseChanged {
+            mint: ctx.accounts.mint.key(),
+            paused,
+            auto_unpause_at,
+        });
+
         Ok(())
     }
 
-    /// Admin: Blacklist/whitelist a user
-    pub fn set_blacklist(
-        ctx: Context<SetUserStatus>,
-        blacklisted: bool,
+    /// Admin: Pause the hook automatically once mint-wide volume within a
+    /// `window_seconds` window exceeds `threshold` (0 = disabled), as a
+    /// guard against an exploit draining the mint. Windows are fixed, like
+    /// the per-user daily window: one opens with the first transfer after
+    /// the last expired. The transfer that crosses the threshold still
+    /// completes (a failing transfer could not persist the pause); every
+    /// later one is rejected until the admin lifts it with `set_pause`.
+    /// Legitimate bursts (listings, airdrops, migrations) trip it just the
+    /// same, so size the threshold well above normal peak volume.
+    pub fn set_circuit_breaker(
+        ctx: Context<AdminAction>,
+        threshold: u64,
+        window_seconds: i64,
     ) -> Result<()> {
-        let user_state = &mut ctx.accounts.user_state;
-        user_state.is_blacklisted = blacklisted;
-        
-        msg!("🚫 User {} blacklist status: {}", 
-            ctx.accounts.user.key(), blacklisted);
+        require!(threshold == 0 || window_seconds > 0, ErrorCode::InvalidFeeConfig);
+
+        let config = &mut ctx.accounts.hook_config;
+        config.spike_threshold = threshold;
+        config.spike_window_seconds = window_seconds;
+        config.spike_window_start = 0;
+        config.spike_window_volume = 0;
+
+        log_at!(config, LOG_INFO, "🚨 Circuit breaker: {} per {}s", threshold, window_seconds);
         Ok(())
     }
 
-    /// Admin: Update fee collector
-    pub fn update_fee_collector(
+    /// Admin: Emit an `HourlyStats` event per UTC hour (unix time / 3600)
+    /// summarizing its transfers, volume and fees. Buckets are closed
+    /// lazily: the first transfer of a later hour emits the previous
+    /// bucket and starts a new one, so the latest hour is only reported
+    /// once the next transfer arrives. Hours without transfers emit
+    /// nothing; a gap between `hour_start`s means zero activity. Exempt
+    /// transfers are not counted, as in the global totals.
+    pub fn set_hourly_stats(ctx: Context<AdminAction>, enabled: bool) -> Result<()> {
+        let config = &mut ctx.accounts.hook_config;
+        config.hourly_stats = enabled;
+        config.current_hour = 0;
+        config.hour_transfers = 0;
+        config.hour_volume = 0;
+        config.hour_fees = 0;
+
+        log_at!(config, LOG_INFO, "🕐 Hourly stats: {}", enabled);
+        Ok(())
+    }
+
+    /// Admin: Let transfers whose source or destination owner is the
+    /// authority or fee collector through while paused, e.g. for emergency
+    /// recovery. Off by default so a pause is absolute. While on, a
+    /// compromised authority or collector key can keep moving tokens (to or
+    /// from anyone) during a halt, so only enable it for the recovery itself.
+    pub fn set_allow_admin_during_pause(ctx: Context<AdminAction>, allowed: bool) -> Result<()> {
+        let config = &mut ctx.accounts.hook_config;
+        config.allow_admin_during_pause = allowed;
+
+        log_at!(config, LOG_INFO, "🛟 Admin transfers during pause: {}", allowed);
+        Ok(())
+    }
+
+    /// Admin: Hand the authority to a PDA of `controller_program` (e.g. a
+    /// DAO or multisig program), derived from `authority_seeds` including
+    /// its bump. Admin instructions keep requiring the authority as a
+    /// `Signer`; a PDA can only sign through `invoke_signed` by the program
+    /// it is derived from, so re-deriving it here against the stored
+    /// `authority_program` means every later admin call is a CPI from that
+    /// program. The controller calls admin instructions by CPI with the
+    /// usual accounts, signing for the authority with its seeds (see the
+    /// README), and may call this again to move control elsewhere.
+    pub fn set_program_authority(
         ctx: Context<AdminAction>,
-        new_collector: Pubkey,
+        controller_program: Pubkey,
+        authority_seeds: Vec<Vec<u8>>,
     ) -> Result<()> {
+        let authority = program_authority_address(&controller_program, &authority_seeds)?;
+
         let config = &mut ctx.accounts.hook_config;
-        config.fee_collector = new_collector;
-        
-        msg!("💰 Fee collector updated: {}", new_collector);
+        let previous_authority = config.authority;
+        config.authority = authority;
+        config.authority_program = controller_program;
+
+        log_at!(config, LOG_INFO, "🏛️ Authority handed to {} of program {}", authority, controller_program);
+
+        emit!(ProgramAuthoritySet {
+            mint: ctx.accounts.mint.key(),
+            previous_authority,
+            authority,
+            authority_program: controller_program,
+        });
+
         Ok(())
     }
-}
 
-/// Calculate fee tier based on transfer amount
-fn calculate_fee_tier(amount: u64) -> u16 {
-    if amount < TIER_1_THRESHOLD {
-        TIER_1_FEE_BPS
-    } else if amount < TIER_2_THRESHOLD {
-        TIER_2_FEE_BPS
-    } else if amount < TIER_3_THRESHOLD {
-        TIER_3_FEE_BPS
-    } else {
-        TIER_4_FEE_BPS
+    /// Admin: Record the program's current deployment (the program data
+    /// `slot`, bumped by every upgrade) as the one this config trusts, or
+    /// with `enforce = false` stop checking. While enforced, every
+    /// `AdminAction` instruction must pass the program data and fails with
+    /// `UpgradeNotAcknowledged` once the program has been redeployed, until
+    /// the authority reviews the new build and acknowledges it here. An
+    /// upgrade therefore cannot quietly drive the config through the
+    /// existing admin flow. Transfers are not affected.
+    pub fn acknowledge_upgrade(ctx: Context<AcknowledgeUpgrade>, enforce: bool) -> Result<()> {
+        let deploy_slot = ctx.accounts.program_data.slot;
+
+        let config = &mut ctx.accounts.hook_config;
+        let previous_slot = config.acknowledged_deploy_slot;
+        config.acknowledged_deploy_slot = if enforce { deploy_slot } else { 0 };
+
+        log_at!(config, LOG_INFO, "🆙 Acknowledged deployment: slot {}", config.acknowledged_deploy_slot);
+
+        emit!(UpgradeAcknowledged {
+            mint: ctx.accounts.mint.key(),
+            previous_slot,
+            deploy_slot: config.acknowledged_deploy_slot,
+        });
+
+        Ok(())
     }
-}
 
-/// Determine loyalty tier based on transfer count
-fn get_loyalty_tier(transfer_count: u64) -> LoyaltyTier {
-    if transfer_count >= LOYALTY_GOLD {
-        LoyaltyTier::Gold
-    } else if transfer_count >= LOYALTY_SILVER {
-        LoyaltyTier::Silver
-    } else if transfer_count >= LOYALTY_BRONZE {
-        LoyaltyTier::Bronze
-    } else {
-        LoyaltyTier::None
+    /// Admin: Let transfers through when the owner's user state has not been
+    /// created, instead of failing them. Such transfers only
+    /// get the mint-wide rules: the reentrancy guard, pause, whitelist mode
+    /// (which fails closed), collector block, memo and max-balance checks,
+    /// the plain tier fee and global statistics. **This weakens per-user
+    /// controls:** anyone who leaves their user state out skips their
+    /// blacklist and directional blocks, transfer caps, cooldowns, account
+    /// age and fee-slippage guards, and the receipt, SOL and second-token
+    /// fees. Off by default; only enable it for integrations that need it.
+    /// Receipts and linked roots resolve accounts from the user state, so
+    /// they must be off.
+    pub fn set_allow_stateless(ctx: Context<AdminAction>, allowed: bool) -> Result<()> {
+        let config = &mut ctx.accounts.hook_config;
+        require!(
+            !allowed || (!config.issue_receipts && !config.linked_roots_enabled),
+            ErrorCode::StatelessConflict
+        );
+        config.allow_stateless = allowed;
+
+        log_at!(config, LOG_INFO, "🫥 Stateless transfers allowed: {}", allowed);
+        Ok(())
     }
-}
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum LoyaltyTier {
-    None,
-    Bronze,
-    Silver,
-    Gold,
-}
+    /// Admin: Choose which side of a transfer pays the fee. In Receiver or
+    /// Split mode the receiver's share is debited from their associated
+    /// token account: the hook cannot move the hooked mint itself, so the
+    /// transfer must be sent as its own top-level instruction and directly
+    /// followed by a transfer of at least that share from the receiver's
+    /// account to the fee collector's associated token account, signed by
+    /// the receiver. Such fee payments (and any other transfer to the
+    /// collector in these modes) are fee-free and pass
+    /// `block_collector_transfers`; they need the receiver's user state.
+    /// The sender's share stays accounting-only like every fee in Sender
+    /// mode, with the source account gated on holding it. Trust: the
+    /// receiver co-signs every transfer they pay for, so only enable these
+    /// for flows where receivers have agreed to pay (e.g. B2B settlement).
+    /// `receiver_share_bps` is the receiver's share of the fee in Split mode.
+    pub fn set_fee_payer_mode(
+        ctx: Context<AdminAction>,
+        mode: u8,
+        receiver_share_bps: u16,
+    ) -> Result<()> {
+        require!(mode <= FEE_PAYER_SPLIT, ErrorCode::InvalidFeeConfig);
+        require!(receiver_share_bps <= 10000, ErrorCode::InvalidFeeConfig);
 
-// ============================================================================
-// Account Structures
-// ============================================================================
+        let config = &mut ctx.accounts.hook_config;
+        require_fee_change_allowed(config, fee_payer_mode_is_decrease(config, mode, receiver_share_bps))?;
+        config.fee_payer_mode = mode;
+        config.receiver_fee_share_bps = receiver_share_bps;
 
-#[derive(Accounts)]
-pub struct Initialize<'info> {
-    #[account(
-        init,
-        payer = authority,
-        space = 8 + HookConfig::INIT_SPACE,
-        seeds = [b"hook-config", mint.key().as_ref()],
-        bump
-    )]
-    pub hook_config: Account<'info, HookConfig>,
-    
-    pub mint: InterfaceAccount<'info, Mint>,
-    
-    #[account(mut)]
-    pub authority: Signer<'info>,
-    
-    pub system_program: Program<'info, System>,
-}
+        log_at!(config, LOG_INFO, "💳 Fee payer mode: {} (receiver share {}bps)", mode, receiver_share_bps);
+        refresh_schedule_hash(config, ctx.accounts.mint.key())?;
+        Ok(())
+    }
 
-#[derive(Accounts)]
-pub struct InitializeExtraAccountMetaList<'info> {
-    #[account(mut)]
-    pub payer: Signer<'info>,
-    
-    /// CHECK: Extra account meta list account
-    #[account(mut)]
-    pub extra_account_meta_list: UncheckedAccount<'info>,
-    
-    pub mint: InterfaceAccount<'info, Mint>,
-    
-    pub system_program: Program<'info, System>,
-}
+    /// Admin: Set the `msg!` verbosity (LOG_NONE..=LOG_DEBUG)
+    pub fn set_log_level(ctx: Context<AdminAction>, level: u8) -> Result<()> {
+        require!(level <= LOG_DEBUG, ErrorCode::InvalidFeeConfig);
 
-#[derive(Accounts)]
-pub struct TransferHook<'info> {
-    pub source_token: InterfaceAccount<'info, TokenAccount>,
-    pub mint: InterfaceAccount<'info, Mint>,
-    pub destination_token: InterfaceAccount<'info, TokenAccount>,
-    
-    /// CHECK: Source token account owner
-    #[account(mut)]
-    pub owner: Signer<'info>,
-    
-    #[account(
-        mut,
-        seeds = [b"hook-config", mint.key().as_ref()],
-        bump
-    )]
-    pub hook_config: Account<'info, HookConfig>,
-    
-    #[account(
-        init_if_needed,
-        payer = owner,
-        space = 8 + UserState::INIT_SPACE,
-        seeds = [b"user-state", owner.key().as_ref(), mint.key().as_ref()],
-        bump
-    )]
-    pub user_state: Account<'info, UserState>,
-    
-    pub system_program: Program<'info, System>,
-}
+        let config = &mut ctx.accounts.hook_config;
+        config.log_level = level;
 
-#[derive(Accounts)]
-pub struct AdminAction<'info> {
-    #[account(
-        mut,
-        seeds = [b"hook-config", mint.key().as_ref()],
-        bump,
-        has_one = authority
-    )]
-    pub hook_config: Account<'info, HookConfig>,
+        log_at!(config, LOG_INFO, "📝 Log level: {}", level);
+        Ok(())
+    }
+
+    /// Admin: Set the fee-free volume each user gets per daily window
+    pub fn set_free_allowance(ctx: Context<AdminAction>, allowance: u64) -> Result<()> {
+        let config = &mut ctx.accounts.hook_config;
+        require_fee_change_allowed(config, free_allowance_is_decrease(config, allowance))?;
+        config.free_allowance_per_period = allowance;
+
+        log_at!(config, LOG_INFO, "🆓 Free allowance per period: {}", allowance);
+        refresh_schedule_hash(config, ctx.accounts.mint.key())?;
+        Ok(())
+    }
+
+    /// Admin: Cap the distinct destination owners each account can send to
+    /// per daily window (0 = unlimited), to curb mass-distribution bots.
+    /// Destinations are tracked in the same kind of Bloom filter as the
+    /// counterparty estimate, reset with the window, so the count can only
+    /// err low: a new destination occasionally passes as a repeat, and
+    /// beyond ~50 distinct destinations increasingly often.
+    pub fn set_max_dests_per_window(ctx: Context<AdminAction>, max_dests: u32) -> Result<()> {
+        let config = &mut ctx.accounts.hook_config;
+        config.max_dests_per_window = max_dests;
+
+        log_at!(config, LOG_INFO, "📤 Max destinations per window: {}", max_dests);
+        Ok(())
+    }
+
+    /// Admin: Cap the number of transfers each account can ever make (0 = unlimited)
+    pub fn set_max_transfers_per_user(ctx: Context<AdminAction>, max_transfers: u64) -> Result<()> {
+        let config = &mut ctx.accounts.hook_config;
+        config.max_transfers_per_user = max_transfers;
+
+        log_at!(config, LOG_INFO, "🎟️ Max transfers per user: {}", max_transfers);
+        Ok(())
+    }
+
+    /// Admin: Set the minimum time between a user's transfers (0 = disabled)
+    pub fn set_cooldown(ctx: Context<AdminAction>, seconds: i64) -> Result<()> {
+        require!(seconds >= 0, ErrorCode::InvalidFeeConfig);
+
+        let config = &mut ctx.accounts.hook_config;
+        config.cooldown_seconds = seconds;
+
+        log_at!(config, LOG_INFO, "⏱️ Transfer cooldown: {}s", seconds);
+        Ok(())
+    }
+
+    /// Admin: Hold each transfer's fee in escrow for `seconds` (0 = off).
+    /// Meant for intents spanning several transactions: a transaction that
+    /// fails reverts its fee record with everything else, but the hook
+    /// cannot know whether a later, separate step of the intent succeeds.
+    /// Lifecycle: the fee is recorded as usual and parked on the owner's
+    /// state; `finalize_escrowed_fee` (callable by anyone, e.g. the
+    /// integrator once the intent completed) releases it to the collector
+    /// and routes its rewards and staking shares; within the window the
+    /// owner may instead `reclaim_escrowed_fee` it as rebate credit. After
+    /// the window it can only be finalized, which the owner's next transfer
+    /// also does. The program cannot verify the intent failed, so a reclaim
+    /// is trusted to the owner: keep the window short.
+    pub fn set_fee_escrow(ctx: Context<AdminAction>, seconds: i64) -> Result<()> {
+        require!(seconds >= 0, ErrorCode::InvalidFeeConfig);
+
+        let config = &mut ctx.accounts.hook_config;
+        config.fee_escrow_seconds = seconds;
+
+        log_at!(config, LOG_INFO, "🔐 Fee escrow window: {}s", seconds);
+        Ok(())
+    }
+
+    /// Release a user's escrowed fee to the collector. Permissionless: it
+    /// only ends the owner's chance to reclaim.
+    pub fn finalize_escrowed_fee(ctx: Context<FinalizeEscrowedFee>) -> Result<()> {
+        let config = &mut ctx.accounts.hook_config;
+        let user_state = &mut ctx.accounts.user_state;
+        let amount = user_state.escrowed_fee;
+        require!(amount > 0, ErrorCode::NoEscrowedFee);
+        release_escrow(config, user_state)?;
+
+        log_at!(config, LOG_INFO, "🔐 Escrowed fee {} of {} finalized", amount, ctx.accounts.user.key());
+
+        emit!(EscrowedFeeSettled {
+            mint: ctx.accounts.mint.key(),
+            user: ctx.accounts.user.key(),
+            amount,
+            reclaimed: false,
+        });
+
+        Ok(())
+    }
+
+    /// Reclaim the owner's escrowed fee as rebate credit while the escrow
+    /// window is open, reversing it from the fee totals
+    pub fn reclaim_escrowed_fee(ctx: Context<ReclaimEscrowedFee>) -> Result<()> {
+        let config = &mut ctx.accounts.hook_config;
+        let user_state = &mut ctx.accounts.user_state;
+        let amount = user_state.escrowed_fee;
+        let now = Clock::get()?.unix_timestamp;
+        require!(amount > 0, ErrorCode::NoEscrowedFee);
+        require!(now < user_state.escrow_expires_at, ErrorCode::EscrowExpired);
+
+        config.total_fees_collected = config.total_fees_collected.saturating_sub(amount);
+        config.total_fees_escrowed = config.total_fees_escrowed.saturating_sub(amount);
+        user_state.total_fees_paid = user_state.total_fees_paid.saturating_sub(amount);
+        credit_rebate(config, user_state, ctx.accounts.mint.key(), ctx.accounts.owner.key(), amount, now);
+        user_state.escrowed_fee = 0;
+        user_state.escrow_expires_at = 0;
+
+        log_at!(config, LOG_INFO, "🔐 Escrowed fee {} reclaimed by {}", amount, ctx.accounts.owner.key());
+
+        emit!(EscrowedFeeSettled {
+            mint: ctx.accounts.mint.key(),
+            user: ctx.accounts.owner.key(),
+            amount,
+            reclaimed: true,
+        });
+
+        Ok(())
+    }
+
+    /// Admin: Adjust the tier bps by supply changes (`sensitivity_bps` = 0
+    /// disables). The supply seen at the start of each `window_seconds`
+    /// window is the reference; a transfer adds `sensitivity_bps` of the
+    /// supply's relative change since then (in bps) to the tier bps, bounded
+    /// to +/- `max_adjustment_bps`, so minting raises fees and burning lowers
+    /// them. This is approximate: the hook only sees the supply when a
+    /// transfer happens, so the reference is taken at the first transfer of
+    /// a window and mints or burns between transfers show up late and
+    /// netted together. While the supply is 0 (before the first mint, or
+    /// after everything is burned) there is no relative change to measure:
+    /// no reference is taken and no adjustment applied.
+    pub fn set_supply_fee_adjustment(
+        ctx: Context<AdminAction>,
+        sensitivity_bps: u16,
+        max_adjustment_bps: u16,
+        window_seconds: i64,
+    ) -> Result<()> {
+        require!(
+            max_adjustment_bps <= 10000 && window_seconds >= 0,
+            ErrorCode::InvalidFeeConfig
+        );
+
+        let config = &mut ctx.accounts.hook_config;
+        // An active adjustment can raise fees, so only switching it off passes
+        require_fee_change_allowed(config, sensitivity_bps == 0)?;
+        config.supply_sensitivity_bps = sensitivity_bps;
+        config.max_supply_adjustment_bps = max_adjustment_bps;
+        config.supply_window_seconds = window_seconds;
+        config.supply_observed_at = 0;
+
+        log_at!(config, LOG_INFO, "📈 Supply fee adjustment: {}bps sensitivity, +/-{}bps over {}s",
+            sensitivity_bps, max_adjustment_bps, window_seconds);
+        refresh_schedule_hash(config, ctx.accounts.mint.key())?;
+        Ok(())
+    }
+
+    /// Admin: Only let senders with at least `min_stake` tokens staked in
+    /// this mint's stake pool transfer (0 = disabled). The sender's
+    /// `StakeAccount` must be passed; it is resolved through the extra
+    /// account metas, so sync them after enabling or disabling this.
+    /// The stake account is this program's own PDA for the sender and mint,
+    /// so no outside stake program is trusted. Exempt mint-authority
+    /// transfers are not gated.
+    pub fn set_min_stake(ctx: Context<AdminAction>, min_stake: u64) -> Result<()> {
+        let config = &mut ctx.accounts.hook_config;
+        config.min_stake = min_stake;
+
+        log_at!(config, LOG_INFO, "🥩 Minimum stake to transfer: {}", min_stake);
+        Ok(())
+    }
+
+    /// Admin: Flag about one in `rate` transfers for audit (0 = none) via
+    /// `audit_flag` on `TransferExecuted`. The pick hashes the slot,
+    /// timestamp, sender and amount: deterministic and predictable, so a
+    /// sender can steer around it. Fine for sampling, not for security.
+    pub fn set_audit_sample_rate(ctx: Context<AdminAction>, rate: u32) -> Result<()> {
+        let config = &mut ctx.accounts.hook_config;
+        config.audit_sample_rate = rate;
+
+        log_at!(config, LOG_INFO, "🔎 Audit sample rate: 1 in {}", rate);
+        Ok(())
+    }
+
+    /// Admin: Require at least `slots` slots between a user's transfers
+    /// (0 = disabled), to deter sandwiching a large transfer. Unlike the
+    /// seconds-based cooldown, which is coarse (timestamps move about once
+    /// per ~400ms slot and may stall), this is slot-precise; there are no
+    /// exemptions, and both can be enabled together, each rejecting on its own.
+    pub fn set_min_slot_spacing(ctx: Context<AdminAction>, slots: u64) -> Result<()> {
+        let config = &mut ctx.accounts.hook_config;
+        config.min_slot_spacing = slots;
+
+        log_at!(config, LOG_INFO, "🥪 Min slot spacing: {}", slots);
+        Ok(())
+    }
+
+    /// Admin: Add `surcharge_bps` to the fee of any transfer larger than
+    /// every transfer seen before it (the very first transfer included)
+    pub fn set_new_high_surcharge(
+        ctx: Context<AdminAction>,
+        enabled: bool,
+        surcharge_bps: u16,
+    ) -> Result<()> {
+        require!(surcharge_bps <= 10000, ErrorCode::InvalidFeeConfig);
+
+        let config = &mut ctx.accounts.hook_config;
+        let effective = |on: bool, bps: u16| if on { bps } else { 0 };
+        require_fee_change_allowed(
+            config,
+            effective(enabled, surcharge_bps)
+                <= effective(config.surcharge_new_highs, config.new_high_surcharge_bps),
+        )?;
+        config.surcharge_new_highs = enabled;
+        config.new_high_surcharge_bps = surcharge_bps;
+
+        log_at!(config, LOG_INFO, "🐋 New-high surcharge: {} ({}bps)", enabled, surcharge_bps);
+        refresh_schedule_hash(config, ctx.accounts.mint.key())?;
+        Ok(())
+    }
+
+    /// Admin: Make transfers to or from the mint authority fee-free and
+    /// exclude them from user and global statistics
+    pub fn set_exempt_mint_authority(ctx: Context<AdminAction>, exempt: bool) -> Result<()> {
+        let config = &mut ctx.accounts.hook_config;
+        require_fee_change_allowed(config, mint_authority_exemption_is_decrease(config, exempt))?;
+        config.exempt_mint_authority = exempt;
+
+        log_at!(config, LOG_INFO, "🏛️ Mint authority exempt: {}", exempt);
+        refresh_schedule_hash(config, ctx.accounts.mint.key())?;
+        Ok(())
+    }
+
+    /// Admin: Write a `FeeReceipt` account for every transfer. Each receipt
+    /// must be created first with `prepare_receipt` and costs its payer rent
+    /// (about 0.0016 SOL) until closed with `close_receipt`, hence opt-in.
+    /// Receipts are resolved from the sender's user state, so stateless
+    /// transfers must be off.
+    pub fn set_issue_receipts(ctx: Context<AdminAction>, enabled: bool) -> Result<()> {
+        let config = &mut ctx.accounts.hook_config;
+        require!(!enabled || !config.allow_stateless, ErrorCode::StatelessConflict);
+        config.issue_receipts = enabled;
+
+        log_at!(config, LOG_INFO, "🧾 Fee receipts: {}", enabled);
+        Ok(())
+    }
+
+    /// Admin: Estimate each user's distinct destinations with a Bloom
+    /// filter. Costs extra compute and a filter write per transfer.
+    pub fn set_track_counterparties(ctx: Context<AdminAction>, enabled: bool) -> Result<()> {
+        let config = &mut ctx.accounts.hook_config;
+        config.track_counterparties = enabled;
+
+        log_at!(config, LOG_INFO, "🕸️ Counterparty tracking: {}", enabled);
+        Ok(())
+    }
+
+    /// Admin: Reject transfers whose transaction carries no SPL Memo
+    /// instruction. Transfers must then pass the instructions sysvar.
+    pub fn set_require_memo(ctx: Context<AdminAction>, required: bool) -> Result<()> {
+        let config = &mut ctx.accounts.hook_config;
+        config.require_memo = required;
+
+        log_at!(config, LOG_INFO, "📝 Memo required: {}", required);
+        Ok(())
+    }
+
+    /// Admin: Set the fee charged on an account's first transfer instead of
+    /// the tier fee (0 = disabled)
+    pub fn set_first_transfer_fee(ctx: Context<AdminAction>, fee_bps: u16) -> Result<()> {
+        require!(fee_bps <= 10000, ErrorCode::InvalidFeeConfig);
+
+        let config = &mut ctx.accounts.hook_config;
+        let mut proposed = current_schedule(config);
+        proposed.first_transfer_fee_bps = fee_bps;
+        require_fee_change_allowed(config, schedule_is_decrease(&current_schedule(config), &proposed))?;
+        config.first_transfer_fee_bps = fee_bps;
+
+        log_at!(config, LOG_INFO, "🚪 First transfer fee: {}bps", fee_bps);
+        refresh_schedule_hash(config, ctx.accounts.mint.key())?;
+        Ok(())
+    }
+
+    /// Admin: Waive all fees for `seconds` after a user's first hooked
+    /// transfer (0 = disabled). The window takes precedence over every other
+    /// fee rule: the first-transfer fee, tier fees, surcharges and discounts
+    /// (so it wins over loyalty), and leaves the free allowance untouched.
+    pub fn set_onboarding_free_seconds(ctx: Context<AdminAction>, seconds: i64) -> Result<()> {
+        require!(seconds >= 0, ErrorCode::InvalidFeeConfig);
+
+        let config = &mut ctx.accounts.hook_config;
+        require_fee_change_allowed(config, onboarding_window_is_decrease(config, seconds))?;
+        config.onboarding_free_seconds = seconds;
+
+        log_at!(config, LOG_INFO, "🎈 Onboarding fee-free window: {}s", seconds);
+        refresh_schedule_hash(config, ctx.accounts.mint.key())?;
+        Ok(())
+    }
+
+    /// Admin: Charge fees in lamports, debited from the transfer owner's
+    /// SOL fee escrow (see `deposit_sol_fees`) to `collector`:
+    /// `flat_lamports` per transfer plus the final token fee converted at
+    /// `lamports_per_token` (lamports per whole token, 0 = flat fee only).
+    /// Owners without a funded escrow cannot transfer while this is on. The rate is set by the
+    /// authority and never checked against a market price, so holders trust
+    /// it to be kept current. The collector must end up rent-exempt, so
+    /// fund it once before enabling small fees. Token fee figures are still
+    /// computed and recorded as before.
+    pub fn set_fee_in_sol(
+        ctx: Context<AdminAction>,
+        enabled: bool,
+        collector: Pubkey,
+        flat_lamports: u64,
+        lamports_per_token: u64,
+    ) -> Result<()> {
+        require!(!enabled || collector != Pubkey::default(), ErrorCode::InvalidFeeConfig);
+
+        let config = &mut ctx.accounts.hook_config;
+        require_fee_change_allowed(
+            config,
+            !enabled
+                || (config.fee_in_sol
+                    && flat_lamports <= config.sol_fee_per_transfer
+                    && lamports_per_token <= config.lamports_per_token),
+        )?;
+        config.fee_in_sol = enabled;
+        config.sol_fee_collector = collector;
+        config.sol_fee_per_transfer = flat_lamports;
+        config.lamports_per_token = lamports_per_token;
+
+        log_at!(config, LOG_INFO, "◎ SOL fees: {} to {} ({} flat, {} per token)",
+            enabled, collector, flat_lamports, lamports_per_token);
+        refresh_schedule_hash(config, ctx.accounts.mint.key())?;
+        Ok(())
+    }
+
+    /// Prefund the owner's SOL fee escrow, which `transfer_hook` debits for
+    /// SOL fees. Created on the first deposit.
+    pub fn deposit_sol_fees(ctx: Context<ManageSolFeeEscrow>, lamports: u64) -> Result<()> {
+        require!(lamports > 0, ErrorCode::InvalidFeeConfig);
+
+        let escrow = &mut ctx.accounts.sol_fee_escrow;
+        escrow.owner = ctx.accounts.owner.key();
+        escrow.mint = ctx.accounts.mint.key();
+
+        system_program::transfer(
+            CpiContext::new(
+                ctx.accounts.system_program.to_account_info(),
+                system_program::Transfer {
+                    from: ctx.accounts.owner.to_account_info(),
+                    to: ctx.accounts.sol_fee_escrow.to_account_info(),
+                },
+            ),
+            lamports,
+        )?;
+
+        log_at!(ctx.accounts.hook_config, LOG_INFO, "◎ SOL fee escrow funded: {} lamports", lamports);
+        Ok(())
+    }
+
+    /// Withdraw unspent lamports from the owner's SOL fee escrow, down to
+    /// its rent-exempt minimum
+    pub fn withdraw_sol_fees(ctx: Context<ManageSolFeeEscrow>, lamports: u64) -> Result<()> {
+        let escrow_info = ctx.accounts.sol_fee_escrow.to_account_info();
+        let rent_floor = Rent::get()?.minimum_balance(escrow_info.data_len());
+        require!(
+            lamports > 0 && escrow_info.lamports() >= lamports.saturating_add(rent_floor),
+            ErrorCode::InsufficientSolBalance
+        );
+
+        **escrow_info.try_borrow_mut_lamports()? -= lamports;
+        **ctx.accounts.owner.to_account_info().try_borrow_mut_lamports()? += lamports;
+
+        log_at!(ctx.accounts.hook_config, LOG_INFO, "◎ SOL fee escrow withdrawn: {} lamports", lamports);
+        Ok(())
+    }
+
+    /// Admin: Select fee tiers on a user's cumulative volume within
+    /// `seconds` of their window opening rather than on each transfer
+    /// alone (0 = per-transfer pricing). Approximates pricing a chunked
+    /// fill as one order; the bps still applies to each chunk's own amount.
+    pub fn set_aggregation_seconds(ctx: Context<AdminAction>, seconds: i64) -> Result<()> {
+        require!(seconds >= 0, ErrorCode::InvalidFeeConfig);
+
+        let config = &mut ctx.accounts.hook_config;
+        require_fee_change_allowed(config, aggregation_is_decrease(config, seconds))?;
+        config.aggregation_seconds = seconds;
+
+        log_at!(config, LOG_INFO, "🧩 Aggregation window: {}s", seconds);
+        refresh_schedule_hash(config, ctx.accounts.mint.key())?;
+        Ok(())
+    }
+
+    /// Admin: Cap the balance any account may reach by receiving transfers
+    /// (0 = uncapped). Transfers exempted by `exempt_mint_authority` (e.g.
+    /// treasury distribution) are not checked, and balances already above a
+    /// newly lowered cap only stop growing.
+    pub fn set_max_account_balance(ctx: Context<AdminAction>, max_balance: u64) -> Result<()> {
+        let config = &mut ctx.accounts.hook_config;
+        config.max_account_balance = max_balance;
+
+        log_at!(config, LOG_INFO, "⚖️ Max account balance: {}", max_balance);
+        Ok(())
+    }
+
+    /// Admin: Select fee tiers on the interest-accrued (displayed) amount
+    /// of interest-bearing mints. The bps fee itself is still charged on the
+    /// raw amount, since a percentage is scale-invariant; mints without a
+    /// scaling extension always use the raw amount.
+    pub fn set_use_scaled_amount(ctx: Context<AdminAction>, enabled: bool) -> Result<()> {
+        let config = &mut ctx.accounts.hook_config;
+        // Interest can run either way, so the tier it picks may be pricier
+        require_fee_change_allowed(config, enabled == config.use_scaled_amount)?;
+        config.use_scaled_amount = enabled;
+
+        log_at!(config, LOG_INFO, "📈 Scaled amount pricing: {}", enabled);
+        refresh_schedule_hash(config, ctx.accounts.mint.key())?;
+        Ok(())
+    }
+
+    /// Admin: Block transfers to or from the fee collector
+    pub fn set_block_collector_transfers(ctx: Context<AdminAction>, blocked: bool) -> Result<()> {
+        let config = &mut ctx.accounts.hook_config;
+        config.block_collector_transfers = blocked;
+
+        log_at!(config, LOG_INFO, "🚧 Collector transfers blocked: {}", blocked);
+        Ok(())
+    }
+
+    /// Admin: Create the mint's reentrancy guard. Transfers that pass it as
+    /// `reentrancy_guard` lock it for the duration of the hook, so a nested
+    /// invocation within the same transaction is rejected. The lock is
+    /// cleared before the hook returns, so sequential transfers, in the same
+    /// transaction or not, are unaffected.
+    pub fn initialize_reentrancy_guard(ctx: Context<InitializeReentrancyGuard>) -> Result<()> {
+        log_at!(ctx.accounts.hook_config, LOG_INFO, "✅ Reentrancy guard initialized!");
+        Ok(())
+    }
+
+    /// Admin: Reject transfers that do not pass the reentrancy guard
+    /// (list it, writable, in the extra account metas first)
+    pub fn set_require_reentrancy_guard(ctx: Context<AdminAction>, required: bool) -> Result<()> {
+        let config = &mut ctx.accounts.hook_config;
+        config.require_reentrancy_guard = required;
+
+        log_at!(config, LOG_INFO, "🔒 Reentrancy guard required: {}", required);
+        Ok(())
+    }
+
+    /// Admin: Fail transfers when the Clock sysvar cannot be read. Off (the
+    /// default), the hook runs without it and time reads as 0: the account
+    /// age, cooldown, slot spacing, loyalty decay, daily window rollover,
+    /// destination fan-out limit, onboarding window, scaled-amount and aggregate pricing, launch ramp,
+    /// supply observation, holding discount, tier-up bonus, expiring
+    /// discount codes, fee escrow, circuit breaker and hourly stats are all
+    /// skipped, and a scheduled pause stays in force.
+    pub fn set_require_clock(ctx: Context<AdminAction>, required: bool) -> Result<()> {
+        let config = &mut ctx.accounts.hook_config;
+        config.require_clock = required;
+
+        log_at!(config, LOG_INFO, "🕰️ Clock required: {}", required);
+        Ok(())
+    }
+
+    /// Admin: Emit `TransferExecuted` through a self-CPI (`emit_cpi!`) so it
+    /// survives log truncation; costs an extra invoke per transfer
+    pub fn set_use_cpi_events(ctx: Context<AdminAction>, enabled: bool) -> Result<()> {
+        let config = &mut ctx.accounts.hook_config;
+        config.use_cpi_events = enabled;
+
+        log_at!(config, LOG_INFO, "📡 CPI events: {}", enabled);
+        Ok(())
+    }
+
+    /// Admin: Round final fees to a multiple of `granularity` base units
+    /// (0 or 1 = no rounding) using `FEE_ROUND_NEAREST`, `_DOWN` or `_UP`
+    pub fn set_fee_rounding(ctx: Context<AdminAction>, granularity: u64, mode: u8) -> Result<()> {
+        require!(mode <= FEE_ROUND_UP, ErrorCode::InvalidFeeConfig);
+
+        let config = &mut ctx.accounts.hook_config;
+        require_fee_change_allowed(config, granularity <= 1 || mode == FEE_ROUND_DOWN)?;
+        config.fee_granularity = granularity;
+        config.fee_rounding_mode = mode;
+
+        log_at!(config, LOG_INFO, "🔢 Fee granularity: {} (mode {})", granularity, mode);
+        refresh_schedule_hash(config, ctx.accounts.mint.key())?;
+        Ok(())
+    }
+
+    /// Admin: Denominate fees in a second token, `fee_mint` (the default
+    /// pubkey turns this off). `rate` is the fee-token base units one whole
+    /// token of this mint is worth, so the two mints' decimals may differ.
+    /// Converted fees are raised to `min_fee_in_fee_token`, which may not
+    /// exceed the converted `max_fee`. Fees are accounting-only, so this is
+    /// a balance gate: the sender's `fee_token_account` must hold the
+    /// converted fee, whatever the fee payer mode, but is never debited.
+    /// That account is the sender's associated token account, derived with
+    /// `fee_token_program` (SPL Token or Token-2022).
+    pub fn set_fee_token(
+        ctx: Context<AdminAction>,
+        fee_mint: Pubkey,
+        fee_token_program: Pubkey,
+        rate: u64,
+        min_fee_in_fee_token: u64,
+    ) -> Result<()> {
+        let enabled = fee_mint != Pubkey::default();
+        require!(
+            !enabled
+                || (rate > 0
+                    && fee_mint != ctx.accounts.mint.key()
+                    && (fee_token_program == spl_token::ID || fee_token_program == spl_token_2022::ID)),
+            ErrorCode::InvalidFeeConfig
+        );
+
+        let config = &mut ctx.accounts.hook_config;
+        require!(
+            config.max_fee == 0
+                || min_fee_in_fee_token <= to_fee_token(config.max_fee, rate, ctx.accounts.mint.decimals),
+            ErrorCode::InvalidFeeConfig
+        );
+        require_fee_change_allowed(
+            config,
+            !enabled
+                || (fee_mint == config.fee_mint
+                    && rate <= config.fee_token_rate
+                    && min_fee_in_fee_token <= config.min_fee_in_fee_token),
+        )?;
+        config.fee_mint = fee_mint;
+        config.fee_token_program = if enabled { fee_token_program } else { Pubkey::default() };
+        config.fee_token_rate = rate;
+        config.min_fee_in_fee_token = min_fee_in_fee_token;
+
+        log_at!(config, LOG_INFO, "💱 Fee token: {} at {} per token (min fee {})",
+            fee_mint, rate, min_fee_in_fee_token);
+        refresh_schedule_hash(config, ctx.accounts.mint.key())?;
+        Ok(())
+    }
+
+    /// Admin: Set the minimum age of a user state before it may transfer.
+    /// Age counts from `user_state.user_created_at`, set when
+    /// `initialize_user_state` creates the state.
+    pub fn set_min_account_age(ctx: Context<AdminAction>, seconds: i64) -> Result<()> {
+        require!(seconds >= 0, ErrorCode::InvalidFeeConfig);
+
+        let config = &mut ctx.accounts.hook_config;
+        config.min_account_age_seconds = seconds;
+
+        log_at!(config, LOG_INFO, "⏳ Minimum account age: {}s", seconds);
+        Ok(())
+    }
+
+    /// Pre-create a user's state ahead of their first transfer.
+    /// `payer` may be any sponsor (e.g. the fee collector), so an owner
+    /// without SOL for rent can still make their first transfer.
+    pub fn initialize_user_state(ctx: Context<InitializeUserState>) -> Result<()> {
+        let user_state = &mut ctx.accounts.user_state;
+        user_state.owner = ctx.accounts.user.key();
+        user_state.user_created_at = Clock::get()?.unix_timestamp;
+        let config = &mut ctx.accounts.hook_config;
+        config.unique_users = config.unique_users.saturating_add(1);
+
+        log_at!(ctx.accounts.hook_config, LOG_INFO, "✅ User state initialized for {}", user_state.owner);
+        Ok(())
+    }
+
+    /// Set the highest final fee the owner's next transfers may be charged
+    /// (0 = no limit). The hook's instruction data is fixed by the
+    /// transfer-hook interface, so wallets send this ahead of the transfer
+    /// in the same transaction, like slippage protection on a swap.
+    pub fn set_max_acceptable_fee(ctx: Context<SetMaxAcceptableFee>, max_fee: u64) -> Result<()> {
+        ctx.accounts.user_state.max_acceptable_fee = max_fee;
+
+        log_at!(ctx.accounts.hook_config, LOG_DEBUG, "🛡️ Max acceptable fee: {}", max_fee);
+        Ok(())
+    }
+
+    /// Pay transfer fees out of rebate credit first: each transfer takes up
+    /// to its whole fee from `rebate_balance` and charges tokens only for
+    /// the rest. A flag on the user state rather than the hook's instruction
+    /// data, which the transfer-hook interface fixes (see
+    /// `set_max_acceptable_fee`). Credit with an expiry is only spent while
+    /// the clock is readable, so an expired balance is never used.
+    pub fn set_use_rebate(ctx: Context<SetUseRebate>, enabled: bool) -> Result<()> {
+        ctx.accounts.user_state.use_rebate = enabled;
+
+        log_at!(ctx.accounts.hook_config, LOG_DEBUG, "🎟️ Pay fees from rebate: {}", enabled);
+        Ok(())
+    }
+
+    /// Opt out of (or back into) loyalty tracking. While opted out the
+    /// owner's loyalty count stops growing and every transfer is priced at
+    /// the None tier: no loyalty, continuous or holding discount, and no
+    /// tier benefits. `stats_opt_out` additionally stops recording the
+    /// owner's volume, fees paid and counterparties, which also forgoes
+    /// volume rewards. Opting back in resumes from the frozen count.
+    pub fn set_loyalty_opt_out(
+        ctx: Context<SetLoyaltyOptOut>,
+        opt_out: bool,
+        stats_opt_out: bool,
+    ) -> Result<()> {
+        let user_state = &mut ctx.accounts.user_state;
+        user_state.loyalty_opt_out = opt_out;
+        user_state.stats_opt_out = stats_opt_out;
+
+        log_at!(ctx.accounts.hook_config, LOG_DEBUG, "🙈 Loyalty opt-out: {} (stats: {})", opt_out, stats_opt_out);
+        Ok(())
+    }
+
+    /// Create the owner's next fee receipt, for the transfer that fills it.
+    /// The hook has no signer to pay rent, so while `issue_receipts` is on
+    /// every transfer needs one prepared first, usually earlier in the same
+    /// transaction. Any `payer` may sponsor it; closing it refunds the owner.
+    pub fn prepare_receipt(ctx: Context<PrepareReceipt>) -> Result<()> {
+        let receipt = &mut ctx.accounts.fee_receipt;
+        receipt.owner = ctx.accounts.owner.key();
+        receipt.mint = ctx.accounts.mint.key();
+        receipt.nonce = ctx.accounts.user_state.receipt_nonce;
+
+        log_at!(ctx.accounts.hook_config, LOG_DEBUG, "🧾 Receipt {} prepared", receipt.nonce);
+        Ok(())
+    }
+
+    /// Close a fee receipt that is no longer needed, returning its rent
+    pub fn close_receipt(ctx: Context<CloseReceipt>) -> Result<()> {
+        log_at!(ctx.accounts.hook_config, LOG_INFO, "🧾 Receipt {} closed", ctx.accounts.fee_receipt.nonce);
+        Ok(())
+    }
+
+    /// Admin: Blacklist/whitelist a user
+    pub fn set_blacklist(
+        ctx: Context<SetUserStatus>,
+        blacklisted: bool,
+    ) -> Result<()> {
+        let user_state = &mut ctx.accounts.user_state;
+        user_state.is_blacklisted = blacklisted;
+        
+        log_at!(ctx.accounts.hook_config, LOG_INFO, "🚫 User {} blacklist status: {}", 
+            ctx.accounts.user.key(), blacklisted);
+        Ok(())
+    }
+
+    /// Link a user to a root identity whose blacklisting they inherit, or
+    /// unlink them by omitting `root_state`. The authority may set, change
+    /// or clear a link; the owner may only link an unlinked account, so a
+    /// sanctioned entity cannot unlink itself.
+    ///
+    /// Precedence: a linked account is blacklisted if either its own flag
+    /// or its root's is set, so clearing its own flag does not lift the
+    /// root's sanction. Only the blacklist is inherited, and only one level
+    /// deep: a root must not itself be linked, which also rules out cycles.
+    /// Linking needs `linked_roots_enabled`, under which the extra account
+    /// metas pass the root's state to the linked account's transfers.
+    pub fn set_linked_root(ctx: Context<SetLinkedRoot>) -> Result<()> {
+        let signer = ctx.accounts.signer.key();
+        let user = ctx.accounts.user.key();
+        let config = &mut ctx.accounts.hook_config;
+        let user_state = &mut ctx.accounts.user_state;
+        require!(
+            signer == config.authority
+                || (signer == user && user_state.linked_root == Pubkey::default()),
+            ErrorCode::UnauthorizedLink
+        );
+
+        let linked_root = match ctx.accounts.root_state.as_ref() {
+            Some(account) => {
+                let root_state = read_user_state(account, &ctx.accounts.mint.key())?;
+                require!(
+                    root_state.owner != user && root_state.linked_root == Pubkey::default(),
+                    ErrorCode::InvalidLinkedRoot
+                );
+                root_state.owner
+            }
+            None => Pubkey::default(),
+        };
+        let was_linked = user_state.linked_root != Pubkey::default();
+        let linked = linked_root != Pubkey::default();
+        require!(!linked || config.linked_roots_enabled, ErrorCode::LinkedRootsDisabled);
+        if linked && !was_linked {
+            config.linked_user_count = config.linked_user_count.saturating_add(1);
+        } else if was_linked && !linked {
+            config.linked_user_count = config.linked_user_count.saturating_sub(1);
+        }
+        user_state.linked_root = linked_root;
+
+        log_at!(config, LOG_INFO, "🔗 User {} linked to root {}", user, linked_root);
+        Ok(())
+    }
+
+    /// Admin: Allow linking accounts to root identities with
+    /// `set_linked_root`. While on, the extra account metas resolve each
+    /// sender's `linked_root_state` from their user state, so stateless
+    /// transfers must be off; sync the metas after toggling. Disabling is
+    /// refused while any account is still linked.
+    pub fn set_linked_roots_enabled(ctx: Context<AdminAction>, enabled: bool) -> Result<()> {
+        let config = &mut ctx.accounts.hook_config;
+        require!(!enabled || !config.allow_stateless, ErrorCode::StatelessConflict);
+        require!(enabled || config.linked_user_count == 0, ErrorCode::LinkedAccountsRemain);
+        config.linked_roots_enabled = enabled;
+
+        log_at!(config, LOG_INFO, "🔗 Linked roots enabled: {}", enabled);
+        Ok(())
+    }
+
+    /// Admin: Escalate the cooldown for rapid transfers. A transfer less
+    /// than `calm_seconds` after the previous one extends the user's rapid
+    /// streak, and the cooldown becomes `cooldown_seconds * (1 + streak)`,
+    /// capped at `max_cooldown_seconds` (0 = uncapped). One transfer after at
+    /// least `calm_seconds` resets the streak; `calm_seconds` = 0 disables
+    /// escalation.
+    pub fn set_cooldown_escalation(
+        ctx: Context<AdminAction>,
+        calm_seconds: i64,
+        max_cooldown_seconds: i64,
+    ) -> Result<()> {
+        require!(calm_seconds >= 0 && max_cooldown_seconds >= 0, ErrorCode::InvalidFeeConfig);
+
+        let config = &mut ctx.accounts.hook_config;
+        config.cooldown_calm_seconds = calm_seconds;
+        config.max_cooldown_seconds = max_cooldown_seconds;
+
+        log_at!(config, LOG_INFO, "⏱️ Cooldown escalation: calm {}s, max {}s", calm_seconds, max_cooldown_seconds);
+        Ok(())
+    }
+
+    /// Admin: Restrict transfers to whitelisted senders. With `strict_pairs`
+    /// the receiver must be whitelisted too, which needs their user state
+    /// passed as `destination_user_state` on every transfer (list it in the
+    /// extra account metas, and create receivers' states up front).
+    pub fn set_whitelist_mode(
+        ctx: Context<AdminAction>,
+        enabled: bool,
+        strict_pairs: bool,
+    ) -> Result<()> {
+        let config = &mut ctx.accounts.hook_config;
+        config.whitelist_mode = enabled;
+        config.strict_pairs = strict_pairs;
+
+        log_at!(config, LOG_INFO, "📋 Whitelist mode: {}, strict pairs: {}", enabled, strict_pairs);
+        Ok(())
+    }
+
+    /// Admin: Add or remove a user from the whitelist
+    pub fn set_whitelisted(
+        ctx: Context<SetUserStatus>,
+        whitelisted: bool,
+    ) -> Result<()> {
+        let user_state = &mut ctx.accounts.user_state;
+        user_state.is_whitelisted = whitelisted;
+
+        log_at!(ctx.accounts.hook_config, LOG_INFO, "📋 User {} whitelisted: {}",
+            ctx.accounts.user.key(), whitelisted);
+        Ok(())
+    }
+
+    /// Admin: Block a user's outgoing and/or incoming transfers, e.g. to
+    /// freeze withdrawals while still allowing deposits. Incoming blocks
+    /// are only enforced when transfers pass the destination's user state
+    /// as `destination_user_state` (list it in the extra account metas).
+    pub fn set_directional_block(
+        ctx: Context<SetUserStatus>,
+        block_outgoing: bool,
+        block_incoming: bool,
+    ) -> Result<()> {
+        let user_state = &mut ctx.accounts.user_state;
+        user_state.block_outgoing = block_outgoing;
+        user_state.block_incoming = block_incoming;
+
+        log_at!(ctx.accounts.hook_config, LOG_INFO, "↕️ User {} blocks: outgoing {}, incoming {}",
+            ctx.accounts.user.key(), block_outgoing, block_incoming);
+        Ok(())
+    }
+
+    /// Admin: Exempt a user (e.g. a market maker or the treasury) from the
+    /// transfer cooldown. Independent of fees: an exempt user still pays them.
+    pub fn set_cooldown_exempt(
+        ctx: Context<SetUserStatus>,
+        exempt: bool,
+    ) -> Result<()> {
+        let user_state = &mut ctx.accounts.user_state;
+        user_state.is_cooldown_exempt = exempt;
+
+        log_at!(ctx.accounts.hook_config, LOG_INFO, "⏱️ User {} cooldown exempt: {}",
+            ctx.accounts.user.key(), exempt);
+        Ok(())
+    }
+
+    /// Admin: Let owners buy a permanent cooldown exemption for `lamports`
+    /// paid to the fee collector (0 = not for sale)
+    pub fn set_cooldown_exemption_price(ctx: Context<AdminAction>, lamports: u64) -> Result<()> {
+        let config = &mut ctx.accounts.hook_config;
+        require_fee_change_allowed(config, cooldown_exemption_price_is_decrease(config, lamports))?;
+        config.cooldown_exemption_price = lamports;
+
+        log_at!(config, LOG_INFO, "⏱️ Cooldown exemption price: {} lamports", lamports);
+        refresh_schedule_hash(config, ctx.accounts.mint.key())?;
+        Ok(())
+    }
+
+    /// Buy the owner a permanent cooldown exemption, paying
+    /// `cooldown_exemption_price` lamports to the fee collector. Paid in
+    /// SOL, as a token payment would itself run through this hook. Fails
+    /// without charging when the owner is already exempt (bought or
+    /// granted), so it cannot be paid for twice. Fees still apply.
+    pub fn purchase_cooldown_exemption(ctx: Context<PurchaseCooldownExemption>) -> Result<()> {
+        let price = ctx.accounts.hook_config.cooldown_exemption_price;
+        require!(price > 0, ErrorCode::CooldownExemptionNotForSale);
+        require!(!ctx.accounts.user_state.is_cooldown_exempt, ErrorCode::AlreadyCooldownExempt);
+
+        system_program::transfer(
+            CpiContext::new(
+                ctx.accounts.system_program.to_account_info(),
+                system_program::Transfer {
+                    from: ctx.accounts.owner.to_account_info(),
+                    to: ctx.accounts.fee_collector.to_account_info(),
+                },
+            ),
+            price,
+        )?;
+        ctx.accounts.user_state.is_cooldown_exempt = true;
+
+        log_at!(ctx.accounts.hook_config, LOG_INFO, "⏱️ {} bought a cooldown exemption for {} lamports",
+            ctx.accounts.owner.key(), price);
+
+        emit!(CooldownExemptionPurchased {
+            mint: ctx.accounts.mint.key(),
+            owner: ctx.accounts.owner.key(),
+            price,
+        });
+
+        Ok(())
+    }
+
+    /// Admin: Cap the fee on each of a user's transfers at `max_fee`
+    /// (0 = only the global `max_fee` applies), e.g. for a contractual
+    /// ceiling. When both caps are set the lower one applies, so a user
+    /// cap never lifts the global one. Capping happens before granularity
+    /// rounding, like the global cap.
+    pub fn set_user_max_fee(ctx: Context<SetUserStatus>, max_fee: u64) -> Result<()> {
+        let user_state = &mut ctx.accounts.user_state;
+        user_state.user_max_fee = max_fee;
+
+        log_at!(ctx.accounts.hook_config, LOG_INFO, "🧢 User {} max fee: {}",
+            ctx.accounts.user.key(), max_fee);
+        Ok(())
+    }
+
+    /// Admin: Credit `amount` to a user's rebate balance, once, when one of
+    /// their transfers carries them into a higher loyalty tier than they
+    /// have ever held (0 amount or `enabled = false` pays nothing). Tiers
+    /// granted through `seed_loyalty` count as already reached and pay no
+    /// bonus; neither does climbing back after loyalty decay.
+    pub fn set_tier_up_bonus(ctx: Context<AdminAction>, enabled: bool, amount: u64) -> Result<()> {
+        let config = &mut ctx.accounts.hook_config;
+        config.tier_up_bonus = enabled;
+        config.tier_up_bonus_amount = amount;
+
+        log_at!(config, LOG_INFO, "🎉 Tier-up bonus: {} ({})", enabled, amount);
+        Ok(())
+    }
+
+    /// Admin: Set the milestones that emit a `Milestone` event when a
+    /// user's transfer carries their lifetime transfer count or volume
+    /// across one, up to `MAX_MILESTONES` each (empty = none). Crossings
+    /// compare the counters before and after the transfer, so each
+    /// threshold fires once per user, and a transfer jumping several
+    /// volume thresholds emits one event for each. Counters that are not
+    /// updated (exempt transfers, volume of stats-opted-out users) never
+    /// cross. `TransferExecuted` is emitted regardless.
+    pub fn set_milestones(
+        ctx: Context<AdminAction>,
+        transfer_counts: Vec<u64>,
+        volumes: Vec<u64>,
+    ) -> Result<()> {
+        require!(
+            transfer_counts.len() <= MAX_MILESTONES && volumes.len() <= MAX_MILESTONES,
+            ErrorCode::InvalidFeeConfig
+        );
+
+        let config = &mut ctx.accounts.hook_config;
+        config.milestone_transfers = [0; MAX_MILESTONES];
+        config.milestone_transfers[..transfer_counts.len()].copy_from_slice(&transfer_counts);
+        config.milestone_volumes = [0; MAX_MILESTONES];
+        config.milestone_volumes[..volumes.len()].copy_from_slice(&volumes);
+
+        log_at!(config, LOG_INFO, "🏁 Milestones: transfers {:?}, volume {:?}", transfer_counts, volumes);
+        Ok(())
+    }
+
+    /// Admin: Add a wealth-based component to the amount-based tier bps:
+    /// `min(max_bps, balance / balance_per_bp)`, where `balance` is the
+    /// sender's holding before the transfer, so one bp per `balance_per_bp`
+    /// held. The sum is kept within 100% and surcharges and discounts then
+    /// apply as usual. Disabled by default; waived and first-transfer-cliff
+    /// transfers carry no balance component.
+    pub fn set_balance_fee(
+        ctx: Context<AdminAction>,
+        enabled: bool,
+        balance_per_bp: u64,
+        max_bps: u16,
+    ) -> Result<()> {
+        require!(!enabled || balance_per_bp > 0, ErrorCode::InvalidFeeConfig);
+        require!(max_bps <= 10000, ErrorCode::InvalidFeeConfig);
+
+        let config = &mut ctx.accounts.hook_config;
+        require_fee_change_allowed(
+            config,
+            !enabled
+                || (config.balance_fee_enabled
+                    && balance_per_bp >= config.balance_per_bp
+                    && max_bps <= config.max_balance_fee_bps),
+        )?;
+        config.balance_fee_enabled = enabled;
+        config.balance_per_bp = balance_per_bp;
+        config.max_balance_fee_bps = max_bps;
+
+        log_at!(config, LOG_INFO, "🐳 Balance fee: {} ({} per bp, max {}bps)",
+            enabled, balance_per_bp, max_bps);
+        refresh_schedule_hash(config, ctx.accounts.mint.key())?;
+        Ok(())
+    }
+
+    /// Admin: Start fees at `launch_fee_bps` at `launch_timestamp` and ramp
+    /// them linearly to the normal rate over `ramp_seconds` (0 = disabled),
+    /// to deter launch bots. During the ramp the tier (or DeFi) bps is
+    /// replaced by `launch_fee_bps + (tier - launch_fee_bps) * elapsed /
+    /// ramp_seconds`, rounded toward the launch rate; surcharges and
+    /// discounts then apply as usual. Before `launch_timestamp` the full
+    /// launch rate applies; from `launch_timestamp + ramp_seconds` on,
+    /// normal pricing. Waived and first-transfer-cliff transfers are not ramped.
+    pub fn set_launch_ramp(
+        ctx: Context<AdminAction>,
+        launch_timestamp: i64,
+        launch_fee_bps: u16,
+        ramp_seconds: i64,
+    ) -> Result<()> {
+        require!(launch_fee_bps <= 10000 && ramp_seconds >= 0, ErrorCode::InvalidFeeConfig);
+
+        let config = &mut ctx.accounts.hook_config;
+        require_fee_change_allowed(config, ramp_seconds == 0)?;
+        config.launch_timestamp = launch_timestamp;
+        config.launch_fee_bps = launch_fee_bps;
+        config.ramp_seconds = ramp_seconds;
+
+        log_at!(config, LOG_INFO, "🚀 Launch ramp: {}bps from {} over {}s",
+            launch_fee_bps, launch_timestamp, ramp_seconds);
+        refresh_schedule_hash(config, ctx.accounts.mint.key())?;
+        Ok(())
+    }
+
+    /// Admin: Credit a user with `historical_transfers` made before the
+    /// hook was enabled, once per user. Only loyalty tiers see the credit;
+    /// transfer counts, volume and rewards are untouched. The figures come
+    /// from off-chain history, so the authority is trusted to attest them.
+    pub fn seed_loyalty(ctx: Context<SetUserStatus>, historical_transfers: u64) -> Result<()> {
+        let user_state = &mut ctx.accounts.user_state;
+        require!(!user_state.loyalty_seeded, ErrorCode::LoyaltyAlreadySeeded);
+
+        user_state.loyalty_seeded = true;
+        user_state.loyalty_credit = historical_transfers;
+        let now = Clock::get()?.unix_timestamp;
+        let loyalty_tier = refresh_cached_tier(&ctx.accounts.hook_config, user_state, now);
+        user_state.highest_tier_reached = user_state.highest_tier_reached.max(loyalty_tier);
+
+        log_at!(ctx.accounts.hook_config, LOG_INFO, "🌱 Loyalty seeded for {}: {} transfers",
+            ctx.accounts.user.key(), historical_transfers);
+
+        emit!(LoyaltySeeded {
+            mint: ctx.accounts.mint.key(),
+            user: ctx.accounts.user.key(),
+            historical_transfers,
+            loyalty_tier,
+        });
+
+        Ok(())
+    }
+
+    /// Admin: Start a recount of `total_transfers` and `total_volume`,
+    /// discarding any unfinished one. Transfers may continue meanwhile: one
+    /// sent from a state that was already tallied is added to the tallies
+    /// as it happens, and later states carry their own. Flows no user state
+    /// records (stateless transfers, opted-out and minter volume) are kept
+    /// in `untracked_transfers` / `untracked_volume` and added at the end.
+    pub fn recount_begin(ctx: Context<AdminAction>) -> Result<()> {
+        let config = &mut ctx.accounts.hook_config;
+
+        config.recount_session = config.recount_session.saturating_add(1);
+        config.recount_active = true;
+        config.recount_transfers = 0;
+        config.recount_volume = 0;
+        config.recount_users = 0;
+
+        log_at!(config, LOG_INFO, "🧮 Recount {} started", config.recount_session);
+
+        Ok(())
+    }
+
+    /// Admin: Tally the writable user states passed as remaining accounts
+    /// into the open recount. Each is stamped with the session, so a state
+    /// supplied twice in a session is only counted once.
+    pub fn recount_add(ctx: Context<AdminAction>) -> Result<()> {
+        let config = &mut ctx.accounts.hook_config;
+        require!(config.recount_active, ErrorCode::RecountNotActive);
+
+        let mint = ctx.accounts.mint.key();
+        let mut added = 0u64;
+        for account in ctx.remaining_accounts.iter() {
+            let mut state = read_user_state(account, &mint)?;
+            if state.recount_session == config.recount_session {
+                continue;
+            }
+            require!(account.is_writable, ErrorCode::InvalidUserState);
+
+            config.recount_transfers = config.recount_transfers
+                .checked_add(state.transfer_count)
+                .ok_or(error!(ErrorCode::ArithmeticOverflow))?;
+            config.recount_volume = config.recount_volume
+                .checked_add(state.total_volume)
+                .ok_or(error!(ErrorCode::ArithmeticOverflow))?;
+            config.recount_users += 1;
+            added += 1;
+
+            state.recount_session = config.recount_session;
+            let mut data = account.try_borrow_mut_data()?;
+            let mut writer: &mut [u8] = &mut data[..];
+            state.try_serialize(&mut writer)?;
+        }
+
+        log_at!(config, LOG_DEBUG, "🧮 Recount {}: {} of {} states added",
+            config.recount_session, added, ctx.remaining_accounts.len());
+
+        Ok(())
+    }
+
+    /// Admin: Replace `total_transfers` and `total_volume` with the tallies
+    /// of the open recount plus the untracked flows, and close it
+    pub fn recount_finalize(ctx: Context<AdminAction>) -> Result<()> {
+        let config = &mut ctx.accounts.hook_config;
+        require!(config.recount_active, ErrorCode::RecountNotActive);
+
+        let before_transfers = config.total_transfers;
+        let before_volume = config.total_volume;
+        config.total_transfers = config.recount_transfers
+            .checked_add(config.untracked_transfers)
+            .ok_or(error!(ErrorCode::ArithmeticOverflow))?;
+        config.total_volume = config.recount_volume
+            .checked_add(config.untracked_volume)
+            .ok_or(error!(ErrorCode::ArithmeticOverflow))?;
+        config.recount_active = false;
+
+        log_at!(config, LOG_INFO, "🧮 Totals recounted over {} users: {} transfers, {} volume",
+            config.recount_users, config.total_transfers, config.total_volume);
+
+        emit!(TotalsRecounted {
+            mint: ctx.accounts.mint.key(),
+            session: config.recount_session,
+            users_counted: config.recount_users,
+            before_transfers,
+            before_volume,
+            after_transfers: config.total_transfers,
+            after_volume: config.total_volume,
+        });
+
+        Ok(())
+    }
+
+    /// Admin: Refund part of a user's recorded fees as rebate credit,
+    /// e.g. to compensate a failed downstream leg of a composite transaction
+    pub fn refund_fee(ctx: Context<RefundFee>, amount: u64) -> Result<()> {
+        let config = &mut ctx.accounts.hook_config;
+        let user_state = &mut ctx.accounts.user_state;
+
+        require!(
+            amount <= user_state.total_fees_paid && amount <= config.total_fees_collected,
+            ErrorCode::RefundExceedsFees
+        );
+
+        user_state.total_fees_paid -= amount;
+        let now = Clock::get()?.unix_timestamp;
+        credit_rebate(config, user_state, ctx.accounts.mint.key(), ctx.accounts.user.key(), amount, now);
+        config.total_fees_collected -= amount;
+
+        log_at!(config, LOG_INFO, "↩️ Refunded {} to {}", amount, ctx.accounts.user.key());
+
+        emit!(FeeRefunded {
+            mint: ctx.accounts.mint.key(),
+            user: ctx.accounts.user.key(),
+            amount,
+            rebate_balance: user_state.rebate_balance,
+        });
+
+        Ok(())
+    }
+
+    /// Admin: Let rebate credit expire `seconds` after it last grew
+    /// (0 = never). Expiry is rolling: every credit (refund, reclaimed
+    /// escrow, tier-up bonus) pushes the whole balance's expiry out again,
+    /// so it lapses only after a quiet spell. Expired credit is forfeited
+    /// the next time the balance is credited, or by `expire_rebate`, and a
+    /// `RebateForfeited` event records the amount. Changing the validity
+    /// applies from each balance's next credit.
+    pub fn set_rebate_validity(ctx: Context<AdminAction>, seconds: i64) -> Result<()> {
+        require!(seconds >= 0, ErrorCode::InvalidFeeConfig);
+
+        let config = &mut ctx.accounts.hook_config;
+        config.rebate_validity_seconds = seconds;
+
+        log_at!(config, LOG_INFO, "⌛ Rebate validity: {}s", seconds);
+        Ok(())
+    }
+
+    /// Forfeit a user's rebate credit once it has expired. Permissionless,
+    /// so the collector's liability can be brought up to date by anyone.
+    pub fn expire_rebate(ctx: Context<ExpireRebate>) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        let forfeited = forfeit_expired_rebate(
+            &mut ctx.accounts.user_state,
+            ctx.accounts.mint.key(),
+            ctx.accounts.user.key(),
+            now,
+        );
+        require!(forfeited > 0, ErrorCode::RebateNotExpired);
+
+        log_at!(ctx.accounts.hook_config, LOG_INFO, "⌛ Rebate of {} forfeited: {}", ctx.accounts.user.key(), forfeited);
+        Ok(())
+    }
+
+    /// Admin: Award `rate` points per `POINTS_RATE_SCALE` of lifetime
+    /// transfer volume (0 = points off). A new rate only applies to volume
+    /// not yet claimed; volume recorded before points were enabled is
+    /// claimable too. Volume is only recorded for users tracking stats.
+    pub fn set_points_rate(ctx: Context<AdminAction>, rate: u64) -> Result<()> {
+        let config = &mut ctx.accounts.hook_config;
+        config.points_rate = rate;
+
+        log_at!(config, LOG_INFO, "🪙 Points rate: {} per {} volume", rate, POINTS_RATE_SCALE);
+        Ok(())
+    }
+
+    /// Convert the owner's unclaimed volume into points. Only the volume
+    /// that yields whole points is marked claimed (`volume_claimed_for_points`),
+    /// so a remainder carries over to the next claim and no volume is ever
+    /// converted twice.
+    pub fn claim_points(ctx: Context<ManagePoints>) -> Result<()> {
+        let config = &ctx.accounts.hook_config;
+        let user_state = &mut ctx.accounts.user_state;
+        let unclaimed = user_state.total_volume.saturating_sub(user_state.volume_claimed_for_points);
+        let (points, volume_converted) = points_for_volume(config.points_rate, unclaimed)?;
+        require!(points > 0, ErrorCode::NoPointsToClaim);
+
+        user_state.volume_claimed_for_points += volume_converted;
+        user_state.points_balance = user_state.points_balance.saturating_add(points);
+
+        log_at!(config, LOG_INFO, "🪙 {} points claimed for {} volume", points, volume_converted);
+
+        emit!(PointsClaimed {
+            mint: ctx.accounts.mint.key(),
+            owner: ctx.accounts.owner.key(),
+            points,
+            volume_converted,
+            points_balance: user_state.points_balance,
+        });
+
+        Ok(())
+    }
+
+    /// Redeem `points` against fees: each point becomes one base unit of
+    /// rebate credit, under the usual rebate expiry
+    pub fn spend_points(ctx: Context<ManagePoints>, points: u64) -> Result<()> {
+        let config = &ctx.accounts.hook_config;
+        let user_state = &mut ctx.accounts.user_state;
+        require!(points > 0 && points <= user_state.points_balance, ErrorCode::InsufficientPoints);
+
+        user_state.points_balance -= points;
+        let now = Clock::get()?.unix_timestamp;
+        credit_rebate(config, user_state, ctx.accounts.mint.key(), ctx.accounts.owner.key(), points, now);
+
+        log_at!(config, LOG_INFO, "🪙 {} points spent", points);
+
+        emit!(PointsSpent {
+            mint: ctx.accounts.mint.key(),
+            owner: ctx.accounts.owner.key(),
+            points,
+            points_balance: user_state.points_balance,
+            rebate_balance: user_state.rebate_balance,
+        });
+
+        Ok(())
+    }
+
+    /// Admin: Update the primary fee collector (`fee_collectors[0]`),
+    /// keeping its weight
+    pub fn update_fee_collector(
+        ctx: Context<AdminAction>,
+        new_collector: Pubkey,
+    ) -> Result<()> {
+        let config = &mut ctx.accounts.hook_config;
+        set_primary_collector(config, new_collector)?;
+        
+        log_at!(config, LOG_INFO, "💰 Fee collector updated: {}", new_collector);
+        Ok(())
+    }
+
+    /// Admin: Cap this mint's revenue-sharing collector list (at most
+    /// `MAX_FEE_COLLECTORS`, and no fewer than the collectors already listed)
+    pub fn set_max_fee_collectors(ctx: Context<AdminAction>, max_collectors: u8) -> Result<()> {
+        let config = &mut ctx.accounts.hook_config;
+        require!(
+            max_collectors as usize <= MAX_FEE_COLLECTORS
+                && max_collectors >= config.fee_collector_count.max(1),
+            ErrorCode::InvalidFeeConfig
+        );
+        config.max_fee_collectors = max_collectors;
+
+        log_at!(config, LOG_INFO, "👥 Max fee collectors: {}", max_collectors);
+        Ok(())
+    }
+
+    /// Admin: Add a revenue-sharing fee collector. `weights` re-specifies
+    /// every collector's share in list order (the new one last) and must
+    /// sum to 10000.
+    pub fn add_fee_collector(
+        ctx: Context<AdminAction>,
+        collector: Pubkey,
+        weights: Vec<u16>,
+    ) -> Result<()> {
+        let config = &mut ctx.accounts.hook_config;
+        add_collector(config, collector, &weights)?;
+
+        log_at!(config, LOG_INFO, "👥 Fee collector added: {}", collector);
+
+        emit!(FeeCollectorAdded {
+            mint: ctx.accounts.mint.key(),
+            collector,
+            collector_count: config.fee_collector_count,
+        });
+
+        Ok(())
+    }
+
+    /// Admin: Remove a revenue-sharing fee collector. `weights` re-specifies
+    /// the remaining collectors' shares in list order; removing the last one
+    /// falls back to the authority at 10000 and ignores `weights`. Removing
+    /// the primary collector promotes the next one to `fee_collector`.
+    pub fn remove_fee_collector(
+        ctx: Context<AdminAction>,
+        collector: Pubkey,
+        weights: Vec<u16>,
+    ) -> Result<()> {
+        let config = &mut ctx.accounts.hook_config;
+        remove_collector(config, collector, &weights)?;
+
+        log_at!(config, LOG_INFO, "👥 Fee collector removed: {}", collector);
+
+        emit!(FeeCollectorRemoved {
+            mint: ctx.accounts.mint.key(),
+            collector,
+            collector_count: config.fee_collector_count,
+        });
+
+        Ok(())
+    }
+
+    /// Admin: Set the fee on transfers whose source or destination owner is
+    /// a listed DeFi program owner, replacing the tier bps
+    pub fn set_defi_fee(ctx: Context<AdminAction>, fee_bps: u16) -> Result<()> {
+        require!(fee_bps <= 10000, ErrorCode::InvalidFeeConfig);
+
+        let config = &mut ctx.accounts.hook_config;
+        require_fee_change_allowed(config, fee_bps <= config.defi_fee_bps)?;
+        config.defi_fee_bps = fee_bps;
+
+        log_at!(config, LOG_INFO, "🏦 DeFi fee: {}bps", fee_bps);
+        refresh_schedule_hash(config, ctx.accounts.mint.key())?;
+        Ok(())
+    }
+
+    /// Admin: List a token-account owner as program-derived DeFi liquidity.
+    /// The hook only sees the owner pubkey of each token account, not the
+    /// program that derived it, so pools and vaults can't be recognised on
+    /// their own: each PDA owner has to be listed here. Unlisted owners keep
+    /// normal pricing.
+    pub fn add_defi_owner(ctx: Context<AdminAction>, owner: Pubkey) -> Result<()> {
+        let config = &mut ctx.accounts.hook_config;
+        require_fee_change_allowed(config, defi_fee_is_decrease(config, true))?;
+        list_defi_owner(config, owner)?;
+
+        log_at!(config, LOG_INFO, "🏦 DeFi owner added: {}", owner);
+        refresh_schedule_hash(config, ctx.accounts.mint.key())?;
+        Ok(())
+    }
+
+    /// Admin: Return a DeFi owner to normal pricing
+    pub fn remove_defi_owner(ctx: Context<AdminAction>, owner: Pubkey) -> Result<()> {
+        let config = &mut ctx.accounts.hook_config;
+        require_fee_change_allowed(config, defi_fee_is_decrease(config, false))?;
+        unlist_defi_owner(config, owner)?;
+
+        log_at!(config, LOG_INFO, "🏦 DeFi owner removed: {}", owner);
+        refresh_schedule_hash(config, ctx.accounts.mint.key())?;
+        Ok(())
+    }
+
+    /// Admin: Waive the fee on transfers whose destination owner has a
+    /// `FeeSink` (off by default: no destination is exempt). While on, the
+    /// destination owner's sink PDA is resolved through the extra account
+    /// metas, so sync them after toggling this. The sender's side is
+    /// unaffected: sender exemptions are separate.
+    pub fn set_sink_exemption(ctx: Context<AdminAction>, enabled: bool) -> Result<()> {
+        let config = &mut ctx.accounts.hook_config;
+        require_fee_change_allowed(config, enabled)?;
+        config.sink_exemption = enabled;
+
+        log_at!(config, LOG_INFO, "🕳️ Sink fee exemption: {}", enabled);
+        refresh_schedule_hash(config, ctx.accounts.mint.key())?;
+        Ok(())
+    }
+
+    /// Admin: Mark transfers sent by `minter` (e.g. an airdrop distributor)
+    /// as non-organic, up to `MAX_AUTHORIZED_MINTERS` (empty by default:
+    /// every transfer counts). Such transfers still pay fees and count in
+    /// the global totals, which carry fee accounting, but add nothing to the
+    /// sender's loyalty count, volume, counterparties or rewards volume.
+    pub fn add_authorized_minter(ctx: Context<AdminAction>, minter: Pubkey) -> Result<()> {
+        let config = &mut ctx.accounts.hook_config;
+        list_authorized_minter(config, minter)?;
+
+        log_at!(config, LOG_INFO, "🪂 Authorized minter added: {}", minter);
+        Ok(())
+    }
+
+    /// Admin: Count `minter`'s transfers as organic again
+    pub fn remove_authorized_minter(ctx: Context<AdminAction>, minter: Pubkey) -> Result<()> {
+        let config = &mut ctx.accounts.hook_config;
+        unlist_authorized_minter(config, minter)?;
+
+        log_at!(config, LOG_INFO, "🪂 Authorized minter removed: {}", minter);
+        Ok(())
+    }
+
+    /// Admin: List `owner` (e.g. a burn address or protocol treasury) as a
+    /// fee-free destination
+    pub fn add_sink(ctx: Context<AddSink>, owner: Pubkey) -> Result<()> {
+        let sink = &mut ctx.accounts.fee_sink;
+        sink.mint = ctx.accounts.mint.key();
+        sink.owner = owner;
+
+        log_at!(ctx.accounts.hook_config, LOG_INFO, "🕳️ Sink added: {}", owner);
+        Ok(())
+    }
+
+    /// Admin: Delist a fee-free destination, refunding its rent
+    pub fn remove_sink(ctx: Context<RemoveSink>) -> Result<()> {
+        log_at!(ctx.accounts.hook_config, LOG_INFO, "🕳️ Sink removed: {}", ctx.accounts.fee_sink.owner);
+        Ok(())
+    }
+
+    /// Admin: Rotate the fee collector, sweeping the old collector's balance
+    /// to the new collector's token account in the same transaction. This
+    /// program cannot move the hooked mint itself (see
+    /// `paired_transfer_amount`), so the old collector sweeps with a
+    /// hooked `TransferChecked` placed right before this instruction; any
+    /// balance left behind fails the rotation.
+    pub fn rotate_fee_collector(ctx: Context<RotateFeeCollector>) -> Result<()> {
+        let old_collector = ctx.accounts.fee_collector.key();
+        let new_collector = ctx.accounts.new_fee_collector.key();
+        require!(
+            ctx.accounts.old_collector_token.amount == 0,
+            ErrorCode::CollectorBalanceNotSwept
+        );
+        let swept_amount = paired_transfer_amount(
+            &ctx.accounts.instructions_sysvar,
+            -1,
+            &ctx.accounts.mint.key(),
+            &ctx.accounts.old_collector_token.key(),
+            &ctx.accounts.new_collector_token.key(),
+        )?
+        .unwrap_or(0);
+
+        let config = &mut ctx.accounts.hook_config;
+        set_primary_collector(config, new_collector)?;
+
+        log_at!(config, LOG_INFO, "💰 Fee collector rotated: {} -> {}", old_collector, new_collector);
+        log_at!(config, LOG_INFO, "Swept: {}", swept_amount);
+
+        emit!(FeeCollectorRotated {
+            mint: ctx.accounts.mint.key(),
+            old_collector,
+            new_collector,
+            swept_amount,
+        });
+
+        Ok(())
+    }
+
+    /// Admin: Enable or waive fees for a single amount tier (0-3)
+    pub fn set_tier_fee_enabled(
+        ctx: Context<AdminAction>,
+        tier: u8,
+        enabled: bool,
+    ) -> Result<()> {
+        require!((tier as usize) < FEE_TIER_COUNT, ErrorCode::InvalidFeeConfig);
+
+        let config = &mut ctx.accounts.hook_config;
+        require_fee_change_allowed(config, !enabled || config.tier_fees_enabled[tier as usize])?;
+        config.tier_fees_enabled[tier as usize] = enabled;
+
+        log_at!(config, LOG_INFO, "💸 Tier {} fees enabled: {}", tier + 1, enabled);
+        refresh_schedule_hash(config, ctx.accounts.mint.key())?;
+        Ok(())
+    }
+
+    /// Admin: Permanently lock the fee schedule. **This cannot be undone:**
+    /// there is no unlock, and once locked every fee-setting instruction
+    /// (the schedule, loyalty ladder, tier waivers, first-transfer fee,
+    /// new-high surcharge, rounding, second-token and SOL fees, the free
+    /// allowance, onboarding window and mint-authority exemption, loyalty
+    /// decay and the continuous and holding discounts, including through
+    /// governance) fails with `FeeScheduleLocked`. With
+    /// `allow_decrease_when_locked`, changes that can only lower fees are
+    /// still accepted. Calling this again may withdraw that allowance but
+    /// never grant it.
+    pub fn lock_fee_schedule(ctx: Context<AdminAction>, allow_decrease_when_locked: bool) -> Result<()> {
+        let config = &mut ctx.accounts.hook_config;
+        require!(
+            !config.fee_schedule_locked || !allow_decrease_when_locked || config.allow_decrease_when_locked,
+            ErrorCode::FeeScheduleLocked
+        );
+        config.fee_schedule_locked = true;
+        config.allow_decrease_when_locked = allow_decrease_when_locked;
+
+        log_at!(config, LOG_INFO, "🔏 Fee schedule locked (decreases allowed: {})", allow_decrease_when_locked);
+        Ok(())
+    }
+
+    /// Admin: Replace the whole fee and loyalty schedule atomically.
+    /// Everything is validated before anything is written, so the config
+    /// never holds a partially updated schedule.
+    pub fn configure_schedule(ctx: Context<AdminAction>, schedule: FeeSchedule) -> Result<()> {
+        validate_schedule(&schedule)?;
+
+        let config = &mut ctx.accounts.hook_config;
+        require_fee_change_allowed(config, schedule_is_decrease(&current_schedule(config), &schedule))?;
+        apply_schedule(config, &schedule);
+        refresh_schedule_hash(config, ctx.accounts.mint.key())?;
+
+        log_at!(config, LOG_INFO, "📐 Fee schedule configured");
+
+        emit!(ScheduleConfigured {
+            mint: ctx.accounts.mint.key(),
+            schedule_hash: config.schedule_hash,
+            schedule: current_schedule(config),
+        });
+
+        Ok(())
+    }
+
+    /// Admin: Replace the loyalty ladder. Tiers are indexed from 1 in the
+    /// order given; thresholds must be strictly increasing.
+    pub fn set_loyalty_tiers(
+        ctx: Context<AdminAction>,
+        thresholds: Vec<u64>,
+        discount_bps: Vec<u16>,
+    ) -> Result<()> {
+        validate_loyalty_ladder(&thresholds, &discount_bps)?;
+
+        let config = &mut ctx.accounts.hook_config;
+        let count = thresholds.len();
+        let mut proposed = current_schedule(config);
+        proposed.loyalty_tier_count = count as u8;
+        proposed.loyalty_tier_thresholds = [0; MAX_LOYALTY_TIERS];
+        proposed.loyalty_tier_thresholds[..count].copy_from_slice(&thresholds);
+        proposed.loyalty_tier_discount_bps = [0; MAX_LOYALTY_TIERS];
+        proposed.loyalty_tier_discount_bps[..count].copy_from_slice(&discount_bps);
+        require_fee_change_allowed(config, schedule_is_decrease(&current_schedule(config), &proposed))?;
+        apply_schedule(config, &proposed);
+
+        log_at!(config, LOG_INFO, "🏅 Loyalty ladder updated: {} tiers", count);
+        refresh_schedule_hash(config, ctx.accounts.mint.key())?;
+        Ok(())
+    }
+
+    /// Admin: Require `minimums[i]` distinct destinations before loyalty
+    /// tier `i + 1` can be reached (0 = no minimum), so self-transfers or a
+    /// single partner cannot farm tiers. Counts come from
+    /// `distinct_dest_estimate`, an approximation that needs
+    /// `set_track_counterparties` on; without it every gated tier stays
+    /// out of reach. A tier held back is reported as `tier_gate`.
+    pub fn set_min_counterparties(
+        ctx: Context<AdminAction>,
+        minimums: [u32; MAX_LOYALTY_TIERS],
+    ) -> Result<()> {
+        let config = &mut ctx.accounts.hook_config;
+        let is_decrease = minimums
+            .iter()
+            .zip(config.min_counterparties_per_tier.iter())
+            .all(|(new, current)| new <= current);
+        require_fee_change_allowed(config, is_decrease)?;
+        config.min_counterparties_per_tier = minimums;
+
+        log_at!(config, LOG_INFO, "🕸️ Counterparty minimums per tier: {:?}", minimums);
+        refresh_schedule_hash(config, ctx.accounts.mint.key())?;
+        Ok(())
+    }
+
+    /// Admin: Reset a user's loyalty progress when they transfer after a gap
+    /// of at least `decay_seconds` (0 = no decay). The first `grace_transfers`
+    /// gapped transfers only flag `decay_pending` in the event.
+    pub fn set_loyalty_decay(
+        ctx: Context<AdminAction>,
+        decay_seconds: i64,
+        grace_transfers: u64,
+    ) -> Result<()> {
+        require!(decay_seconds >= 0, ErrorCode::InvalidLoyaltyConfig);
+
+        let config = &mut ctx.accounts.hook_config;
+        require_fee_change_allowed(
+            config,
+            loyalty_decay_is_decrease(config, decay_seconds, grace_transfers),
+        )?;
+        config.loyalty_decay_seconds = decay_seconds;
+        config.decay_grace_transfers = grace_transfers;
+
+        log_at!(config, LOG_INFO, "🍂 Loyalty decay: {}s ({} grace transfers)", decay_seconds, grace_transfers);
+        refresh_schedule_hash(config, ctx.accounts.mint.key())?;
+        Ok(())
+    }
+
+    /// Admin: Set the `TIER_BENEFIT_*` mask unlocked by loyalty tier
+    /// `tier` (1-based). Masks are kept when the ladder is replaced.
+    pub fn set_tier_benefits(ctx: Context<AdminAction>, tier: u8, benefits: u8) -> Result<()> {
+        require!(
+            tier >= 1 && tier as usize <= MAX_LOYALTY_TIERS,
+            ErrorCode::InvalidLoyaltyConfig
+        );
+        require!(benefits & !TIER_BENEFIT_ALL == 0, ErrorCode::InvalidLoyaltyConfig);
+
+        let config = &mut ctx.accounts.hook_config;
+        config.tier_benefits[tier as usize - 1] = benefits;
+
+        log_at!(config, LOG_INFO, "🏅 Tier {} benefits: {:#04b}", tier, benefits);
+        Ok(())
+    }
+
+    /// Admin: Replace the loyalty tier discount with a smooth curve,
+    /// `min(max_discount_bps, total_volume / volume_per_bp)`, over the
+    /// sender's lifetime volume before this transfer. Disabled by default.
+    pub fn set_continuous_discount(
+        ctx: Context<AdminAction>,
+        enabled: bool,
+        volume_per_bp: u64,
+        max_discount_bps: u16,
+    ) -> Result<()> {
+        require!(!enabled || volume_per_bp > 0, ErrorCode::InvalidLoyaltyConfig);
+        require!(max_discount_bps <= 10000, ErrorCode::InvalidLoyaltyConfig);
+
+        let config = &mut ctx.accounts.hook_config;
+        require_fee_change_allowed(
+            config,
+            continuous_discount_is_decrease(config, enabled, volume_per_bp, max_discount_bps),
+        )?;
+        config.continuous_discount = enabled;
+        config.volume_per_bp = volume_per_bp;
+        config.max_continuous_discount_bps = max_discount_bps;
+
+        log_at!(config, LOG_INFO, "📈 Continuous discount: {} ({} per bp, max {}bps)",
+            enabled, volume_per_bp, max_discount_bps);
+        refresh_schedule_hash(config, ctx.accounts.mint.key())?;
+        Ok(())
+    }
+
+    /// Admin: Discount `per_day_bps` for every full day since the sender's
+    /// first hooked transfer, up to `max_bps`, stacked on or replacing the
+    /// volume discount per `HOLDING_DISCOUNT_*`
+    pub fn set_holding_discount(
+        ctx: Context<AdminAction>,
+        mode: u8,
+        per_day_bps: u16,
+        max_bps: u16,
+    ) -> Result<()> {
+        require!(mode <= HOLDING_DISCOUNT_REPLACE, ErrorCode::InvalidLoyaltyConfig);
+        require!(max_bps <= 10000, ErrorCode::InvalidLoyaltyConfig);
+
+        let config = &mut ctx.accounts.hook_config;
+        require_fee_change_allowed(
+            config,
+            holding_discount_is_decrease(config, mode, per_day_bps, max_bps),
+        )?;
+        config.holding_discount_mode = mode;
+        config.holding_discount_per_day_bps = per_day_bps;
+        config.max_holding_discount_bps = max_bps;
+
+        log_at!(config, LOG_INFO, "💎 Holding discount: mode {} ({}bps/day, max {}bps)",
+            mode, per_day_bps, max_bps);
+        refresh_schedule_hash(config, ctx.accounts.mint.key())?;
+        Ok(())
+    }
+
+    /// Admin: Cap the stacked discount at `max_bps` (0 = up to the base
+    /// fee). Discounts are summed before any is applied: the loyalty tier
+    /// (or continuous curve) plus the holding-duration discount, or the
+    /// holding discount alone in replace mode, then a discount code's bps,
+    /// then the dust consolidation discount.
+    /// The sum is clamped to this cap and to the fee bps, and only then
+    /// taken off the base fee, so it can never go negative.
+    pub fn set_max_total_discount(ctx: Context<AdminAction>, max_bps: u16) -> Result<()> {
+        require!(max_bps <= 10000, ErrorCode::InvalidFeeConfig);
+
+        let config = &mut ctx.accounts.hook_config;
+        require_fee_change_allowed(
+            config,
+            max_bps == 0
+                || (config.max_total_discount_bps != 0 && max_bps >= config.max_total_discount_bps),
+        )?;
+        config.max_total_discount_bps = max_bps;
+
+        log_at!(config, LOG_INFO, "🧮 Max total discount: {}bps", max_bps);
+        refresh_schedule_hash(config, ctx.accounts.mint.key())?;
+        Ok(())
+    }
+
+    /// Admin: Discount a transfer by `discount_bps` (0 = disabled) when it
+    /// sweeps dust: its raw `amount` is below the tier-1 threshold and it
+    /// leaves the source account empty. The hook runs after Token-2022 has
+    /// moved the tokens, so "empty" means the source balance it sees is 0
+    /// (that is, `amount` was the whole pre-transfer balance). Stacks with
+    /// the other discounts under `max_total_discount_bps`; not applied to
+    /// waived or first-transfer-cliff transfers.
+    pub fn set_dust_consolidation_discount(ctx: Context<AdminAction>, discount_bps: u16) -> Result<()> {
+        require!(discount_bps <= 10000, ErrorCode::InvalidFeeConfig);
+
+        let config = &mut ctx.accounts.hook_config;
+        require_fee_change_allowed(config, discount_bps >= config.dust_consolidation_discount_bps)?;
+        config.dust_consolidation_discount_bps = discount_bps;
+
+        log_at!(config, LOG_INFO, "🧹 Dust consolidation discount: {}bps", discount_bps);
+        refresh_schedule_hash(config, ctx.accounts.mint.key())?;
+        Ok(())
+    }
+
+    /// Admin: Set how long a user's cached loyalty tier stays valid (0 = no cache)
+    pub fn set_tier_cache_seconds(ctx: Context<AdminAction>, seconds: i64) -> Result<()> {
+        require!(seconds >= 0, ErrorCode::InvalidLoyaltyConfig);
+
+        let config = &mut ctx.accounts.hook_config;
+        config.tier_cache_seconds = seconds;
+
+        log_at!(config, LOG_INFO, "🏅 Tier cache: {}s", seconds);
+        Ok(())
+    }
+
+    /// Force recomputation of a user's cached loyalty tier
+    pub fn refresh_tier(ctx: Context<RefreshTier>) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        let tier = refresh_cached_tier(&ctx.accounts.hook_config, &mut ctx.accounts.user_state, now);
+
+        log_at!(ctx.accounts.hook_config, LOG_INFO, "🏅 Tier refreshed: {}", tier);
+        Ok(())
+    }
+
+    /// Store and return the user's approximate volume percentile in bps
+    /// (0-10000 = share of active users with lower lifetime volume).
+    /// Users are bucketed by decimal magnitude, so everyone in the same
+    /// bucket gets the bucket's midpoint rank: the error is at most half of
+    /// that bucket's share of users. Users inactive since before the
+    /// histogram existed are missing from it until their next transfer.
+    pub fn compute_percentile(ctx: Context<ComputePercentile>) -> Result<u16> {
+        let user_state = &mut ctx.accounts.user_state;
+        let percentile_bps = if user_state.transfer_count > 0 {
+            volume_percentile_bps(&ctx.accounts.hook_config.volume_histogram, user_state.total_volume)
+        } else {
+            0
+        };
+        user_state.volume_percentile_bps = percentile_bps;
+
+        log_at!(ctx.accounts.hook_config, LOG_INFO, "📊 Volume percentile: {}bps", percentile_bps);
+        Ok(percentile_bps)
+    }
+
+    /// Admin: Create a campaign discount code worth `discount_bps` off the
+    /// fee for up to `uses` transfers, until `expires_at` (0 = never).
+    /// Users redeem it with `redeem_discount_code`.
+    pub fn create_discount_code(
+        ctx: Context<CreateDiscountCode>,
+        code_hash: [u8; 32],
+        discount_bps: u16,
+        uses: u64,
+        expires_at: i64,
+    ) -> Result<()> {
+        require!(discount_bps <= 10000, ErrorCode::InvalidFeeConfig);
+
+        let code = &mut ctx.accounts.discount_code;
+        code.mint = ctx.accounts.mint.key();
+        code.code_hash = code_hash;
+        code.discount_bps = discount_bps;
+        code.remaining_uses = uses;
+        code.expires_at = expires_at;
+
+        log_at!(ctx.accounts.hook_config, LOG_INFO, "🏷️ Discount code created: {}bps x {}", discount_bps, uses);
+        Ok(())
+    }
+
+    /// Redeem a campaign discount code for the owner's next transfer. The
+    /// transfer-hook interface fixes the hook's instruction data and extra
+    /// accounts, so a code cannot ride along with a transfer: redeeming
+    /// uses it up here and parks its discount on the user state until a
+    /// transfer it applies to (one code at a time).
+    pub fn redeem_discount_code(ctx: Context<RedeemDiscountCode>) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        let code = &mut ctx.accounts.discount_code;
+        let user_state = &mut ctx.accounts.user_state;
+        require!(
+            code.remaining_uses > 0
+                && (code.expires_at == 0 || now < code.expires_at)
+                && user_state.pending_code_discount_bps == 0,
+            ErrorCode::DiscountCodeUnavailable
+        );
+        code.remaining_uses -= 1;
+        user_state.pending_code_discount_bps = code.discount_bps;
+        user_state.pending_code_expires_at = code.expires_at;
+
+        log_at!(ctx.accounts.hook_config, LOG_DEBUG, "🏷️ Discount code redeemed: {}bps", code.discount_bps);
+        Ok(())
+    }
+
+    /// Admin: Create the program-owned rewards vault for the mint
+    pub fn initialize_rewards_vault(ctx: Context<InitializeRewardsVault>) -> Result<()> {
+        log_at!(ctx.accounts.hook_config, LOG_INFO, "✅ Rewards vault initialized!");
+        Ok(())
+    }
+
+    /// Admin: Set the share of each fee owed to the rewards vault. The
+    /// share cannot be routed as fees are collected: the hook never
+    /// collects them (fees are accounting-only, the owner is not a signer,
+    /// and the hook cannot transfer the hooked mint by CPI). It accumulates
+    /// in `unfunded_rewards` instead, until someone (normally the fee
+    /// collector) pays it in with `fund_rewards`; only funded rewards
+    /// become claimable.
+    pub fn set_rewards_bps(ctx: Context<AdminAction>, rewards_bps: u16) -> Result<()> {
+        let config = &mut ctx.accounts.hook_config;
+        require!(
+            rewards_bps as u32 + config.staking_bps as u32 <= 10000,
+            ErrorCode::InvalidFeeConfig
+        );
+        config.rewards_bps = rewards_bps;
+
+        log_at!(config, LOG_INFO, "🎁 Rewards share: {}bps", rewards_bps);
+        Ok(())
+    }
+
+    /// Pay the `amount` the funder's preceding hooked transfer moved into
+    /// the rewards vault out to holders pro rata by claimable volume (see
+    /// `paired_transfer_amount`). Settles `unfunded_rewards` first; any
+    /// excess is a top-up. Must be a top-level instruction.
+    pub fn fund_rewards(ctx: Context<FundRewards>, amount: u64) -> Result<()> {
+        require!(amount > 0, ErrorCode::InvalidRewardsFunding);
+        require_top_level()?;
+        let deposited = paired_transfer_amount(
+            &ctx.accounts.instructions_sysvar,
+            -1,
+            &ctx.accounts.mint.key(),
+            &ctx.accounts.funder_token.key(),
+            &ctx.accounts.rewards_vault.key(),
+        )?;
+        require!(deposited == Some(amount), ErrorCode::PairedTransferMissing);
+
+        let config = &mut ctx.accounts.hook_config;
+        // Nobody could ever claim rewards spread over no volume
+        require!(config.claimable_volume > 0, ErrorCode::InvalidRewardsFunding);
+        accrue_rewards(config, amount)?;
+        config.unfunded_rewards = config.unfunded_rewards.saturating_sub(amount);
+
+        log_at!(config, LOG_INFO, "🎁 Rewards funded: {}", amount);
+
+        emit!(RewardsFunded {
+            mint: ctx.accounts.mint.key(),
+            funder: ctx.accounts.funder.key(),
+            amount,
+            unfunded_rewards: config.unfunded_rewards,
+        });
+
+        Ok(())
+    }
+
+    /// Claim the caller's whole pro-rata share of the rewards vault through
+    /// the owner's following hooked transfer out of it, which must move
+    /// exactly that amount: the owner is approved as the vault's delegate
+    /// for it (see `paired_transfer_amount`). Must be a top-level
+    /// instruction. A share the vault cannot cover is rejected, never
+    /// paid in part.
+    pub fn claim_rewards(ctx: Context<ClaimRewards>) -> Result<()> {
+        settle_rewards(&ctx.accounts.hook_config, &mut ctx.accounts.user_state)?;
+
+        let amount = ctx.accounts.user_state.reward_share;
+        require!(amount > 0, ErrorCode::NothingToClaim);
+        require!(
+            amount <= ctx.accounts.rewards_vault.amount,
+            ErrorCode::RewardsVaultUnderfunded
+        );
+        require_top_level()?;
+        let withdrawn = paired_transfer_amount(
+            &ctx.accounts.instructions_sysvar,
+            1,
+            &ctx.accounts.mint.key(),
+            &ctx.accounts.rewards_vault.key(),
+            &ctx.accounts.owner_token.key(),
+        )?;
+        require!(withdrawn == Some(amount), ErrorCode::PairedTransferMissing);
+
+        let mint_key = ctx.accounts.mint.key();
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            b"hook-config",
+            mint_key.as_ref(),
+            &[ctx.bumps.hook_config],
+        ]];
+
+        approve(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                Approve {
+                    to: ctx.accounts.rewards_vault.to_account_info(),
+                    delegate: ctx.accounts.owner.to_account_info(),
+                    authority: ctx.accounts.hook_config.to_account_info(),
+                },
+                signer_seeds,
+            ),
+            amount,
+        )?;
+
+        let user_state = &mut ctx.accounts.user_state;
+        user_state.reward_share = 0;
+
+        log_at!(ctx.accounts.hook_config, LOG_INFO, "🎁 Rewards claimed: {}", amount);
+
+        emit!(RewardsClaimed {
+            mint: mint_key,
+            owner: ctx.accounts.owner.key(),
+            amount,
+            remaining: user_state.reward_share,
+        });
+
+        Ok(())
+    }
+
+    /// Admin: Create the stake pool and its program-owned vault for the mint.
+    ///
+    /// Staking lifecycle: holders `stake` tokens into the vault and
+    /// `unstake` them back at any time. `staking_bps` of every final fee is
+    /// set aside in `HookConfig::pending_staking_rewards`, and each stake
+    /// pool instruction first syncs it into the pool's reward-per-stake
+    /// accumulator, so it is shared pro rata by the stake held while it was
+    /// earned. Fees earned while nothing is staked are dropped rather than
+    /// handed to the next staker. Rewards are paid out of the vault balance
+    /// above `total_staked`, which the fee collector funds; stakes are never
+    /// used to pay rewards. Rewards settled before an unstake stay
+    /// claimable afterwards, including after a full unstake.
+    ///
+    /// This program cannot move the hooked mint by CPI (see
+    /// `paired_transfer_amount`), so every stake movement pairs with a
+    /// hooked `TransferChecked` signed by the owner in the same
+    /// transaction: `stake` follows the owner's transfer into the vault,
+    /// while `unstake` and `claim_staking_rewards` approve the owner as
+    /// the vault's delegate for the amount and precede the owner's
+    /// transfer out of it. All three must be top-level instructions.
+    pub fn initialize_stake_pool(ctx: Context<InitializeStakePool>) -> Result<()> {
+        let pool = &mut ctx.accounts.stake_pool;
+        pool.mint = ctx.accounts.mint.key();
+
+        log_at!(ctx.accounts.hook_config, LOG_INFO, "✅ Stake pool initialized!");
+        Ok(())
+    }
+
+    /// Admin: Set the share of each fee routed to stakers. Together with
+    /// `rewards_bps` it may not exceed the whole fee.
+    pub fn set_staking_bps(ctx: Context<AdminAction>, staking_bps: u16) -> Result<()> {
+        let config = &mut ctx.accounts.hook_config;
+        require!(
+            staking_bps as u32 + config.rewards_bps as u32 <= 10000,
+            ErrorCode::InvalidFeeConfig
+        );
+        config.staking_bps = staking_bps;
+
+        log_at!(config, LOG_INFO, "🥩 Staking share: {}bps", staking_bps);
+        Ok(())
+    }
+
+    /// Stake the `amount` tokens the owner's preceding transfer moved into
+    /// the pool vault
+    pub fn stake(ctx: Context<ManageStake>, amount: u64) -> Result<()> {
+        require!(amount > 0, ErrorCode::InvalidStakeAmount);
+        require_top_level()?;
+        let deposited = paired_transfer_amount(
+            &ctx.accounts.instructions_sysvar,
+            -1,
+            &ctx.accounts.mint.key(),
+            &ctx.accounts.owner_token.key(),
+            &ctx.accounts.stake_vault.key(),
+        )?;
+        require!(deposited == Some(amount), ErrorCode::PairedTransferMissing);
+
+        let stake_account = &mut ctx.accounts.stake_account;
+        let pool = &mut ctx.accounts.stake_pool;
+        sync_stake_pool(&mut ctx.accounts.hook_config, pool)?;
+        settle_stake(pool, stake_account)?;
+
+        stake_account.owner = ctx.accounts.owner.key();
+        stake_account.amount = stake_account
+            .amount
+            .checked_add(amount)
+            .ok_or(error!(ErrorCode::ArithmeticOverflow))?;
+        stake_account.reward_debt = scaled_rewards(stake_account.amount, pool.reward_per_stake)?;
+        pool.total_staked = pool
+            .total_staked
+            .checked_add(amount)
+            .ok_or(error!(ErrorCode::ArithmeticOverflow))?;
+
+        log_at!(ctx.accounts.hook_config, LOG_INFO, "🥩 Staked: {}", amount);
+
+        emit!(StakeChanged {
+            mint: ctx.accounts.mint.key(),
+            owner: ctx.accounts.owner.key(),
+            amount,
+            staked: true,
+            owner_stake: stake_account.amount,
+            total_staked: pool.total_staked,
+        });
+
+        Ok(())
+    }
+
+    /// Withdraw `amount` staked tokens from the pool vault through the
+    /// owner's following transfer. Rewards earned so far are settled and
+    /// stay claimable.
+    pub fn unstake(ctx: Context<ManageStake>, amount: u64) -> Result<()> {
+        let stake_account = &mut ctx.accounts.stake_account;
+        require!(
+            amount > 0 && amount <= stake_account.amount,
+            ErrorCode::InvalidStakeAmount
+        );
+        require_top_level()?;
+        let withdrawn = paired_transfer_amount(
+            &ctx.accounts.instructions_sysvar,
+            1,
+            &ctx.accounts.mint.key(),
+            &ctx.accounts.stake_vault.key(),
+            &ctx.accounts.owner_token.key(),
+        )?;
+        require!(withdrawn == Some(amount), ErrorCode::PairedTransferMissing);
+
+        let pool = &mut ctx.accounts.stake_pool;
+        sync_stake_pool(&mut ctx.accounts.hook_config, pool)?;
+        settle_stake(pool, stake_account)?;
+
+        stake_account.amount -= amount;
+        stake_account.reward_debt = scaled_rewards(stake_account.amount, pool.reward_per_stake)?;
+        pool.total_staked -= amount;
+
+        let mint_key = ctx.accounts.mint.key();
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            b"hook-config",
+            mint_key.as_ref(),
+            &[ctx.bumps.hook_config],
+        ]];
+
+        approve(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                Approve {
+                    to: ctx.accounts.stake_vault.to_account_info(),
+                    delegate: ctx.accounts.owner.to_account_info(),
+                    authority: ctx.accounts.hook_config.to_account_info(),
+                },
+                signer_seeds,
+            ),
+            amount,
+        )?;
+
+        log_at!(ctx.accounts.hook_config, LOG_INFO, "🥩 Unstaked: {}", amount);
+
+        emit!(StakeChanged {
+            mint: mint_key,
+            owner: ctx.accounts.owner.key(),
+            amount,
+            staked: false,
+            owner_stake: stake_account.amount,
+            total_staked: pool.total_staked,
+        });
+
+        Ok(())
+    }
+
+    /// Claim the caller's staking rewards through the owner's following
+    /// transfer out of the vault, which must move exactly the amount paid:
+    /// at most the vault balance above `total_staked`, any remainder
+    /// staying claimable.
+    pub fn claim_staking_rewards(ctx: Context<ManageStake>) -> Result<()> {
+        let stake_account = &mut ctx.accounts.stake_account;
+        let pool = &mut ctx.accounts.stake_pool;
+        sync_stake_pool(&mut ctx.accounts.hook_config, pool)?;
+        settle_stake(pool, stake_account)?;
+
+        let available = ctx.accounts.stake_vault.amount.saturating_sub(pool.total_staked);
+        let amount = stake_account.pending_rewards.min(available);
+        require!(amount > 0, ErrorCode::NothingToClaim);
+        require_top_level()?;
+        let withdrawn = paired_transfer_amount(
+            &ctx.accounts.instructions_sysvar,
+            1,
+            &ctx.accounts.mint.key(),
+            &ctx.accounts.stake_vault.key(),
+            &ctx.accounts.owner_token.key(),
+        )?;
+        require!(withdrawn == Some(amount), ErrorCode::PairedTransferMissing);
+
+        let mint_key = ctx.accounts.mint.key();
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            b"hook-config",
+            mint_key.as_ref(),
+            &[ctx.bumps.hook_config],
+        ]];
+
+        approve(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                Approve {
+                    to: ctx.accounts.stake_vault.to_account_info(),
+                    delegate: ctx.accounts.owner.to_account_info(),
+                    authority: ctx.accounts.hook_config.to_account_info(),
+                },
+                signer_seeds,
+            ),
+            amount,
+        )?;
+
+        stake_account.pending_rewards -= amount;
+
+        log_at!(ctx.accounts.hook_config, LOG_INFO, "🥩 Staking rewards claimed: {}", amount);
+
+        emit!(StakingRewardsClaimed {
+            mint: mint_key,
+            owner: ctx.accounts.owner.key(),
+            amount,
+            remaining: stake_account.pending_rewards,
+        });
+
+        Ok(())
+    }
+
+    /// Admin: Enable or disable governance proposals and set the voting
+    /// period and quorum. A proposal passes only if the votes cast reach
+    /// `quorum_bps` of the claimable volume (all tracked users' volume)
+    /// when it was created.
+    pub fn set_governance(
+        ctx: Context<AdminAction>,
+        enabled: bool,
+        voting_seconds: i64,
+        quorum_bps: u16,
+    ) -> Result<()> {
+        require!(voting_seconds > 0 && quorum_bps <= 10000, ErrorCode::InvalidFeeConfig);
+
+        let config = &mut ctx.accounts.hook_config;
+        config.governance_enabled = enabled;
+        config.proposal_voting_seconds = voting_seconds;
+        config.governance_quorum_bps = quorum_bps;
+
+        log_at!(config, LOG_INFO, "🗳️ Governance enabled: {} ({}s voting, {}bps quorum)",
+            enabled, voting_seconds, quorum_bps);
+        Ok(())
+    }
+
+    /// Propose a config change, open for voting for `proposal_voting_seconds`
+    pub fn create_proposal(ctx: Context<CreateProposal>, change: ConfigChange) -> Result<()> {
+        let config = &mut ctx.accounts.hook_config;
+        require!(config.governance_enabled, ErrorCode::GovernanceDisabled);
+
+        let proposal = &mut ctx.accounts.proposal;
+        proposal.mint = ctx.accounts.mint.key();
+        proposal.id = config.proposal_count;
+        proposal.proposer = ctx.accounts.proposer.key();
+        proposal.change = change.clone();
+        proposal.voting_ends_at = Clock::get()?
+            .unix_timestamp
+            .saturating_add(config.proposal_voting_seconds);
+        proposal.quorum_votes = bps_to_fee(config.claimable_volume, config.governance_quorum_bps)
+            .ok_or(error!(ErrorCode::ArithmeticOverflow))?;
+
+        config.proposal_count = config.proposal_count.saturating_add(1);
+
+        log_at!(config, LOG_INFO, "🗳️ Proposal {} created: {:?}", proposal.id, change);
+
+        emit!(ProposalCreated {
+            mint: proposal.mint,
+            proposal_id: proposal.id,
+            proposer: proposal.proposer,
+            change,
+            voting_ends_at: proposal.voting_ends_at,
+        });
+
+        Ok(())
+    }
+
+    /// Vote on an open proposal, weighted by the voter's lifetime volume.
+    /// Volume is cheap to inflate with wash transfers, and one entity can
+    /// split volume across many accounts, so this tally is sybil-prone and
+    /// only suits low-stakes parameter tuning. The quorum does not change
+    /// that: inflated volume counts toward it like any other.
+    pub fn cast_vote(ctx: Context<CastVote>, approve: bool) -> Result<()> {
+        let proposal = &mut ctx.accounts.proposal;
+        require!(
+            Clock::get()?.unix_timestamp < proposal.voting_ends_at,
+            ErrorCode::VotingClosed
+        );
+
+        let weight = ctx.accounts.user_state.total_volume;
+        require!(weight > 0, ErrorCode::NoVotingWeight);
+
+        if approve {
+            proposal.yes_votes = proposal.yes_votes.saturating_add(weight);
+        } else {
+            proposal.no_votes = proposal.no_votes.saturating_add(weight);
+        }
+
+        let vote_record = &mut ctx.accounts.vote_record;
+        vote_record.proposal = proposal.key();
+        vote_record.voter = ctx.accounts.voter.key();
+        vote_record.weight = weight;
+        vote_record.approve = approve;
+
+        log_at!(ctx.accounts.hook_config, LOG_INFO, "🗳️ Vote on {}: {} ({})", proposal.id, approve, weight);
+
+        emit!(VoteCast {
+            mint: proposal.mint,
+            proposal_id: proposal.id,
+            voter: vote_record.voter,
+            approve,
+            weight,
+        });
+
+        Ok(())
+    }
+
+    /// Close voting after the deadline, applying the change if yes > no
+    /// and the votes cast reach the proposal's quorum
+    pub fn execute_proposal(ctx: Context<ExecuteProposal>) -> Result<()> {
+        let proposal = &mut ctx.accounts.proposal;
+        require!(!proposal.executed, ErrorCode::ProposalAlreadyExecuted);
+        require!(
+            Clock::get()?.unix_timestamp >= proposal.voting_ends_at,
+            ErrorCode::VotingOpen
+        );
+
+        let config = &mut ctx.accounts.hook_config;
+        require!(config.governance_enabled, ErrorCode::GovernanceDisabled);
+
+        proposal.executed = true;
+        proposal.passed = proposal_passes(proposal.yes_votes, proposal.no_votes, proposal.quorum_votes);
+        if proposal.passed {
+            apply_config_change(config, &proposal.change)?;
+            refresh_schedule_hash(config, proposal.mint)?;
+        }
+
+        log_at!(config, LOG_INFO, "🗳️ Proposal {} passed: {}", proposal.id, proposal.passed);
+
+        emit!(ProposalExecuted {
+            mint: proposal.mint,
+            proposal_id: proposal.id,
+            passed: proposal.passed,
+            yes_votes: proposal.yes_votes,
+            no_votes: proposal.no_votes,
+            quorum_votes: proposal.quorum_votes,
+        });
+
+        Ok(())
+    }
+
+    /// Read: Emit a `ConfigSnapshot` of every numeric config field at the
+    /// current slot, as a reconciliation anchor for off-chain accounting
+    pub fn snapshot_config(ctx: Context<ReadConfig>) -> Result<()> {
+        let config = &ctx.accounts.hook_config;
+        let clock = Clock::get()?;
+
+        emit!(ConfigSnapshot {
+            mint: ctx.accounts.mint.key(),
+            slot: clock.slot,
+            timestamp: clock.unix_timestamp,
+            total_transfers: config.total_transfers,
+            total_volume: config.total_volume,
+            total_fees_collected: config.total_fees_collected,
+            total_discounts_given: config.total_discounts_given,
+            tier_transfer_counts: config.tier_transfer_counts,
+            tier_fees: config.tier_fees,
+            rewards_bps: config.rewards_bps,
+            reward_per_volume: config.reward_per_volume,
+            total_rewards_accrued: config.total_rewards_accrued,
+            loyalty_tier_count: config.loyalty_tier_count,
+            loyalty_tier_thresholds: config.loyalty_tier_thresholds,
+            loyalty_tier_discount_bps: config.loyalty_tier_discount_bps,
+            min_account_age_seconds: config.min_account_age_seconds,
+            fee_payer_mode: config.fee_payer_mode,
+            receiver_fee_share_bps: config.receiver_fee_share_bps,
+            log_level: config.log_level,
+            free_allowance_per_period: config.free_allowance_per_period,
+            tier_cache_seconds: config.tier_cache_seconds,
+            max_transfers_per_user: config.max_transfers_per_user,
+            proposal_voting_seconds: config.proposal_voting_seconds,
+            proposal_count: config.proposal_count,
+            first_transfer_fee_bps: config.first_transfer_fee_bps,
+            fee_tier_thresholds: config.fee_tier_thresholds,
+            fee_tier_bps: config.fee_tier_bps,
+            max_fee: config.max_fee,
+            fee_granularity: config.fee_granularity,
+            fee_rounding_mode: config.fee_rounding_mode,
+            cooldown_seconds: config.cooldown_seconds,
+            loyalty_decay_seconds: config.loyalty_decay_seconds,
+            decay_grace_transfers: config.decay_grace_transfers,
+            largest_transfer: config.largest_transfer,
+            surcharge_new_highs: config.surcharge_new_highs,
+            new_high_surcharge_bps: config.new_high_surcharge_bps,
+            exempt_mint_authority: config.exempt_mint_authority,
+            auto_unpause_at: config.auto_unpause_at,
+            cooldown_calm_seconds: config.cooldown_calm_seconds,
+            max_cooldown_seconds: config.max_cooldown_seconds,
+            tier_benefits: config.tier_benefits,
+            volume_per_bp: config.volume_per_bp,
+            max_continuous_discount_bps: config.max_continuous_discount_bps,
+            holding_discount_mode: config.holding_discount_mode,
+            holding_discount_per_day_bps: config.holding_discount_per_day_bps,
+            max_holding_discount_bps: config.max_holding_discount_bps,
+            fee_token_rate: config.fee_token_rate,
+            min_fee_in_fee_token: config.min_fee_in_fee_token,
+            unique_users: config.unique_users,
+            onboarding_free_seconds: config.onboarding_free_seconds,
+            max_account_balance: config.max_account_balance,
+            sol_fee_per_transfer: config.sol_fee_per_transfer,
+            lamports_per_token: config.lamports_per_token,
+            total_sol_fees_collected: config.total_sol_fees_collected,
+            aggregation_seconds: config.aggregation_seconds,
+            cumulative_fee_bps_weighted: config.cumulative_fee_bps_weighted,
+            defi_fee_bps: config.defi_fee_bps,
+            defi_owner_count: config.defi_owner_count,
+            label: config.label,
+            min_slot_spacing: config.min_slot_spacing,
+            fee_escrow_seconds: config.fee_escrow_seconds,
+            total_fees_escrowed: config.total_fees_escrowed,
+            supply_sensitivity_bps: config.supply_sensitivity_bps,
+            max_supply_adjustment_bps: config.max_supply_adjustment_bps,
+            last_observed_supply: config.last_observed_supply,
+            min_stake: config.min_stake,
+            audit_sample_rate: config.audit_sample_rate,
+            max_total_discount_bps: config.max_total_discount_bps,
+            spike_threshold: config.spike_threshold,
+            spike_window_seconds: config.spike_window_seconds,
+            dust_consolidation_discount_bps: config.dust_consolidation_discount_bps,
+            launch_timestamp: config.launch_timestamp,
+            launch_fee_bps: config.launch_fee_bps,
+            ramp_seconds: config.ramp_seconds,
+            cooldown_exemption_price: config.cooldown_exemption_price,
+            balance_per_bp: config.balance_per_bp,
+            max_balance_fee_bps: config.max_balance_fee_bps,
+            max_dests_per_window: config.max_dests_per_window,
+            unfunded_rewards: config.unfunded_rewards,
+            claimable_volume: config.claimable_volume,
+            linked_user_count: config.linked_user_count,
+            governance_quorum_bps: config.governance_quorum_bps,
+            untracked_transfers: config.untracked_transfers,
+            untracked_volume: config.untracked_volume,
+        });
+
+        Ok(())
+    }
+
+    /// Read: Projected total fees for a new user making `transfers_per_year`
+    /// transfers of `per_transfer_amount`, advancing through the loyalty
+    /// ladder as their count grows. Assumes the current schedule never
+    /// changes and ignores time-based features (free allowance, caches),
+    /// scaled pricing, which side pays and the continuous discount mode;
+    /// includes the first-transfer fee, the max-fee cap and fee rounding.
+    pub fn estimate_annual_fee(
+        ctx: Context<ReadConfig>,
+        per_transfer_amount: u64,
+        transfers_per_year: u64,
+    ) -> Result<u64> {
+        estimate_fees(&ctx.accounts.hook_config, per_transfer_amount, transfers_per_year)
+    }
+
+    /// Read: Emit a `UserStatesRead` summary of the user states passed as
+    /// remaining accounts, in order. Each must be a program-owned
+    /// `UserState` at its PDA for this mint; an invalid account fails the
+    /// read, or is listed in `skipped` when `skip_invalid` is set.
+    pub fn get_user_states(ctx: Context<ReadConfig>, skip_invalid: bool) -> Result<()> {
+        let config = &ctx.accounts.hook_config;
+        let mint = ctx.accounts.mint.key();
+        let mut states = Vec::with_capacity(ctx.remaining_accounts.len());
+        let mut skipped = Vec::new();
+
+        for account in ctx.remaining_accounts.iter() {
+            match read_user_state(account, &mint) {
+                Ok(state) => states.push(UserStateSummary {
+                    owner: state.owner,
+                    transfer_count: state.transfer_count,
+                    total_volume: state.total_volume,
+                    total_fees_paid: state.total_fees_paid,
+                    loyalty_tier: user_loyalty_tier(config, &state),
+                    is_blacklisted: state.is_blacklisted,
+                    last_transfer_timestamp: state.last_transfer_timestamp,
+                }),
+                Err(_) if skip_invalid => skipped.push(account.key()),
+                Err(err) => return Err(err),
+            }
+        }
+
+        emit!(UserStatesRead { mint, states, skipped });
+
+        Ok(())
+    }
+
+    /// Read: Export the user state passed as the first remaining account as
+    /// a compact blob in the transaction's return data, for other programs
+    /// and off-chain consumers. The runtime attributes return data to this
+    /// program's id, which together with the embedded mint is its
+    /// provenance; the blob itself is not signed. Layout (little-endian):
+    ///
+    /// | Offset | Size | Field |
+    /// |--------|------|-------|
+    /// | 0 | 1 | version (`USER_STATE_EXPORT_VERSION`) |
+    /// | 1 | 32 | mint |
+    /// | 33 | 32 | owner |
+    /// | 65 | 8 | transfer_count |
+    /// | 73 | 8 | total_volume |
+    /// | 81 | 8 | total_fees_paid |
+    /// | 89 | 1 | loyalty tier |
+    /// | 90 | 1 | flags (`EXPORT_FLAG_*`) |
+    /// | 91 | 8 | first_transfer_timestamp |
+    /// | 99 | 8 | last_transfer_timestamp |
+    /// | 107 | 8 | export timestamp |
+    ///
+    /// A published version's layout never changes; any change ships under
+    /// a new version byte, so consumers should reject versions they don't know.
+    pub fn export_user_state(ctx: Context<ReadConfig>) -> Result<()> {
+        let account = ctx.remaining_accounts.first().ok_or(error!(ErrorCode::InvalidUserState))?;
+        let state = read_user_state(account, &ctx.accounts.mint.key())?;
+        let loyalty_tier = user_loyalty_tier(&ctx.accounts.hook_config, &state);
+
+        let blob = encode_user_state_export(
+            &ctx.accounts.mint.key(),
+            &state,
+            loyalty_tier,
+            Clock::get()?.unix_timestamp,
+        );
+        set_return_data(&blob);
+
+        Ok(())
+    }
+
+    /// Read: Dry-run a proposed config change through the same validation
+    /// `execute_proposal` applies, without writing anything. Returns whether
+    /// it passes and, if not, the error number of the first failing check.
+    pub fn validate_config(ctx: Context<ReadConfig>, proposed: ConfigChange) -> Result<ConfigValidation> {
+        Ok(dry_run_config_change(&ctx.accounts.hook_config, &proposed))
+    }
+
+    /// Read: Rent-exempt lamports locked in this mint's user states, from
+    /// `unique_users` and the current rent for a `UserState`
+    pub fn rent_estimate(ctx: Context<ReadConfig>) -> Result<u64> {
+        Ok(rent_liability(&Rent::get()?, ctx.accounts.hook_config.unique_users))
+    }
+
+    /// Read: Volume-weighted average effective fee in bps, scaled by
+    /// `AVERAGE_FEE_BPS_PRECISION`: `cumulative_fee_bps_weighted /
+    /// total_volume`. The weight is the fee actually charged, after
+    /// discounts, caps and rounding, so this is the realized take rate.
+    pub fn get_average_fee_bps(ctx: Context<ReadConfig>) -> Result<u64> {
+        let config = &ctx.accounts.hook_config;
+        Ok(average_fee_bps(config.cumulative_fee_bps_weighted, config.total_volume))
+    }
+
+    /// Read: Split `amount` (e.g. the fees collected over a period) between
+    /// the listed fee collectors by weight, for revenue-sharing payouts
+    pub fn get_collector_shares(ctx: Context<ReadConfig>, amount: u64) -> Result<Vec<CollectorShare>> {
+        collector_shares(&ctx.accounts.hook_config, amount)
+    }
+
+    /// Read: Return the global statistics of the hook
+    pub fn get_config_stats(ctx: Context<ReadConfig>) -> Result<ConfigStats> {
+        let config = &ctx.accounts.hook_config;
+
+        Ok(ConfigStats {
+            total_transfers: config.total_transfers,
+            total_volume: config.total_volume,
+            total_fees_collected: config.total_fees_collected,
+            total_discounts_given: config.total_discounts_given,
+            tier_transfer_counts: config.tier_transfer_counts,
+            tier_fees: config.tier_fees,
+            unique_users: config.unique_users,
+            schedule_hash: config.schedule_hash,
+        })
+    }
+}
+
+/// Rent-exempt lamports held by `users` user state accounts
+fn rent_liability(rent: &Rent, users: u64) -> u64 {
+    rent.minimum_balance(8 + UserState::INIT_SPACE).saturating_mul(users)
+}
+
+/// Extra accounts for `transfer_hook`, in `TransferHook` order after the
+/// validation account. Anchor reads an optional account passed as this
+/// program's id as `None`, so the slot of a feature that is off resolves to
+/// the program id, which every hooked transaction already contains; only
+/// enabled features add accounts:
+///
+/// - `destination_user_state`: strict-pair whitelisting. Receivers without a
+///   user state could not receive if it were always resolved, so incoming
+///   blocks are only enforced while strict pairs are on.
+/// - `linked_root_state`: `linked_roots_enabled`, from the sender's
+///   `user_state.linked_root` (unlinked senders resolve an empty address)
+/// - `fee_receipt`: `issue_receipts`, from the sender's `receipt_nonce`
+/// - `instructions_sysvar`: `require_memo`, and Receiver and Split fee payer
+///   modes
+/// - `associated_token_program`, `receiver_fee_account`: Receiver and Split
+///   fee payer modes; the destination owner's associated token account
+/// - `sol_fee_collector`, `sol_fee_escrow`: `fee_in_sol`
+/// - `associated_token_program`, `fee_token_account`: a `fee_mint`; the
+///   owner's associated token account in `fee_mint`
+/// - `reentrancy_guard`: `require_reentrancy_guard`
+/// - `stake_account`: `min_stake`
+/// - `fee_sink`: `sink_exemption`
+///
+/// Seeds read from `user_state` data cannot resolve for a sender without a
+/// user state, so receipts and linked roots exclude `allow_stateless`.
+fn extra_account_metas(config: &HookConfig) -> Result<Vec<ExtraAccountMeta>> {
+    let unused = || ExtraAccountMeta::new_with_pubkey(&crate::ID, false, false);
+    let mint = Seed::AccountKey { index: 1 };
+    let owner = Seed::AccountKey { index: 3 };
+    // Owner of the destination token account (`TokenAccount::owner`)
+    let destination_owner = Seed::AccountData { account_index: 2, data_index: 32, length: 32 };
+    // Account indices of `user_state` and `associated_token_program` in the
+    // `Execute` instruction (five fixed accounts precede the extra ones)
+    let user_state_index = 6;
+    let associated_token_program_index = 11;
+    let receiver_pays = config.fee_payer_mode != FEE_PAYER_SENDER;
+    let fee_token = config.fee_mint != Pubkey::default();
+
+    let metas = vec![
+        // hook_config
+        ExtraAccountMeta::new_with_seeds(
+            &[Seed::Literal { bytes: b"hook-config".to_vec() }, mint.clone()],
+            false,
+            true,
+        )?,
+        // user_state
+        ExtraAccountMeta::new_with_seeds(
+            &[Seed::Literal { bytes: b"user-state".to_vec() }, owner.clone(), mint.clone()],
+            false,
+            true,
+        )?,
+        // destination_user_state
+        if config.whitelist_mode && config.strict_pairs {
+            ExtraAccountMeta::new_with_seeds(
+                &[Seed::Literal { bytes: b"user-state".to_vec() }, destination_owner.clone(), mint.clone()],
+                false,
+                false,
+            )?
+        } else {
+            unused()?
+        },
+        // linked_root_state
+        if config.linked_roots_enabled {
+            ExtraAccountMeta::new_with_seeds(
+                &[
+                    Seed::Literal { bytes: b"user-state".to_vec() },
+                    Seed::AccountData {
+                        account_index: user_state_index,
+                        data_index: USER_STATE_LINKED_ROOT_OFFSET,
+                        length: 32,
+                    },
+                    mint.clone(),
+                ],
+                false,
+                false,
+            )?
+        } else {
+            unused()?
+        },
+        // fee_receipt
+        if config.issue_receipts {
+            ExtraAccountMeta::new_with_seeds(
+                &[
+                    Seed::Literal { bytes: b"receipt".to_vec() },
+                    owner.clone(),
+                    mint.clone(),
+                    Seed::AccountData {
+                        account_index: user_state_index,
+                        data_index: USER_STATE_RECEIPT_NONCE_OFFSET,
+                        length: 8,
+                    },
+                ],
+                false,
+                true,
+            )?
+        } else {
+            unused()?
+        },
+        // instructions_sysvar
+        if config.require_memo || receiver_pays {
+            ExtraAccountMeta::new_with_pubkey(&sysvar::instructions::ID, false, false)?
+        } else {
+            unused()?
+        },
+        // associated_token_program
+        if receiver_pays || fee_token {
+            ExtraAccountMeta::new_with_pubkey(&associated_token::ID, false, false)?
+        } else {
+            unused()?
+        },
+        // receiver_fee_account
+        if receiver_pays {
+            ExtraAccountMeta::new_external_pda_with_seeds(
+                associated_token_program_index,
+                &[
+                    destination_owner.clone(),
+                    Seed::Literal { bytes: spl_token_2022::ID.to_bytes().to_vec() },
+                    mint.clone(),
+                ],
+                false,
+                false,
+            )?
+        } else {
+            unused()?
+        },
+        // sol_fee_collector, sol_fee_escrow
+        if config.fee_in_sol {
+            ExtraAccountMeta::new_with_pubkey(&config.sol_fee_collector, false, true)?
+        } else {
+            unused()?
+        },
+        if config.fee_in_sol {
+            ExtraAccountMeta::new_with_seeds(
+                &[Seed::Literal { bytes: b"sol-escrow".to_vec() }, owner.clone(), mint.clone()],
+                false,
+                true,
+            )?
+        } else {
+            unused()?
+        },
+        // fee_token_account
+        if fee_token {
+            ExtraAccountMeta::new_external_pda_with_seeds(
+                associated_token_program_index,
+                &[
+                    owner.clone(),
+                    Seed::Literal { bytes: config.fee_token_program.to_bytes().to_vec() },
+                    Seed::Literal { bytes: config.fee_mint.to_bytes().to_vec() },
+                ],
+                false,
+                false,
+            )?
+        } else {
+            unused()?
+        },
+        // reentrancy_guard
+        if config.require_reentrancy_guard {
+            ExtraAccountMeta::new_with_seeds(
+                &[Seed::Literal { bytes: b"reentrancy-guard".to_vec() }, mint.clone()],
+                false,
+                true,
+            )?
+        } else {
+            unused()?
+        },
+        // stake_account
+        if config.min_stake > 0 {
+            ExtraAccountMeta::new_with_seeds(
+                &[Seed::Literal { bytes: b"stake".to_vec() }, owner, mint.clone()],
+                false,
+                false,
+            )?
+        } else {
+            unused()?
+        },
+        // fee_sink
+        if config.sink_exemption {
+            ExtraAccountMeta::new_with_seeds(
+                &[Seed::Literal { bytes: b"sink".to_vec() }, destination_owner, mint],
+                false,
+                false,
+            )?
+        } else {
+            unused()?
+        },
+        // event_authority and program, appended by `#[event_cpi]`
+        ExtraAccountMeta::new_with_seeds(
+            &[Seed::Literal { bytes: b"__event_authority".to_vec() }],
+            false,
+            false,
+        )?,
+        ExtraAccountMeta::new_with_pubkey(&crate::ID, false, false)?,
+    ];
+
+    Ok(metas)
+}
+
+/// Fingerprint of the config inputs to `extra_account_metas`, never 0
+fn metas_version(config: &HookConfig) -> u64 {
+    let sol_fee_collector = if config.fee_in_sol {
+        config.sol_fee_collector
+    } else {
+        Pubkey::default()
+    };
+    let hash = keccak::hashv(&[
+        &[
+            (config.whitelist_mode && config.strict_pairs) as u8,
+            config.require_memo as u8,
+            config.fee_in_sol as u8,
+            config.require_reentrancy_guard as u8,
+            (config.min_stake > 0) as u8,
+            config.sink_exemption as u8,
+            config.linked_roots_enabled as u8,
+            config.issue_receipts as u8,
+            (config.fee_payer_mode != FEE_PAYER_SENDER) as u8,
+        ],
+        sol_fee_collector.as_ref(),
+        config.fee_mint.as_ref(),
+        config.fee_token_program.as_ref(),
+        &(EXTRA_ACCOUNT_META_COUNT as u64).to_le_bytes(),
+    ]);
+    hash.to_bytes()[..8].iter().fold(0u64, |version, &byte| version << 8 | byte as u64).max(1)
+}
+
+/// Whole points `volume` earns at `rate` per `POINTS_RATE_SCALE`, and the
+/// smallest volume that earns them (at most `volume`)
+fn points_for_volume(rate: u64, volume: u64) -> Result<(u64, u64)> {
+    if rate == 0 {
+        return Ok((0, 0));
+    }
+    let points = (volume as u128) * (rate as u128) / (POINTS_RATE_SCALE as u128);
+    let points = u64::try_from(points).map_err(|_| error!(ErrorCode::ArithmeticOverflow))?;
+    // ceil(points * scale / rate) <= volume, as points <= volume * rate / scale
+    let converted = ((points as u128) * (POINTS_RATE_SCALE as u128)).div_ceil(rate as u128);
+    Ok((points, converted as u64))
+}
+
+/// Balance-based fee bps for a sender holding `balance`
+fn balance_component_bps(config: &HookConfig, balance: u64) -> u16 {
+    if !config.balance_fee_enabled || config.balance_per_bp == 0 {
+        return 0;
+    }
+    let bps = (balance as u128) / (config.balance_per_bp as u128);
+    bps.min(config.max_balance_fee_bps as u128) as u16
+}
+
+/// `tier_bps` interpolated from `launch_fee_bps` while inside the launch
+/// ramp; the integer step rounds toward the launch rate
+fn launch_ramp_bps(config: &HookConfig, tier_bps: u16, now: i64) -> u16 {
+    if config.ramp_seconds == 0 {
+        return tier_bps;
+    }
+    let elapsed = now.saturating_sub(config.launch_timestamp).max(0);
+    if elapsed >= config.ramp_seconds {
+        return tier_bps;
+    }
+    let launch = config.launch_fee_bps as i128;
+    let step = (tier_bps as i128 - launch) * elapsed as i128 / config.ramp_seconds as i128;
+    (launch + step) as u16
+}
+
+/// Whether the passed `fee_sink` PDA (address checked by its seeds) exists,
+/// i.e. the destination owner is a listed sink
+fn is_fee_sink(config: &HookConfig, fee_sink: Option<&UncheckedAccount>) -> bool {
+    config.sink_exemption
+        && fee_sink.is_some_and(|account| *account.owner == crate::ID && !account.data_is_empty())
+}
+
+/// Configured thresholds (0 = unused slot) a counter passed on its way
+/// from `before` to `after`
+fn crossed_milestones(
+    thresholds: &[u64; MAX_MILESTONES],
+    before: u64,
+    after: u64,
+) -> impl Iterator<Item = u64> + '_ {
+    thresholds
+        .iter()
+        .copied()
+        .filter(move |&threshold| threshold > 0 && before < threshold && threshold <= after)
+}
+
+/// Whether the meta list was last synced under a different config
+/// (lists from before versioning, at version 0, are trusted)
+fn metas_out_of_sync(config: &HookConfig) -> bool {
+    config.metas_version != 0 && config.metas_version != metas_version(config)
+}
+
+/// A transfer's `effective_bps * amount`, where the effective bps is
+/// `final_fee * 10000 / amount`; computed from the fee alone, so no
+/// per-transfer rounding of the bps is accumulated
+fn effective_bps_weight(final_fee: u64) -> u128 {
+    (final_fee as u128) * 10_000
+}
+
+/// `cumulative_fee_bps_weighted / total_volume`, scaled by
+/// `AVERAGE_FEE_BPS_PRECISION` (0 before any volume)
+fn average_fee_bps(cumulative_fee_bps_weighted: u128, total_volume: u64) -> u64 {
+    if total_volume == 0 {
+        return 0;
+    }
+    let average = cumulative_fee_bps_weighted.saturating_mul(AVERAGE_FEE_BPS_PRECISION) / (total_volume as u128);
+    average.min(u64::MAX as u128) as u64
+}
+
+/// Histogram bucket of a lifetime volume (its decimal digit count - 1)
+fn volume_bucket(volume: u64) -> usize {
+    volume.checked_ilog10().unwrap_or(0) as usize
+}
+
+/// Move a user from the bucket of `previous` (None = not yet counted) to
+/// the bucket of `volume`
+fn record_volume_bucket(config: &mut HookConfig, previous: Option<u64>, volume: u64) {
+    if let Some(previous) = previous {
+        let bucket = volume_bucket(previous);
+        config.volume_histogram[bucket] = config.volume_histogram[bucket].saturating_sub(1);
+    }
+    let bucket = volume_bucket(volume);
+    config.volume_histogram[bucket] = config.volume_histogram[bucket].saturating_add(1);
+}
+
+/// Share of counted users below `volume` in bps, placing `volume` at the
+/// midpoint of its own bucket; 0 for an empty histogram
+fn volume_percentile_bps(histogram: &[u64; VOLUME_BUCKET_COUNT], volume: u64) -> u16 {
+    let bucket = volume_bucket(volume);
+    let total: u128 = histogram.iter().map(|&count| count as u128).sum();
+    if total == 0 {
+        return 0;
+    }
+    let below: u128 = histogram[..bucket].iter().map(|&count| count as u128).sum();
+    let doubled_rank = below * 2 + histogram[bucket] as u128;
+    (doubled_rank * 10000 / (total * 2)).min(10000) as u16
+}
+
+/// Set `counterparty` in a 256-bit, 3-hash Bloom filter, returning whether
+/// it was probably new. Pubkeys are already uniformly distributed, so their
+/// first bytes serve as the bit indices without extra hashing. A repeat is
+/// never counted as new, but a new destination is missed with probability
+/// (1 - e^(-3n/256))^3 after n distinct ones: ~0.1% at 10, ~9% at 50 and
+/// ~33% at 100, so the estimate undercounts increasingly beyond ~50.
+fn record_counterparty(filter: &mut [u8; COUNTERPARTY_FILTER_BYTES], counterparty: &Pubkey) -> bool {
+    let mut new = false;
+    for &bit in counterparty.as_ref()[..COUNTERPARTY_FILTER_HASHES].iter() {
+        let (byte, mask) = (bit as usize / 8, 1u8 << (bit % 8));
+        new |= filter[byte] & mask == 0;
+        filter[byte] |= mask;
+    }
+    new
+}
+
+/// Whether any top-level instruction of the current transaction targets
+/// the SPL Memo program. The sysvar only lists top-level instructions, so a
+/// memo issued by CPI is not seen, and a memo anywhere in the transaction
+/// counts for every hooked transfer in it.
+fn has_memo_instruction(sysvar: &AccountInfo) -> bool {
+    (0..)
+        .map_while(|index| load_instruction_at_checked(index, sysvar).ok())
+        .any(|ix| ix.program_id == MEMO_PROGRAM_ID || ix.program_id == MEMO_V1_PROGRAM_ID)
+}
+
+/// Amount of `ix` if it is a Token-2022 `TransferChecked` of `mint` from
+/// `source` to `destination`
+fn transfer_checked_amount(ix: &Instruction, mint: &Pubkey, source: &Pubkey, destination: &Pubkey) -> Option<u64> {
+    if ix.program_id != spl_token_2022::ID {
+        return None;
+    }
+    let [source_meta, mint_meta, destination_meta, _authority, ..] = ix.accounts.as_slice() else {
+        return None;
+    };
+    if source_meta.pubkey != *source || mint_meta.pubkey != *mint || destination_meta.pubkey != *destination {
+        return None;
+    }
+    match TokenInstruction::unpack(&ix.data).ok()? {
+        TokenInstruction::TransferChecked { amount, .. } => Some(amount),
+        _ => None,
+    }
+}
+
+/// Amount of the hooked transfer `offset` top-level instructions away from
+/// the current one, if it moves `mint` from `source` to `destination`.
+/// The runtime only allows a program to reenter itself directly, so a
+/// transfer of the hooked mint cannot be made by CPI from this program:
+/// Token-2022 would call back into `transfer_hook`. Instructions that move
+/// the mint pair with a transfer the client places next to them instead.
+fn paired_transfer_amount(
+    sysvar: &AccountInfo,
+    offset: i64,
+    mint: &Pubkey,
+    source: &Pubkey,
+    destination: &Pubkey,
+) -> Result<Option<u64>> {
+    let index = load_current_index_checked(sysvar)? as i64 + offset;
+    let Ok(index) = usize::try_from(index) else {
+        return Ok(None);
+    };
+    Ok(load_instruction_at_checked(index, sysvar)
+        .ok()
+        .and_then(|ix| transfer_checked_amount(&ix, mint, source, destination)))
+}
+
+/// Fail unless the current instruction is top-level, so that a single
+/// paired transfer cannot be counted by several invocations
+fn require_top_level() -> Result<()> {
+    require!(
+        get_stack_height() == TRANSACTION_LEVEL_STACK_HEIGHT,
+        ErrorCode::PairedTransferMissing
+    );
+    Ok(())
+}
+
+/// Whether the user is inside the fee-free onboarding window, which opens
+/// with their first transfer (`first_transfer_timestamp` is already set
+/// when that transfer is priced)
+fn in_onboarding_window(config: &HookConfig, user_state: &UserState, now: i64) -> bool {
+    config.onboarding_free_seconds > 0
+        && now.saturating_sub(user_state.first_transfer_timestamp) < config.onboarding_free_seconds
+}
+
+/// Lamports owed in SOL fee mode: the flat fee plus `final_fee` converted
+/// at `lamports_per_token`
+fn sol_fee(config: &HookConfig, final_fee: u64, decimals: u8) -> u64 {
+    config
+        .sol_fee_per_transfer
+        .saturating_add(to_fee_token(final_fee, config.lamports_per_token, decimals))
+}
+
+/// Whether a destination's post-transfer balance breaks `max_account_balance`
+fn exceeds_max_balance(config: &HookConfig, post_transfer_balance: u64) -> bool {
+    config.max_account_balance > 0 && post_transfer_balance > config.max_account_balance
+}
+
+/// Start a fresh daily window once `DAILY_WINDOW_SECONDS` have passed
+fn roll_daily_window(user_state: &mut UserState, now: i64) {
+    if now.saturating_sub(user_state.window_start) >= DAILY_WINDOW_SECONDS {
+        user_state.window_start = now;
+        user_state.window_volume = 0;
+        user_state.free_used_this_window = 0;
+        user_state.window_dest_filter = [0; COUNTERPARTY_FILTER_BYTES];
+        user_state.window_dest_count = 0;
+    }
+}
+
+/// Add `priced_amount` to the user's aggregation window and return the
+/// window's cumulative volume, or `priced_amount` alone while aggregation
+/// is off. A window opens with the first transfer after the previous one
+/// expired and is never extended, so this approximates aggregate pricing:
+/// an order whose chunks straddle a rollover restarts from its next chunk.
+fn aggregate_priced_amount(
+    config: &HookConfig,
+    user_state: &mut UserState,
+    priced_amount: u64,
+    now: i64,
+) -> u64 {
+    if config.aggregation_seconds == 0 {
+        return priced_amount;
+    }
+    if now.saturating_sub(user_state.aggregation_window_start) >= config.aggregation_seconds {
+        user_state.aggregation_window_start = now;
+        user_state.aggregation_window_volume = 0;
+    }
+    user_state.aggregation_window_volume = user_state.aggregation_window_volume.saturating_add(priced_amount);
+    user_state.aggregation_window_volume
+}
+
+/// `amount` in displayed base units for mints with the Token-2022
+/// interest-bearing extension (the only scaling extension supported),
+/// otherwise the raw amount. Uses the extension's own float math, which
+/// costs extra compute.
+fn scaled_amount(mint: &AccountInfo, amount: u64, now: i64) -> u64 {
+    let Ok(data) = mint.try_borrow_data() else {
+        return amount;
+    };
+    let Ok(mint_state) = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(&data) else {
+        return amount;
+    };
+    let Ok(interest) = mint_state.get_extension::<InterestBearingConfig>() else {
+        return amount;
+    };
+
+    interest
+        .amount_to_ui_amount(amount, 0, now)
+        .and_then(|ui_amount| ui_amount.parse::<f64>().ok())
+        .map_or(amount, |scaled| scaled as u64)
+}
+
+/// Total fees for `transfers` identical transfers by a new user. Fees are
+/// constant between loyalty thresholds, so this walks tier segments rather
+/// than individual transfers.
+fn estimate_fees(config: &HookConfig, amount: u64, transfers: u64) -> Result<u64> {
+    let fee_tier = get_fee_tier(config, amount);
+    let fee_bps = if config.tier_fees_enabled[fee_tier] {
+        calculate_fee_tier(config, amount)
+    } else {
+        0
+    };
+
+    let mut total: u64 = 0;
+    let mut count: u64 = 0;
+
+    if transfers > 0 && config.first_transfer_fee_bps > 0 {
+        let fee = compute_final_fee(amount, config.first_transfer_fee_bps, 0)?;
+        total = finalize_fee(config, fee, amount, config.max_fee).final_fee;
+        count = 1;
+    }
+
+    while count < transfers {
+        let tier = get_loyalty_tier(config, count);
+        let next_threshold = if tier < config.loyalty_tier_count {
+            config.loyalty_tier_thresholds[tier as usize]
+        } else {
+            u64::MAX
+        };
+        let segment_end = next_threshold.min(transfers);
+
+        let fee = compute_final_fee(amount, fee_bps, loyalty_discount_bps(config, tier))?;
+        let per_transfer = finalize_fee(config, fee, amount, config.max_fee).final_fee;
+        total = total.saturating_add(per_transfer.saturating_mul(segment_end - count));
+        count = segment_end;
+    }
+
+    Ok(total)
+}
+
+/// The sender's user state as passed to `transfer_hook` (its address is
+/// checked by the accounts struct), or `None` while it has not been created
+fn load_transfer_user_state(account: &AccountInfo) -> Result<Option<UserState>> {
+    if *account.owner != crate::ID {
+        return Ok(None);
+    }
+    let data = account.try_borrow_data()?;
+    UserState::try_deserialize(&mut &data[..])
+        .map(Some)
+        .map_err(|_| error!(ErrorCode::InvalidUserState))
+}
+
+/// Write back a user state loaded with `load_transfer_user_state`
+fn store_user_state(account: &AccountInfo, state: &UserState) -> Result<()> {
+    let mut data = account.try_borrow_mut_data()?;
+    state.try_serialize(&mut &mut data[..])
+}
+
+/// Address of the owner's `FeeReceipt` with `nonce`
+fn receipt_address(owner: &Pubkey, mint: &Pubkey, nonce: u64) -> Pubkey {
+    let (address, _) = Pubkey::find_program_address(
+        &[b"receipt", owner.as_ref(), mint.as_ref(), &nonce.to_le_bytes()],
+        &crate::ID,
+    );
+    address
+}
+
+/// Deserialize a `UserState` passed outside the accounts struct, checking
+/// program ownership, the discriminator and its PDA derivation for `mint`
+fn read_user_state(account: &AccountInfo, mint: &Pubkey) -> Result<UserState> {
+    require_keys_eq!(*account.owner, crate::ID, ErrorCode::InvalidUserState);
+
+    let data = account.try_borrow_data()?;
+    let state = UserState::try_deserialize(&mut &data[..])
+        .map_err(|_| error!(ErrorCode::InvalidUserState))?;
+
+    let (expected, _) = Pubkey::find_program_address(
+        &[b"user-state", state.owner.as_ref(), mint.as_ref()],
+        &crate::ID,
+    );
+    require_keys_eq!(account.key(), expected, ErrorCode::InvalidUserState);
+
+    Ok(state)
+}
+
+/// Serialize a user state in the `export_user_state` layout
+fn encode_user_state_export(
+    mint: &Pubkey,
+    state: &UserState,
+    loyalty_tier: u8,
+    now: i64,
+) -> [u8; USER_STATE_EXPORT_LEN] {
+    let mut flags = 0;
+    if state.is_blacklisted {
+        flags |= EXPORT_FLAG_BLACKLISTED;
+    }
+    if state.is_whitelisted {
+        flags |= EXPORT_FLAG_WHITELISTED;
+    }
+    if state.loyalty_opt_out {
+        flags |= EXPORT_FLAG_LOYALTY_OPT_OUT;
+    }
+
+    let mut blob = [0u8; USER_STATE_EXPORT_LEN];
+    blob[0] = USER_STATE_EXPORT_VERSION;
+    blob[1..33].copy_from_slice(mint.as_ref());
+    blob[33..65].copy_from_slice(state.owner.as_ref());
+    blob[65..73].copy_from_slice(&state.transfer_count.to_le_bytes());
+    blob[73..81].copy_from_slice(&state.total_volume.to_le_bytes());
+    blob[81..89].copy_from_slice(&state.total_fees_paid.to_le_bytes());
+    blob[89] = loyalty_tier;
+    blob[90] = flags;
+    blob[91..99].copy_from_slice(&state.first_transfer_timestamp.to_le_bytes());
+    blob[99..107].copy_from_slice(&state.last_transfer_timestamp.to_le_bytes());
+    blob[107..115].copy_from_slice(&now.to_le_bytes());
+    blob
+}
+
+/// Whether `mint` is the wrapped-SOL mint of either token program
+fn is_native_mint(mint: &Pubkey) -> bool {
+    *mint == spl_token::native_mint::ID || *mint == spl_token_2022::native_mint::ID
+}
+
+/// Append `collector` and replace every collector's weight
+fn add_collector(config: &mut HookConfig, collector: Pubkey, weights: &[u16]) -> Result<()> {
+    let count = config.fee_collector_count as usize;
+    require!(
+        !config.fee_collectors[..count].contains(&collector),
+        ErrorCode::DuplicateFeeCollector
+    );
+    require!(count < config.max_fee_collectors as usize, ErrorCode::TooManyFeeCollectors);
+    validate_collector_weights(weights, count + 1)?;
+
+    config.fee_collectors[count] = collector;
+    config.fee_collector_count += 1;
+    config.fee_collector_weights[..count + 1].copy_from_slice(weights);
+    config.fee_collector = config.fee_collectors[0];
+    Ok(())
+}
+
+/// Remove `collector`, keeping the others in order, and replace their weights
+fn remove_collector(config: &mut HookConfig, collector: Pubkey, weights: &[u16]) -> Result<()> {
+    let count = config.fee_collector_count as usize;
+    let index = config.fee_collectors[..count]
+        .iter()
+        .position(|&listed| listed == collector)
+        .ok_or(error!(ErrorCode::FeeCollectorNotFound))?;
+
+    let remaining = count - 1;
+    if remaining == 0 {
+        config.fee_collectors = [Pubkey::default(); MAX_FEE_COLLECTORS];
+        config.fee_collector_weights = [0; MAX_FEE_COLLECTORS];
+        config.fee_collectors[0] = config.authority;
+        config.fee_collector_weights[0] = 10000;
+        config.fee_collector_count = 1;
+        config.fee_collector = config.authority;
+        return Ok(());
+    }
+    validate_collector_weights(weights, remaining)?;
+
+    config.fee_collectors.copy_within(index + 1..count, index);
+    config.fee_collectors[remaining] = Pubkey::default();
+    config.fee_collector_weights = [0; MAX_FEE_COLLECTORS];
+    config.fee_collector_weights[..remaining].copy_from_slice(weights);
+    config.fee_collector_count = remaining as u8;
+    config.fee_collector = config.fee_collectors[0];
+    Ok(())
+}
+
+/// Replace the primary collector, in the list and in `fee_collector`
+fn set_primary_collector(config: &mut HookConfig, collector: Pubkey) -> Result<()> {
+    let count = config.fee_collector_count as usize;
+    require!(
+        !config.fee_collectors[1..count.max(1)].contains(&collector),
+        ErrorCode::DuplicateFeeCollector
+    );
+    config.fee_collectors[0] = collector;
+    config.fee_collector_count = config.fee_collector_count.max(1);
+    config.fee_collector = collector;
+    Ok(())
+}
+
+/// Whether `owner` is one of the listed fee collectors
+fn is_fee_collector(config: &HookConfig, owner: &Pubkey) -> bool {
+    config.fee_collectors[..config.fee_collector_count as usize].contains(owner)
+}
+
+/// Split `amount` between the listed collectors by weight, each share
+/// rounded down; the rounding remainder goes to the primary collector
+fn collector_shares(config: &HookConfig, amount: u64) -> Result<Vec<CollectorShare>> {
+    let count = config.fee_collector_count as usize;
+    let mut shares = config.fee_collectors[..count]
+        .iter()
+        .zip(config.fee_collector_weights[..count].iter())
+        .map(|(&collector, &weight)| {
+            let amount = bps_to_fee(amount, weight).ok_or(error!(ErrorCode::ArithmeticOverflow))?;
+            Ok(CollectorShare { collector, amount })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let distributed = shares.iter().map(|share| share.amount).sum::<u64>();
+    if let Some(primary) = shares.first_mut() {
+        primary.amount = primary.amount.saturating_add(amount - distributed);
+    }
+    Ok(shares)
+}
+
+/// Whether `owner` is on the admin-maintained DeFi owner list
+fn is_defi_owner(config: &HookConfig, owner: &Pubkey) -> bool {
+    config.defi_owners[..config.defi_owner_count as usize].contains(owner)
+}
+
+/// Append `owner` to the DeFi owner list
+fn list_defi_owner(config: &mut HookConfig, owner: Pubkey) -> Result<()> {
+    let count = config.defi_owner_count as usize;
+    require!(!config.defi_owners[..count].contains(&owner), ErrorCode::DuplicateDefiOwner);
+    require!(count < MAX_DEFI_OWNERS, ErrorCode::TooManyDefiOwners);
+
+    config.defi_owners[count] = owner;
+    config.defi_owner_count += 1;
+    Ok(())
+}
+
+/// Remove `owner`, keeping the others in order
+fn unlist_defi_owner(config: &mut HookConfig, owner: Pubkey) -> Result<()> {
+    let count = config.defi_owner_count as usize;
+    let index = config.defi_owners[..count]
+        .iter()
+        .position(|&listed| listed == owner)
+        .ok_or(error!(ErrorCode::DefiOwnerNotFound))?;
+
+    config.defi_owners.copy_within(index + 1..count, index);
+    config.defi_owners[count - 1] = Pubkey::default();
+    config.defi_owner_count -= 1;
+    Ok(())
+}
+
+/// Whether `owner` is on the authorized minter list
+fn is_authorized_minter(config: &HookConfig, owner: &Pubkey) -> bool {
+    config.authorized_minters[..config.authorized_minter_count as usize].contains(owner)
+}
+
+/// Append `minter` to the authorized minter list
+fn list_authorized_minter(config: &mut HookConfig, minter: Pubkey) -> Result<()> {
+    let count = config.authorized_minter_count as usize;
+    require!(
+        !config.authorized_minters[..count].contains(&minter),
+        ErrorCode::DuplicateAuthorizedMinter
+    );
+    require!(count < MAX_AUTHORIZED_MINTERS, ErrorCode::TooManyAuthorizedMinters);
+
+    config.authorized_minters[count] = minter;
+    config.authorized_minter_count += 1;
+    Ok(())
+}
+
+/// Remove `minter`, keeping the others in order
+fn unlist_authorized_minter(config: &mut HookConfig, minter: Pubkey) -> Result<()> {
+    let count = config.authorized_minter_count as usize;
+    let index = config.authorized_minters[..count]
+        .iter()
+        .position(|&listed| listed == minter)
+        .ok_or(error!(ErrorCode::AuthorizedMinterNotFound))?;
+
+    config.authorized_minters.copy_within(index + 1..count, index);
+    config.authorized_minters[count - 1] = Pubkey::default();
+    config.authorized_minter_count -= 1;
+    Ok(())
+}
+
+/// Whether listing (or unlisting) a DeFi owner can only lower its fees:
+/// the DeFi rate must be no higher (or no lower) than every enabled tier
+fn defi_fee_is_decrease(config: &HookConfig, listing: bool) -> bool {
+    (0..FEE_TIER_COUNT).all(|tier| {
+        let tier_bps = if config.tier_fees_enabled[tier] { config.fee_tier_bps[tier] } else { 0 };
+        if listing {
+            config.defi_fee_bps <= tier_bps
+        } else {
+            config.defi_fee_bps >= tier_bps
+        }
+    })
+}
+
+/// Whether the new free allowance is no smaller
+fn free_allowance_is_decrease(config: &HookConfig, allowance: u64) -> bool {
+    allowance >= config.free_allowance_per_period
+}
+
+/// Whether the change can only waive fees: exempting the mint authority,
+/// or keeping it charged
+fn mint_authority_exemption_is_decrease(config: &HookConfig, exempt: bool) -> bool {
+    exempt || !config.exempt_mint_authority
+}
+
+/// Whether the onboarding fee-free window is no shorter
+fn onboarding_window_is_decrease(config: &HookConfig, seconds: i64) -> bool {
+    seconds >= config.onboarding_free_seconds
+}
+
+/// Whether loyalty can only decay less: decay turned off, or an existing
+/// decay with no shorter gap and no fewer grace transfers
+fn loyalty_decay_is_decrease(config: &HookConfig, decay_seconds: i64, grace_transfers: u64) -> bool {
+    decay_seconds == 0
+        || (config.loyalty_decay_seconds != 0
+            && decay_seconds >= config.loyalty_decay_seconds
+            && grace_transfers >= config.decay_grace_transfers)
+}
+
+/// Whether the continuous curve can only discount more. Switching between
+/// it and the tier discount can go either way, so it must stay as it is.
+fn continuous_discount_is_decrease(
+    config: &HookConfig,
+    enabled: bool,
+    volume_per_bp: u64,
+    max_discount_bps: u16,
+) -> bool {
+    enabled == config.continuous_discount
+        && (!enabled
+            || (volume_per_bp <= config.volume_per_bp
+                && max_discount_bps >= config.max_continuous_discount_bps))
+}
+
+/// Whether the holding discount can only grow: stacking it where there was
+/// none, or the same mode with no smaller rate or cap. Replacing the volume
+/// discount can go either way, so entering or leaving replace mode cannot.
+fn holding_discount_is_decrease(config: &HookConfig, mode: u8, per_day_bps: u16, max_bps: u16) -> bool {
+    let current = config.holding_discount_mode;
+    (mode == current || (current == HOLDING_DISCOUNT_OFF && mode == HOLDING_DISCOUNT_STACK))
+        && (mode == HOLDING_DISCOUNT_OFF
+            || (per_day_bps >= config.holding_discount_per_day_bps
+                && max_bps >= config.max_holding_discount_bps))
+}
+
+/// Whether the fee payer setup is unchanged. Moving any share of the fee
+/// between sender and receiver raises it for one of them.
+fn fee_payer_mode_is_decrease(config: &HookConfig, mode: u8, receiver_share_bps: u16) -> bool {
+    mode == config.fee_payer_mode
+        && (mode != FEE_PAYER_SPLIT || receiver_share_bps == config.receiver_fee_share_bps)
+}
+
+/// Whether the aggregation window can only lower fees: the same window,
+/// or a longer one while no enabled tier charges more than the tier below
+/// it, since a longer window only ever selects a higher tier
+fn aggregation_is_decrease(config: &HookConfig, seconds: i64) -> bool {
+    let tier_bps: [u16; FEE_TIER_COUNT] =
+        core::array::from_fn(|tier| if config.tier_fees_enabled[tier] { config.fee_tier_bps[tier] } else { 0 });
+    seconds == config.aggregation_seconds
+        || (seconds > config.aggregation_seconds && tier_bps.windows(2).all(|pair| pair[0] >= pair[1]))
+}
+
+/// Whether the cooldown exemption gets no dearer: putting it on sale, or
+/// a price no higher than the current one. Withdrawing it from sale is not.
+fn cooldown_exemption_price_is_decrease(config: &HookConfig, lamports: u64) -> bool {
+    lamports > 0 && (config.cooldown_exemption_price == 0 || lamports <= config.cooldown_exemption_price)
+}
+
+/// One weight per collector, summing to exactly 10000 bps
+fn validate_collector_weights(weights: &[u16], count: usize) -> Result<()> {
+    require!(
+        weights.len() == count && weights.iter().map(|&bps| bps as u32).sum::<u32>() == 10000,
+        ErrorCode::InvalidFeeConfig
+    );
+    Ok(())
+}
+
+/// Apply a governance-approved change with the same validation as the
+/// corresponding admin instruction
+fn apply_config_change(config: &mut HookConfig, change: &ConfigChange) -> Result<()> {
+    match *change {
+        ConfigChange::RewardsBps(bps) => {
+            require!(bps as u32 + config.staking_bps as u32 <= 10000, ErrorCode::InvalidFeeConfig);
+            config.rewards_bps = bps;
+        }
+        ConfigChange::TierFeeEnabled { tier, enabled } => {
+            require!((tier as usize) < FEE_TIER_COUNT, ErrorCode::InvalidFeeConfig);
+            require_fee_change_allowed(config, !enabled || config.tier_fees_enabled[tier as usize])?;
+            config.tier_fees_enabled[tier as usize] = enabled;
+        }
+        ConfigChange::FeePayerMode { mode, receiver_share_bps } => {
+            require!(mode <= FEE_PAYER_SPLIT, ErrorCode::InvalidFeeConfig);
+            require!(receiver_share_bps <= 10000, ErrorCode::InvalidFeeConfig);
+            require_fee_change_allowed(config, fee_payer_mode_is_decrease(config, mode, receiver_share_bps))?;
+            config.fee_payer_mode = mode;
+            config.receiver_fee_share_bps = receiver_share_bps;
+        }
+        ConfigChange::FreeAllowance(allowance) => {
+            require_fee_change_allowed(config, free_allowance_is_decrease(config, allowance))?;
+            config.free_allowance_per_period = allowance;
+        }
+        ConfigChange::MaxTransfersPerUser(max_transfers) => {
+            config.max_transfers_per_user = max_transfers;
+        }
+        ConfigChange::MinAccountAge(seconds) => {
+            require!(seconds >= 0, ErrorCode::InvalidFeeConfig);
+            config.min_account_age_seconds = seconds;
+        }
+        ConfigChange::Schedule(ref schedule) => {
+            validate_schedule(schedule)?;
+            require_fee_change_allowed(config, schedule_is_decrease(&current_schedule(config), schedule))?;
+            apply_schedule(config, schedule);
+        }
+        ConfigChange::DeclareEvent(event) => {
+            if event != ActiveEvent::default() {
+                require!(is_valid_label(&event.name), ErrorCode::InvalidFeeConfig);
+                require!(event.starts_at < event.ends_at, ErrorCode::InvalidFeeConfig);
+                require!(
+                    event.fee_bps <= 10000 && event.discount_bps <= 10000,
+                    ErrorCode::InvalidFeeConfig
+                );
+                require_fee_change_allowed(config, event_is_decrease(config, &event))?;
+            } else {
+                require_fee_change_allowed(config, event_cancel_is_decrease(config))?;
+            }
+            config.active_event = event;
+        }
+    }
+    Ok(())
+}
+
+/// Net bps a transfer pays under `event`, its discount clamped as applied
+fn event_net_bps(config: &HookConfig, event: &ActiveEvent) -> u16 {
+    event.fee_bps - clamp_total_discount(config, event.fee_bps, event.discount_bps)
+}
+
+/// The rates an event replaces: each tier's (0 when disabled) and, with
+/// DeFi owners listed, the DeFi rate
+fn event_replaced_bps(config: &HookConfig) -> impl Iterator<Item = u16> + '_ {
+    (0..FEE_TIER_COUNT)
+        .map(move |tier| if config.tier_fees_enabled[tier] { config.fee_tier_bps[tier] } else { 0 })
+        .chain((config.defi_owner_count > 0).then_some(config.defi_fee_bps))
+}
+
+/// Whether declaring `event` can only lower fees: its net rate is below
+/// every replaced rate less the largest discount the caps allow there,
+/// and below the net rate of any event already declared
+fn event_is_decrease(config: &HookConfig, event: &ActiveEvent) -> bool {
+    let net_bps = event_net_bps(config, event);
+    event_replaced_bps(config).all(|bps| net_bps <= bps - clamp_total_discount(config, bps, u16::MAX))
+        && (config.active_event.ends_at == 0 || net_bps <= event_net_bps(config, &config.active_event))
+}
+
+/// Whether cancelling the declared event can only lower fees: none is
+/// declared, or it never undercuts an undiscounted replaced rate. Without
+/// the clock here, an event that has already ended counts as declared.
+fn event_cancel_is_decrease(config: &HookConfig) -> bool {
+    let current = &config.active_event;
+    current.ends_at == 0 || event_replaced_bps(config).all(|bps| event_net_bps(config, current) >= bps)
+}
+
+/// Whether `event` is declared and its window contains `now`
+fn is_event_running(event: &ActiveEvent, now: i64) -> bool {
+    event.ends_at != 0 && event.starts_at <= now && now < event.ends_at
+}
+
+/// Apply `change` to a scratch copy of the config, reporting the outcome
+fn dry_run_config_change(config: &HookConfig, change: &ConfigChange) -> ConfigValidation {
+    let mut scratch = config.clone();
+    match apply_config_change(&mut scratch, change) {
+        Ok(()) => ConfigValidation { valid: true, error_code: 0 },
+        Err(err) => ConfigValidation {
+            valid: false,
+            error_code: match err {
+                anchor_lang::error::Error::AnchorError(anchor_error) => anchor_error.error_code_number,
+                anchor_lang::error::Error::ProgramError(_) => ErrorCode::InvalidFeeConfig.into(),
+            },
+        },
+    }
+}
+
+/// The PDA of `program` at `seeds` (bump included). This program's own
+/// PDAs are refused: it never signs admin instructions for itself, so
+/// handing it the authority would lock every admin instruction.
+fn program_authority_address(program: &Pubkey, seeds: &[Vec<u8>]) -> Result<Pubkey> {
+    require_keys_neq!(*program, crate::ID, ErrorCode::InvalidProgramAuthority);
+    let seeds: Vec<&[u8]> = seeds.iter().map(Vec::as_slice).collect();
+    Pubkey::create_program_address(&seeds, program).map_err(|_| error!(ErrorCode::InvalidProgramAuthority))
+}
+
+/// The clock, or `None` when the sysvar cannot be read and `require_clock`
+/// is off; with it on the read error fails the transfer
+fn resolve_clock(
+    config: &HookConfig,
+    clock: std::result::Result<Clock, ProgramError>,
+) -> Result<Option<Clock>> {
+    match clock {
+        Ok(clock) => Ok(Some(clock)),
+        Err(err) if config.require_clock => Err(err.into()),
+        Err(_) => Ok(None),
+    }
+}
+
+/// `transfer_hook` for a transfer without the owner's user state (see
+/// `set_allow_stateless`): mint-wide checks, the tier fee with no
+/// discounts or surcharges, and global statistics only
+fn stateless_transfer_hook(ctx: Context<TransferHook>, amount: u64) -> Result<()> {
+    let config = &mut ctx.accounts.hook_config;
+    let mint = ctx.accounts.mint.key();
+    let owner = ctx.accounts.owner.key();
+    let clock = resolve_clock(config, Clock::get())?;
+    let clock_available = clock.is_some();
+    let now = clock.map_or(0, |clock| clock.unix_timestamp);
+
+    if !config.allow_stateless {
+        return reject_transfer(config, mint, owner, amount, ErrorCode::CannotInitUserState);
+    }
+
+    if let Some(guard) = ctx.accounts.reentrancy_guard.as_mut() {
+        if guard.locked {
+            return reject_transfer(config, mint, owner, amount, ErrorCode::ReentrancyDetected);
+        }
+        guard.locked = true;
+        guard.exit(&crate::ID)?;
+    } else if config.require_reentrancy_guard {
+        return reject_transfer(config, mint, owner, amount, ErrorCode::ReentrancyGuardMissing);
+    }
+
+    let sides = [ctx.accounts.source_token.owner, ctx.accounts.destination_token.owner];
+    let paused = config.is_paused && (config.auto_unpause_at == 0 || now < config.auto_unpause_at);
+    let pause_bypassed = paused
+        && config.allow_admin_during_pause
+        && sides.iter().any(|side| *side == config.authority || is_fee_collector(config, side));
+    if paused && !pause_bypassed {
+        return reject_transfer(config, mint, owner, amount, ErrorCode::HookPaused);
+    }
+
+    // The sender's whitelist status lives in the missing state
+    if config.whitelist_mode {
+        return reject_transfer(config, mint, owner, amount, ErrorCode::NotWhitelisted);
+    }
+    if config.block_collector_transfers && sides.iter().any(|side| is_fee_collector(config, side)) {
+        return reject_transfer(config, mint, owner, amount, ErrorCode::CollectorTransferBlocked);
+    }
+    if config.require_memo
+        && !ctx
+            .accounts
+            .instructions_sysvar
+            .as_ref()
+            .is_some_and(|sysvar| has_memo_instruction(sysvar))
+    {
+        return reject_transfer(config, mint, owner, amount, ErrorCode::MemoRequired);
+    }
+
+    let mint_authority = ctx.accounts.mint.mint_authority;
+    let authority_exempt = config.exempt_mint_authority
+        && sides.iter().any(|side| mint_authority == COption::Some(*side));
+    if !authority_exempt
+        && ctx.accounts.source_token.key() != ctx.accounts.destination_token.key()
+        && exceeds_max_balance(config, ctx.accounts.destination_token.amount)
+    {
+        return reject_transfer(config, mint, owner, amount, ErrorCode::MaxBalanceExceeded);
+    }
+    if !authority_exempt && !meets_min_stake(config, ctx.accounts.stake_account.as_deref(), &owner) {
+        return reject_transfer(config, mint, owner, amount, ErrorCode::InsufficientStake);
+    }
+
+    let priced_amount = if clock_available && config.use_scaled_amount {
+        scaled_amount(&ctx.accounts.mint.to_account_info(), amount, now)
+    } else {
+        amount
+    };
+    let fee_tier = get_fee_tier(config, priced_amount);
+    let sink_destination = is_fee_sink(config, ctx.accounts.fee_sink.as_ref());
+    let event_active = !authority_exempt
+        && !sink_destination
+        && clock_available
+        && is_event_running(&config.active_event, now);
+    let fee_bps = if event_active {
+        config.active_event.fee_bps
+    } else if authority_exempt || sink_destination || !config.tier_fees_enabled[fee_tier] {
+        0
+    } else if clock_available {
+        launch_ramp_bps(config, calculate_fee_tier(config, priced_amount), now)
+    } else {
+        calculate_fee_tier(config, priced_amount)
+    };
+    let FeeBreakdown { final_fee, .. } =
+        finalize_fee(config, compute_final_fee(amount, fee_bps, 0)?, amount, config.max_fee);
+
+    if !authority_exempt {
+        record_global_transfer(config, amount, fee_tier, final_fee, 0, 0);
+        record_recount_flow(config, None, amount);
+        if clock_available {
+            track_volume_spike(config, mint, amount, now);
+            record_hourly_stats(config, mint, amount, final_fee, now);
+        }
+        route_fee_shares(config, final_fee)?;
+    }
+
+    if let Some(guard) = ctx.accounts.reentrancy_guard.as_mut() {
+        guard.locked = false;
+    }
+
+    log_at!(config, LOG_INFO, "🫥 Stateless transfer: {} (fee {}bps: {})", amount, fee_bps, final_fee);
+
+    let use_cpi_events = config.use_cpi_events;
+    let event = StatelessTransferExecuted {
+        mint,
+        owner,
+        amount,
+        fee_tier: fee_tier as u8,
+        fee_bps,
+        final_fee,
+        pause_bypassed,
+        label: config.label,
+        timestamp: now,
+    };
+    if use_cpi_events {
+        emit_cpi!(event);
+    } else {
+        emit!(event);
+    }
+
+    Ok(())
+}
+
+/// Whether admin actions may run under the current deployment: always
+/// when no upgrade has been acknowledged, else only with the program data
+/// of the acknowledged one
+fn upgrade_acknowledged(config: &HookConfig, program_data: Option<&ProgramData>) -> bool {
+    config.acknowledged_deploy_slot == 0
+        || program_data.is_some_and(|data| data.slot == config.acknowledged_deploy_slot)
+}
+
+/// Add a transfer to the current hour bucket, first emitting and
+/// resetting the bucket if the hour has rolled over
+fn record_hourly_stats(config: &mut HookConfig, mint: Pubkey, amount: u64, fee: u64, now: i64) {
+    if !config.hourly_stats {
+        return;
+    }
+    let hour = now.div_euclid(HOUR_SECONDS);
+    if hour != config.current_hour {
+        if config.hour_transfers > 0 {
+            emit!(HourlyStats {
+                mint,
+                hour_start: config.current_hour * HOUR_SECONDS,
+                transfers: config.hour_transfers,
+                volume: config.hour_volume,
+                fees: config.hour_fees,
+            });
+        }
+        config.current_hour = hour;
+        config.hour_transfers = 0;
+        config.hour_volume = 0;
+        config.hour_fees = 0;
+    }
+    config.hour_transfers = config.hour_transfers.saturating_add(1);
+    config.hour_volume = config.hour_volume.saturating_add(amount);
+    config.hour_fees = config.hour_fees.saturating_add(fee);
+}
+
+/// Add `amount` to the mint-wide spike window and trip the circuit
+/// breaker (pause until manually lifted) once it exceeds `spike_threshold`
+fn track_volume_spike(config: &mut HookConfig, mint: Pubkey, amount: u64, now: i64) {
+    if config.spike_threshold == 0 {
+        return;
+    }
+    if now.saturating_sub(config.spike_window_start) >= config.spike_window_seconds {
+        config.spike_window_start = now;
+        config.spike_window_volume = 0;
+    }
+    config.spike_window_volume = config.spike_window_volume.saturating_add(amount);
+    if config.spike_window_volume > config.spike_threshold && !config.is_paused {
+        config.is_paused = true;
+        config.auto_unpause_at = 0;
+        config.auto_paused = true;
+        log_at!(config, LOG_ERRORS, "🚨 Circuit breaker tripped: {} in window", config.spike_window_volume);
+        emit!(CircuitBreakerTripped {
+            mint,
+            window_volume: config.spike_window_volume,
+            spike_threshold: config.spike_threshold,
+            window_start: config.spike_window_start,
+            timestamp: now,
+        });
+    }
+}
+
+/// Add a transfer and its net (post-discount) fee to the global
+/// statistics, attributing both to the transfer's amount tier
+fn record_global_transfer(
+    config: &mut HookConfig,
+    amount: u64,
+    fee_tier: usize,
+    final_fee: u64,
+    discount_amount: u64,
+    sol_fee: u64,
+) {
+    config.total_transfers = config.total_transfers.saturating_add(1);
+    config.total_volume = config.total_volume.saturating_add(amount);
+    config.total_fees_collected = config.total_fees_collected.saturating_add(final_fee);
+    config.cumulative_fee_bps_weighted = config
+        .cumulative_fee_bps_weighted
+        .saturating_add(effective_bps_weight(final_fee));
+    config.total_discounts_given = config.total_discounts_given.saturating_add(discount_amount);
+    config.total_sol_fees_collected = config.total_sol_fees_collected.saturating_add(sol_fee);
+    config.largest_transfer = config.largest_transfer.max(amount);
+    config.tier_transfer_counts[fee_tier] = config.tier_transfer_counts[fee_tier].saturating_add(1);
+    config.tier_fees[fee_tier] = config.tier_fees[fee_tier].saturating_add(final_fee);
+}
+
+/// Keep what a recount rebuilds in step with a transfer. `sender` is the
+/// sender's state and whether it tracks volume, or `None` when stateless.
+/// Anything the per-user sums miss goes to the untracked counters, and a
+/// state already tallied by the open recount adds its transfer directly.
+fn record_recount_flow(config: &mut HookConfig, sender: Option<(&UserState, bool)>, amount: u64) {
+    let Some((state, tracked)) = sender else {
+        config.untracked_transfers = config.untracked_transfers.saturating_add(1);
+        config.untracked_volume = config.untracked_volume.saturating_add(amount);
+        return;
+    };
+    if !tracked {
+        config.untracked_volume = config.untracked_volume.saturating_add(amount);
+    }
+    if config.recount_active && state.recount_session == config.recount_session {
+        config.recount_transfers = config.recount_transfers.saturating_add(1);
+        if tracked {
+            config.recount_volume = config.recount_volume.saturating_add(amount);
+        }
+    }
+}
+
+/// Add `amount` of rebate credit after forfeiting any expired balance,
+/// restarting the balance's expiry (see `set_rebate_validity`)
+fn credit_rebate(config: &HookConfig, user_state: &mut UserState, mint: Pubkey, user: Pubkey, amount: u64, now: i64) {
+    forfeit_expired_rebate(user_state, mint, user, now);
+    if amount == 0 {
+        return;
+    }
+    user_state.rebate_balance = user_state.rebate_balance.saturating_add(amount);
+    user_state.rebate_expires_at = if config.rebate_validity_seconds > 0 {
+        now.saturating_add(config.rebate_validity_seconds)
+    } else {
+        0
+    };
+}
+
+/// Take up to `fee` out of the user's rebate balance, returning the amount
+/// taken; credit that may have expired is left alone without the clock
+fn apply_rebate(user_state: &mut UserState, fee: u64, clock_available: bool) -> u64 {
+    if !clock_available && user_state.rebate_expires_at != 0 {
+        return 0;
+    }
+    let applied = fee.min(user_state.rebate_balance);
+    user_state.rebate_balance -= applied;
+    applied
+}
+
+/// Zero an expired rebate balance, emitting `RebateForfeited`; returns the
+/// amount forfeited
+fn forfeit_expired_rebate(user_state: &mut UserState, mint: Pubkey, user: Pubkey, now: i64) -> u64 {
+    let forfeited = user_state.rebate_balance;
+    if forfeited == 0 || user_state.rebate_expires_at == 0 || now < user_state.rebate_expires_at {
+        return 0;
+    }
+    user_state.rebate_balance = 0;
+    user_state.rebate_expires_at = 0;
+    emit!(RebateForfeited { mint, user, amount: forfeited });
+    forfeited
+}
+
+/// Release the user's escrowed fee, if any, routing its shares as a
+/// directly collected fee would have been
+fn release_escrow(config: &mut HookConfig, user_state: &mut UserState) -> Result<()> {
+    let amount = user_state.escrowed_fee;
+    if amount == 0 {
+        return Ok(());
+    }
+    route_fee_shares(config, amount)?;
+    config.total_fees_escrowed = config.total_fees_escrowed.saturating_sub(amount);
+    user_state.escrowed_fee = 0;
+    user_state.escrow_expires_at = 0;
+    Ok(())
+}
+
+/// Record the rewards share of `final_fee` as owed to the rewards vault
+/// (it accrues to holders once `fund_rewards` pays it in) and set aside
+/// the stakers' share; the stake pool picks it up on its next sync
+fn route_fee_shares(config: &mut HookConfig, final_fee: u64) -> Result<()> {
+    let rewards_cut = bps_to_fee(final_fee, config.rewards_bps)
+        .ok_or(error!(ErrorCode::ArithmeticOverflow))?;
+    config.unfunded_rewards = config.unfunded_rewards.saturating_add(rewards_cut);
+
+    let staking_cut = bps_to_fee(final_fee, config.staking_bps)
+        .ok_or(error!(ErrorCode::ArithmeticOverflow))?;
+    config.pending_staking_rewards = config.pending_staking_rewards.saturating_add(staking_cut);
+    Ok(())
+}
+
+/// A label is printable ASCII followed only by zero padding
+fn is_valid_label(label: &[u8; LABEL_LEN]) -> bool {
+    let len = label.iter().position(|&byte| byte == 0).unwrap_or(LABEL_LEN);
+    label[..len].iter().all(|&byte| byte == b' ' || byte.is_ascii_graphic())
+        && label[len..].iter().all(|&byte| byte == 0)
+}
+
+/// The label without its zero padding
+fn label_str(label: &[u8; LABEL_LEN]) -> &str {
+    let len = label.iter().position(|&byte| byte == 0).unwrap_or(LABEL_LEN);
+    std::str::from_utf8(&label[..len]).unwrap_or_default()
+}
+
+/// Emit `TransferRejected` and fail the transfer with `code`.
+/// The failed instruction reverts every state write, so no on-chain
+/// rejection counter can survive; the event in the failed transaction's
+/// logs is the durable record. `reason_code` is the Anchor error number.
+fn reject_transfer(
+    config: &HookConfig,
+    mint: Pubkey,
+    owner: Pubkey,
+    amount: u64,
+    code: ErrorCode,
+) -> Result<()> {
+    log_at!(config, LOG_ERRORS, "⛔ Transfer rejected: {}", code);
+    emit!(TransferRejected {
+        mint,
+        owner,
+        amount,
+        reason_code: code.into(),
+        label: config.label,
+    });
+    Err(error!(code))
+}
+
+/// Determine fee tier index (0-3) based on transfer amount
+fn get_fee_tier(config: &HookConfig, amount: u64) -> usize {
+    config
+        .fee_tier_thresholds
+        .iter()
+        .position(|&threshold| amount < threshold)
+        .unwrap_or(FEE_TIER_THRESHOLD_COUNT)
+}
+
+/// Calculate fee tier based on transfer amount
+fn calculate_fee_tier(config: &HookConfig, amount: u64) -> u16 {
+    config.fee_tier_bps[get_fee_tier(config, amount)]
+}
+
+/// Determine loyalty tier index (0 = no tier) based on transfer count.
+/// Thresholds are strictly increasing, so the highest tier met wins.
+fn get_loyalty_tier(config: &HookConfig, transfer_count: u64) -> u8 {
+    let count = config.loyalty_tier_count as usize;
+    config.loyalty_tier_thresholds[..count]
+        .iter()
+        .rposition(|&threshold| transfer_count >= threshold)
+        .map_or(0, |index| index as u8 + 1)
+}
+
+/// Highest tier reachable with `distinct_counterparties` destinations:
+/// just below the first tier whose minimum is not met
+fn counterparty_tier_cap(config: &HookConfig, distinct_counterparties: u32) -> u8 {
+    config
+        .min_counterparties_per_tier
+        .iter()
+        .position(|&minimum| distinct_counterparties < minimum)
+        .map_or(MAX_LOYALTY_TIERS as u8, |index| index as u8)
+}
+
+/// The user's loyalty tier: earned by their loyalty count, capped by
+/// their (approximate) number of distinct destinations
+fn user_loyalty_tier(config: &HookConfig, user_state: &UserState) -> u8 {
+    get_loyalty_tier(config, loyalty_count(user_state))
+        .min(counterparty_tier_cap(config, user_state.distinct_dest_estimate))
+}
+
+/// Validate a full schedule in one pass: strictly increasing fee and
+/// loyalty thresholds, every bps within 10000, and no loyalty discount
+/// larger than the highest tier fee (it could never be fully applied)
+fn validate_schedule(schedule: &FeeSchedule) -> Result<()> {
+    require!(
+        schedule.fee_tier_thresholds.windows(2).all(|pair| pair[0] < pair[1]),
+        ErrorCode::InvalidFeeConfig
+    );
+    require!(
+        schedule.fee_tier_bps.iter().all(|&bps| bps <= 10000)
+            && schedule.first_transfer_fee_bps <= 10000,
+        ErrorCode::InvalidFeeConfig
+    );
+
+    let count = schedule.loyalty_tier_count as usize;
+    require!(count <= MAX_LOYALTY_TIERS, ErrorCode::InvalidLoyaltyConfig);
+    validate_loyalty_ladder(
+        &schedule.loyalty_tier_thresholds[..count],
+        &schedule.loyalty_tier_discount_bps[..count],
+    )?;
+
+    let max_fee_bps = schedule.fee_tier_bps.iter().copied().max().unwrap_or(0);
+    require!(
+        schedule.loyalty_tier_discount_bps[..count]
+            .iter()
+            .all(|&bps| bps <= max_fee_bps),
+        ErrorCode::InvalidFeeConfig
+    );
+    Ok(())
+}
+
+/// The default 4-tier fee schedule and Bronze/Silver/Gold loyalty ladder
+fn default_schedule() -> FeeSchedule {
+    let mut loyalty_tier_thresholds = [0; MAX_LOYALTY_TIERS];
+    loyalty_tier_thresholds[..3].copy_from_slice(&[LOYALTY_BRONZE, LOYALTY_SILVER, LOYALTY_GOLD]);
+    let mut loyalty_tier_discount_bps = [0; MAX_LOYALTY_TIERS];
+    loyalty_tier_discount_bps[..3].copy_from_slice(&[
+        LOYALTY_BRONZE_DISCOUNT_BPS,
+        LOYALTY_SILVER_DISCOUNT_BPS,
+        LOYALTY_GOLD_DISCOUNT_BPS,
+    ]);
+
+    FeeSchedule {
+        fee_tier_thresholds: [TIER_1_THRESHOLD, TIER_2_THRESHOLD, TIER_3_THRESHOLD],
+        fee_tier_bps: [TIER_1_FEE_BPS, TIER_2_FEE_BPS, TIER_3_FEE_BPS, TIER_4_FEE_BPS],
+        loyalty_tier_count: 3,
+        loyalty_tier_thresholds,
+        loyalty_tier_discount_bps,
+        first_transfer_fee_bps: 0,
+        max_fee: 0,
+    }
+}
+
+/// Write a validated schedule, zeroing unused loyalty slots so the
+/// stored schedule (and its hash) is canonical
+fn apply_schedule(config: &mut HookConfig, schedule: &FeeSchedule) {
+    let count = schedule.loyalty_tier_count as usize;
+
+    config.fee_tier_thresholds = schedule.fee_tier_thresholds;
+    config.fee_tier_bps = schedule.fee_tier_bps;
+    config.loyalty_tier_count = schedule.loyalty_tier_count;
+    config.loyalty_tier_thresholds = [0; MAX_LOYALTY_TIERS];
+    config.loyalty_tier_thresholds[..count].copy_from_slice(&schedule.loyalty_tier_thresholds[..count]);
+    config.loyalty_tier_discount_bps = [0; MAX_LOYALTY_TIERS];
+    config.loyalty_tier_discount_bps[..count]
+        .copy_from_slice(&schedule.loyalty_tier_discount_bps[..count]);
+    config.first_transfer_fee_bps = schedule.first_transfer_fee_bps;
+    config.max_fee = schedule.max_fee;
+}
+
+/// The schedule currently stored in the config
+fn current_schedule(config: &HookConfig) -> FeeSchedule {
+    FeeSchedule {
+        fee_tier_thresholds: config.fee_tier_thresholds,
+        fee_tier_bps: config.fee_tier_bps,
+        loyalty_tier_count: config.loyalty_tier_count,
+        loyalty_tier_thresholds: config.loyalty_tier_thresholds,
+        loyalty_tier_discount_bps: config.loyalty_tier_discount_bps,
+        first_transfer_fee_bps: config.first_transfer_fee_bps,
+        max_fee: config.max_fee,
+    }
+}
+
+/// Reject a fee change once the schedule is locked, unless it can only
+/// lower fees and decreases are still allowed
+fn require_fee_change_allowed(config: &HookConfig, is_decrease: bool) -> Result<()> {
+    require!(
+        !config.fee_schedule_locked || (config.allow_decrease_when_locked && is_decrease),
+        ErrorCode::FeeScheduleLocked
+    );
+    Ok(())
+}
+
+/// Whether moving from `current` to `proposed` can only lower fees: the
+/// same tier and loyalty boundaries, no higher tier bps or first-transfer
+/// fee (which turning off would replace with the tier fee), no smaller
+/// loyalty discounts and no looser cap
+fn schedule_is_decrease(current: &FeeSchedule, proposed: &FeeSchedule) -> bool {
+    let count = current.loyalty_tier_count as usize;
+    let first_fee = proposed.first_transfer_fee_bps == current.first_transfer_fee_bps
+        || (proposed.first_transfer_fee_bps != 0
+            && proposed.first_transfer_fee_bps <= current.first_transfer_fee_bps);
+    let cap = proposed.max_fee == current.max_fee
+        || (proposed.max_fee != 0 && (current.max_fee == 0 || proposed.max_fee <= current.max_fee));
+
+    proposed.fee_tier_thresholds == current.fee_tier_thresholds
+        && proposed.fee_tier_bps.iter().zip(&current.fee_tier_bps).all(|(new, old)| new <= old)
+        && proposed.loyalty_tier_count == current.loyalty_tier_count
+        && proposed.loyalty_tier_thresholds[..count] == current.loyalty_tier_thresholds[..count]
+        && proposed.loyalty_tier_discount_bps[..count]
+            .iter()
+            .zip(&current.loyalty_tier_discount_bps[..count])
+            .all(|(new, old)| new >= old)
+        && first_fee
+        && cap
+}
+
+/// Keccak hash of the Borsh-serialized schedule plus every other field
+/// that prices a transfer
+fn effective_schedule_hash(config: &HookConfig) -> Result<[u8; 32]> {
+    let mut data = current_schedule(config).try_to_vec()?;
+    (
+        config.tier_fees_enabled,
+        config.surcharge_new_highs,
+        config.new_high_surcharge_bps,
+        config.fee_granularity,
+        config.fee_rounding_mode,
+        config.use_scaled_amount,
+        config.exempt_mint_authority,
+        config.fee_payer_mode,
+        config.receiver_fee_share_bps,
+    )
+        .serialize(&mut data)?;
+    (
+        config.free_allowance_per_period,
+        config.onboarding_free_seconds,
+        config.aggregation_seconds,
+        config.defi_fee_bps,
+        config.defi_owner_count,
+        config.defi_owners,
+    )
+        .serialize(&mut data)?;
+    (
+        config.continuous_discount,
+        config.volume_per_bp,
+        config.max_continuous_discount_bps,
+        config.holding_discount_mode,
+        config.holding_discount_per_day_bps,
+        config.max_holding_discount_bps,
+        config.loyalty_decay_seconds,
+        config.decay_grace_transfers,
+        config.max_total_discount_bps,
+        config.dust_consolidation_discount_bps,
+    )
+        .serialize(&mut data)?;
+    (
+        config.supply_sensitivity_bps,
+        config.max_supply_adjustment_bps,
+        config.supply_window_seconds,
+        config.fee_in_sol,
+        config.sol_fee_collector,
+        config.sol_fee_per_transfer,
+        config.lamports_per_token,
+        config.fee_mint,
+        config.fee_token_rate,
+        config.min_fee_in_fee_token,
+    )
+        .serialize(&mut data)?;
+    (
+        config.sink_exemption,
+        config.launch_timestamp,
+        config.launch_fee_bps,
+        config.ramp_seconds,
+        config.balance_fee_enabled,
+        config.balance_per_bp,
+        config.max_balance_fee_bps,
+        config.min_counterparties_per_tier,
+        config.active_event,
+    )
+        .serialize(&mut data)?;
+    Ok(keccak::hash(&data).to_bytes())
+}
+
+/// Recompute `schedule_hash` after a fee-setting change, announcing it
+/// when it moved
+fn refresh_schedule_hash(config: &mut HookConfig, mint: Pubkey) -> Result<()> {
+    let schedule_hash = effective_schedule_hash(config)?;
+    if schedule_hash != config.schedule_hash {
+        config.schedule_hash = schedule_hash;
+        emit!(FeeScheduleChanged { mint, schedule_hash });
+    }
+    Ok(())
+}
+
+/// Deterministic ~1-in-`rate` pick of a transfer for audit sampling
+fn is_audit_sample(rate: u32, slot: u64, timestamp: i64, owner: &Pubkey, amount: u64) -> bool {
+    if rate == 0 {
+        return false;
+    }
+    let hash = keccak::hashv(&[
+        &slot.to_le_bytes(),
+        &timestamp.to_le_bytes(),
+        owner.as_ref(),
+        &amount.to_le_bytes(),
+    ]);
+    let draw = hash.to_bytes()[..8].iter().fold(0u64, |draw, &byte| draw << 8 | byte as u64);
+    draw % rate as u64 == 0
+}
+
+/// Whether `owner` has at least `min_stake` staked (always, when unset);
+/// a missing stake account counts as nothing staked
+fn meets_min_stake(config: &HookConfig, stake_account: Option<&StakeAccount>, owner: &Pubkey) -> bool {
+    config.min_stake == 0
+        || stake_account.is_some_and(|stake| stake.owner == *owner && stake.amount >= config.min_stake)
+}
+
+/// Take a new supply reference once the observation window has elapsed
+/// (or none has been taken yet); a zero supply is never a reference
+fn observe_supply(config: &mut HookConfig, supply: u64, now: i64) {
+    if config.supply_sensitivity_bps == 0 || supply == 0 {
+        return;
+    }
+    if config.supply_observed_at == 0
+        || now.saturating_sub(config.supply_observed_at) >= config.supply_window_seconds
+    {
+        config.last_observed_supply = supply;
+        config.supply_observed_at = now;
+    }
+}
+
+/// Bps added to (or, when negative, taken off) the tier bps for the supply
+/// change since the reference, bounded by `max_supply_adjustment_bps`;
+/// none while either supply is 0
+fn supply_adjustment_bps(config: &HookConfig, supply: u64) -> i32 {
+    if config.supply_sensitivity_bps == 0 || config.last_observed_supply == 0 || supply == 0 {
+        return 0;
+    }
+    let reference = config.last_observed_supply as i128;
+    let change_bps = (supply as i128 - reference) * 10000 / reference;
+    let adjustment = change_bps * config.supply_sensitivity_bps as i128 / 10000;
+    let bound = config.max_supply_adjustment_bps as i128;
+    adjustment.clamp(-bound, bound) as i32
+}
+
+/// `fee_bps` moved by `adjustment_bps`, kept within 0..=10000
+fn apply_supply_adjustment(fee_bps: u16, adjustment_bps: i32) -> u16 {
+    (fee_bps as i32 + adjustment_bps).clamp(0, 10000) as u16
+}
+
+/// Whether a transfer at `slot` comes fewer than `min_slot_spacing` slots
+/// after the user's previous one (never for their first)
+fn violates_slot_spacing(config: &HookConfig, user_state: &UserState, slot: u64) -> bool {
+    config.min_slot_spacing > 0
+        && user_state.last_transfer_slot > 0
+        && slot.saturating_sub(user_state.last_transfer_slot) < config.min_slot_spacing
+}
+
+/// Cooldown for a user with `rapid_streak`, capped at `max_cooldown_seconds`
+fn effective_cooldown(config: &HookConfig, rapid_streak: u16) -> i64 {
+    let cooldown = config
+        .cooldown_seconds
+        .saturating_mul(1 + rapid_streak as i64);
+    if config.max_cooldown_seconds > 0 {
+        cooldown.min(config.max_cooldown_seconds)
+    } else {
+        cooldown
+    }
+}
+
+/// Extend the rapid streak for a transfer `gap` seconds after the previous
+/// one inside the calm period, or reset it once the user has been calm
+fn update_rapid_streak(config: &HookConfig, user_state: &mut UserState, gap: i64) {
+    user_state.rapid_streak = if gap < config.cooldown_calm_seconds {
+        user_state.rapid_streak.saturating_add(1)
+    } else {
+        0
+    };
+}
+
+/// Decay the user's loyalty progress if this transfer follows a gap of at
+/// least `loyalty_decay_seconds`, returning whether decay is pending instead.
+/// Gaps are measured from `last_transfer_timestamp`, which grace transfers
+/// still update: a follow-up inside the decay window closes the gap and
+/// clears the grace count, so decay only lands after `decay_grace_transfers`
+/// consecutive gapped transfers, on the next gapped one.
+fn apply_loyalty_decay(config: &HookConfig, user_state: &mut UserState, now: i64) -> bool {
+    if config.loyalty_decay_seconds == 0 || user_state.transfer_count == 0 {
+        return false;
+    }
+
+    if now.saturating_sub(user_state.last_transfer_timestamp) < config.loyalty_decay_seconds {
+        user_state.decay_grace_used = 0;
+        return false;
+    }
+
+    if user_state.decay_grace_used < config.decay_grace_transfers {
+        user_state.decay_grace_used += 1;
+        return true;
+    }
+
+    user_state.loyalty_count_base = user_state.transfer_count;
+    user_state.loyalty_credit = 0;
+    user_state.decay_grace_used = 0;
+    false
+}
+
+/// Transfers counted toward loyalty since the user's last decay, plus any
+/// seeded historical credit
+fn loyalty_count(user_state: &UserState) -> u64 {
+    user_state
+        .transfer_count
+        .saturating_sub(user_state.loyalty_count_base)
+        .saturating_add(user_state.loyalty_credit)
+}
+
+/// The tier a transfer has just carried the user into, if it is above
+/// `previous_tier` (the tier before the count was incremented) and above
+/// every tier they have held before, so each crossing is reported once
+fn detect_tier_up(config: &HookConfig, user_state: &mut UserState, previous_tier: u8) -> Option<u8> {
+    let tier = user_loyalty_tier(config, user_state);
+    let crossed = tier > previous_tier && tier > user_state.highest_tier_reached;
+    user_state.highest_tier_reached = user_state.highest_tier_reached.max(tier);
+    crossed.then_some(tier)
+}
+
+/// Loyalty tier from the user's cache while it is valid and the loyalty
+/// count is still inside the cached tier's bounds; otherwise recomputed.
+/// Crossing a threshold always recomputes, so the cache can only be stale
+/// for ladder changes that keep the count inside the old tier's bounds,
+/// and then for at most `tier_cache_seconds`.
+fn cached_loyalty_tier(config: &HookConfig, user_state: &mut UserState, now: i64) -> u8 {
+    let tier = user_state.cached_tier;
+    let count = loyalty_count(user_state);
+    let thresholds = &config.loyalty_tier_thresholds;
+
+    let still_valid = now < user_state.cached_tier_valid_until
+        && tier <= config.loyalty_tier_count
+        && (tier == 0 || count >= thresholds[tier as usize - 1])
+        && (tier == config.loyalty_tier_count || count < thresholds[tier as usize]);
+
+    if still_valid {
+        tier
+    } else {
+        refresh_cached_tier(config, user_state, now)
+    }
+}
+
+/// Recompute the user's loyalty tier and cache it for `tier_cache_seconds`
+fn refresh_cached_tier(config: &HookConfig, user_state: &mut UserState, now: i64) -> u8 {
+    let tier = user_loyalty_tier(config, user_state);
+    user_state.cached_tier = tier;
+    user_state.cached_tier_valid_until = now.saturating_add(config.tier_cache_seconds);
+    tier
+}
+
+/// Validate a loyalty ladder: at most `MAX_LOYALTY_TIERS` tiers,
+/// strictly increasing thresholds and discounts within 10000 bps
+fn validate_loyalty_ladder(thresholds: &[u64], discount_bps: &[u16]) -> Result<()> {
+    require!(
+        thresholds.len() == discount_bps.len() && thresholds.len() <= MAX_LOYALTY_TIERS,
+        ErrorCode::InvalidLoyaltyConfig
+    );
+    require!(
+        thresholds.windows(2).all(|pair| pair[0] < pair[1]),
+        ErrorCode::InvalidLoyaltyConfig
+    );
+    require!(
+        discount_bps.iter().all(|&bps| bps <= 10000),
+        ErrorCode::InvalidLoyaltyConfig
+    );
+    Ok(())
+}
+
+/// Rewards accrued by `volume` at the given accumulator value, scaled by `REWARD_PRECISION`
+fn scaled_rewards(volume: u64, reward_per_volume: u128) -> Result<u128> {
+    (volume as u128)
+        .checked_mul(reward_per_volume)
+        .ok_or(error!(ErrorCode::ArithmeticOverflow))
+}
+
+/// Move the user's rewards accrued since their last settlement into `reward_share`.
+/// The sub-unit remainder stays in the scaled debt, so nothing is lost to rounding.
+fn settle_rewards(config: &HookConfig, user_state: &mut UserState) -> Result<()> {
+    let accrued = scaled_rewards(user_state.total_volume, config.reward_per_volume)?;
+    let pending = accrued.saturating_sub(user_state.reward_debt) / REWARD_PRECISION;
+
+    user_state.reward_share = user_state.reward_share.saturating_add(pending as u64);
+    user_state.reward_debt += pending * REWARD_PRECISION;
+    Ok(())
+}
+
+/// Add `amount` of new claimable volume, excluding rewards accrued before it
+fn add_reward_volume(config: &mut HookConfig, user_state: &mut UserState, amount: u64) -> Result<()> {
+    user_state.reward_debt = user_state
+        .reward_debt
+        .checked_add(scaled_rewards(amount, config.reward_per_volume)?)
+        .ok_or(error!(ErrorCode::ArithmeticOverflow))?;
+    config.claimable_volume = config
+        .claimable_volume
+        .checked_add(amount)
+        .ok_or(error!(ErrorCode::ArithmeticOverflow))?;
+    Ok(())
+}
+
+/// Spread `rewards` across the claimable volume through the accumulator.
+/// Volume of opted-out, exempt or minter transfers never earns a share,
+/// so none of it is stranded. Rounds down, so the sum of claims never
+/// exceeds what was accrued.
+fn accrue_rewards(config: &mut HookConfig, rewards: u64) -> Result<()> {
+    let claimable_volume = config.claimable_volume;
+    if rewards == 0 || claimable_volume == 0 {
+        return Ok(());
+    }
+
+    let increment = (rewards as u128)
+        .checked_mul(REWARD_PRECISION)
+        .ok_or(error!(ErrorCode::ArithmeticOverflow))?
+        / (claimable_volume as u128);
+
+    config.reward_per_volume = config
+        .reward_per_volume
+        .checked_add(increment)
+        .ok_or(error!(ErrorCode::ArithmeticOverflow))?;
+    config.total_rewards_accrued = config.total_rewards_accrued.saturating_add(rewards);
+    Ok(())
+}
+
+/// Spread the staking share of fees set aside since the last sync across
+/// the current stake. Rounds down, so claims never exceed what was accrued;
+/// fees earned while nothing is staked are dropped.
+fn sync_stake_pool(config: &mut HookConfig, pool: &mut StakePool) -> Result<()> {
+    let rewards = std::mem::take(&mut config.pending_staking_rewards);
+    if rewards == 0 || pool.total_staked == 0 {
+        return Ok(());
+    }
+
+    let increment = (rewards as u128)
+        .checked_mul(REWARD_PRECISION)
+        .ok_or(error!(ErrorCode::ArithmeticOverflow))?
+        / (pool.total_staked as u128);
+
+    pool.reward_per_stake = pool
+        .reward_per_stake
+        .checked_add(increment)
+        .ok_or(error!(ErrorCode::ArithmeticOverflow))?;
+    pool.total_rewards_accrued = pool.total_rewards_accrued.saturating_add(rewards);
+    Ok(())
+}
+
+/// Whether a closed vote passes: a yes majority with at least
+/// `quorum_votes` of weight cast either way
+fn proposal_passes(yes_votes: u64, no_votes: u64, quorum_votes: u64) -> bool {
+    yes_votes > no_votes && yes_votes.saturating_add(no_votes) >= quorum_votes
+}
+
+/// Move the staker's rewards accrued since their last settlement into
+/// `pending_rewards`. Callers reset `reward_debt` after changing the stake.
+fn settle_stake(pool: &StakePool, stake_account: &mut StakeAccount) -> Result<()> {
+    let accrued = scaled_rewards(stake_account.amount, pool.reward_per_stake)?;
+    let pending = accrued.saturating_sub(stake_account.reward_debt) / REWARD_PRECISION;
+
+    stake_account.pending_rewards = stake_account.pending_rewards.saturating_add(pending as u64);
+    stake_account.reward_debt += pending * REWARD_PRECISION;
+    Ok(())
+}
+
+/// `TIER_BENEFIT_*` mask unlocked by a loyalty tier index
+fn tier_benefits(config: &HookConfig, tier: u8) -> u8 {
+    match tier {
+        0 => 0,
+        tier => config.tier_benefits[tier as usize - 1],
+    }
+}
+
+/// Holding-duration discount bps. "Holding" counts from the user's
+/// `first_transfer_timestamp`, the first transfer they sent through the
+/// hook: tokens received earlier do not count, and later sales do not
+/// reset it. That timestamp is set by the first transfer itself, so a
+/// first transfer always has 0 days held.
+fn holding_discount_bps(config: &HookConfig, user_state: &UserState, now: i64) -> u16 {
+    if config.holding_discount_mode == HOLDING_DISCOUNT_OFF || user_state.transfer_count == 0 {
+        return 0;
+    }
+    let days_held = now.saturating_sub(user_state.first_transfer_timestamp).max(0) / DAILY_WINDOW_SECONDS;
+    let bps = (days_held as u128) * (config.holding_discount_per_day_bps as u128);
+    bps.min(config.max_holding_discount_bps as u128) as u16
+}
+
+/// Discount bps on the continuous curve for a lifetime `volume`; the fee
+/// pipeline caps the resulting discount at the base fee
+fn continuous_discount_bps(config: &HookConfig, volume: u64) -> u16 {
+    if config.volume_per_bp == 0 {
+        return 0;
+    }
+    let bps = (volume as u128) / (config.volume_per_bp as u128);
+    bps.min(config.max_continuous_discount_bps as u128) as u16
+}
+
+/// Whether a transfer sweeps dust: a nonzero `amount` below the tier-1
+/// threshold that leaves the source (post-transfer) balance at zero
+fn is_dust_consolidation(config: &HookConfig, amount: u64, source_balance_after: u64) -> bool {
+    config.dust_consolidation_discount_bps > 0
+        && amount > 0
+        && amount < config.fee_tier_thresholds[0]
+        && source_balance_after == 0
+}
+
+/// Clamp the summed discount bps to `max_total_discount_bps` (when set)
+/// and to the fee bps, beyond which it has nothing left to discount
+fn clamp_total_discount(config: &HookConfig, fee_bps: u16, discount_bps: u16) -> u16 {
+    let cap = match config.max_total_discount_bps {
+        0 => fee_bps,
+        max_bps => max_bps.min(fee_bps),
+    };
+    discount_bps.min(cap)
+}
+
+/// Discount basis points granted by a loyalty tier index
+fn loyalty_discount_bps(config: &HookConfig, tier: u8) -> u16 {
+    match tier {
+        0 => 0,
+        tier => config.loyalty_tier_discount_bps[tier as usize - 1],
+    }
+}
+
+/// Fee amounts for a single transfer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FeeBreakdown {
+    pub base_fee: u64,
+    pub discount: u64,
+    pub final_fee: u64,
+}
+
+/// Fee charged on `amount` at `bps` basis points, rounded down to the base
+/// unit. Computed in u128; `None` only if the result does not fit in u64
+/// (possible for `bps > 10000`). This is the pricing primitive used on-chain.
+pub fn bps_to_fee(amount: u64, bps: u16) -> Option<u64> {
+    u64::try_from((amount as u128) * (bps as u128) / 10000).ok()
+}
+
+/// Effective basis points of `fee` on `amount`, rounded down.
+/// `None` for a zero amount or a rate above `u16::MAX` bps.
+pub fn fee_to_bps(amount: u64, fee: u64) -> Option<u16> {
+    if amount == 0 {
+        return None;
+    }
+    u16::try_from((fee as u128) * 10000 / (amount as u128)).ok()
+}
+
+/// Clamp the final fee to `max_fee` (0 = uncapped); the clamped amount
+/// counts as discount so `base_fee - discount == final_fee` still holds
+fn cap_fee(fee: FeeBreakdown, max_fee: u64) -> FeeBreakdown {
+    if max_fee == 0 || fee.final_fee <= max_fee {
+        return fee;
+    }
+    FeeBreakdown {
+        base_fee: fee.base_fee,
+        discount: fee.base_fee - max_fee,
+        final_fee: max_fee,
+    }
+}
+
+/// Apply the `max_fee` cap (see `effective_max_fee`) and then granularity
+/// rounding to a computed fee
+fn finalize_fee(config: &HookConfig, fee: FeeBreakdown, amount: u64, max_fee: u64) -> FeeBreakdown {
+    round_fee(
+        cap_fee(fee, max_fee),
+        config.fee_granularity,
+        config.fee_rounding_mode,
+        fee_ceiling(amount, max_fee),
+    )
+}
+
+/// The tighter of the global and a user's max fee, where each is set
+/// (0 = uncapped): a user cap can only lower the global one
+fn effective_max_fee(global_max_fee: u64, user_max_fee: u64) -> u64 {
+    match (global_max_fee, user_max_fee) {
+        (0, cap) | (cap, 0) => cap,
+        (global, user) => global.min(user),
+    }
+}
+
+/// Highest fee a transfer may be charged after rounding
+fn fee_ceiling(amount: u64, max_fee: u64) -> u64 {
+    if max_fee == 0 {
+        amount
+    } else {
+        amount.min(max_fee)
+    }
+}
+
+/// Fee-token base units worth `amount` base units of a mint with
+/// `decimals`, at `rate` fee-token units per whole token
+fn to_fee_token(amount: u64, rate: u64, decimals: u8) -> u64 {
+    let Some(unit) = 10u128.checked_pow(decimals as u32) else {
+        return 0;
+    };
+    ((amount as u128) * (rate as u128) / unit).min(u64::MAX as u128) as u64
+}
+
+/// Fee charged in the fee token: the converted final fee, floored at
+/// `min_fee_in_fee_token` so small fees don't round away, but never above
+/// the converted fee ceiling (the transfer's value, or `max_fee`).
+/// Fee-free transfers stay free.
+fn fee_in_fee_token(config: &HookConfig, final_fee: u64, amount: u64, decimals: u8) -> u64 {
+    if final_fee == 0 {
+        return 0;
+    }
+    let rate = config.fee_token_rate;
+    let ceiling = to_fee_token(fee_ceiling(amount, config.max_fee), rate, decimals);
+    to_fee_token(final_fee, rate, decimals)
+        .max(config.min_fee_in_fee_token)
+        .min(ceiling)
+}
+
+/// Round the final fee to a multiple of `granularity` (0 or 1 = base-unit
+/// precision). A result above `ceiling` falls back to rounding down, so
+/// rounding never pushes the fee above the amount or the cap. Rounding up
+/// may exceed the base fee, in which case the discount is reported as 0.
+fn round_fee(fee: FeeBreakdown, granularity: u64, mode: u8, ceiling: u64) -> FeeBreakdown {
+    if granularity <= 1 {
+        return fee;
+    }
+
+    let down = fee.final_fee - fee.final_fee % granularity;
+    let remainder = fee.final_fee - down;
+    let up = down.checked_add(granularity);
+    let rounded = match mode {
+        FEE_ROUND_DOWN => Some(down),
+        FEE_ROUND_UP if remainder > 0 => up,
+        FEE_ROUND_NEAREST if remainder > 0 && remainder >= granularity - remainder => up,
+        _ => Some(down),
+    };
+    let final_fee = rounded.filter(|&fee| fee <= ceiling).unwrap_or(down);
+
+    FeeBreakdown {
+        base_fee: fee.base_fee,
+        discount: fee.base_fee.saturating_sub(final_fee),
+        final_fee,
+    }
+}
+
+/// Compute the base fee, discount and final fee for a transfer.
+/// The discount is capped at the base fee, so `final_fee <= base_fee`.
+pub fn compute_final_fee(amount: u64, fee_bps: u16, discount_bps: u16) -> Result<FeeBreakdown> {
+    let base_fee = bps_to_fee(amount, fee_bps).ok_or(error!(ErrorCode::ArithmeticOverflow))?;
+    let raw_discount =
+        bps_to_fee(amount, discount_bps).ok_or(error!(ErrorCode::ArithmeticOverflow))?;
+
+    let final_fee = base_fee.saturating_sub(raw_discount);
+
+    Ok(FeeBreakdown {
+        base_fee,
+        discount: base_fee - final_fee,
+        final_fee,
+    })
+}
+
+// ============================================================================
+// Account Structures
+// ============================================================================
+
+#[derive(Accounts)]
+pub struct Initialize<'info> {
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + HookConfig::INIT_SPACE,
+        seeds = [b"hook-config", mint.key().as_ref()],
+        bump
+    )]
+    pub hook_config: Account<'info, HookConfig>,
+    
+    pub mint: InterfaceAccount<'info, Mint>,
+    
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    
+    pub system_program: Program<'info, System>,
+    
+    /// The fee collector's associated token account for the mint,
+    /// required with `validate_collector`
+    pub fee_collector_token: Option<InterfaceAccount<'info, TokenAccount>>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeExtraAccountMetaList<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    
+    /// CHECK: Extra account meta list account, sized for every slot so
+    /// `sync_extra_account_metas` never needs to reallocate
+    #[account(
+        init,
+        payer = payer,
+        space = ExtraAccountMetaList::size_of(EXTRA_ACCOUNT_META_COUNT)?,
+        seeds = [b"extra-account-metas", mint.key().as_ref()],
+        bump
+    )]
+    pub extra_account_meta_list: UncheckedAccount<'info>,
+    
+    pub mint: InterfaceAccount<'info, Mint>,
+    
+    #[account(
+        mut,
+        seeds = [b"hook-config", mint.key().as_ref()],
+        bump
+    )]
+    pub hook_config: Account<'info, HookConfig>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SyncExtraAccountMetas<'info> {
+    #[account(
+        mut,
+        seeds = [b"hook-config", mint.key().as_ref()],
+        bump,
+        has_one = authority
+    )]
+    pub hook_config: Account<'info, HookConfig>,
+    
+    pub mint: InterfaceAccount<'info, Mint>,
+    
+    /// CHECK: Extra account meta list account, rewritten in place and
+    /// resized when `EXTRA_ACCOUNT_META_COUNT` has grown since it was created
+    #[account(
+        mut,
+        seeds = [b"extra-account-metas", mint.key().as_ref()],
+        bump,
+        realloc = ExtraAccountMetaList::size_of(EXTRA_ACCOUNT_META_COUNT)?,
+        realloc::payer = authority,
+        realloc::zero = false
+    )]
+    pub extra_account_meta_list: UncheckedAccount<'info>,
+    
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+/// `#[event_cpi]` appends the `event_authority` PDA (seeds `b"__event_authority"`)
+/// and this `program`; both must be listed in the extra account metas.
+#[event_cpi]
+#[derive(Accounts)]
+pub struct TransferHook<'info> {
+    pub source_token: InterfaceAccount<'info, TokenAccount>,
+    pub mint: InterfaceAccount<'info, Mint>,
+    pub destination_token: InterfaceAccount<'info, TokenAccount>,
+    
+    /// CHECK: Source token account owner (or delegate). Token-2022 passes
+    /// it read-only and unsigned, so the hook can never charge it rent
+    pub owner: UncheckedAccount<'info>,
+    
+    /// CHECK: Validation account Token-2022 passes ahead of the extra accounts
+    #[account(
+        seeds = [b"extra-account-metas", mint.key().as_ref()],
+        bump
+    )]
+    pub extra_account_meta_list: UncheckedAccount<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"hook-config", mint.key().as_ref()],
+        bump
+    )]
+    pub hook_config: Account<'info, HookConfig>,
+    
+    /// CHECK: The owner's user state, loaded in `transfer_hook`. The hook
+    /// has no signer to pay rent, so it never creates it: the user (or a
+    /// sponsor) does beforehand with `initialize_user_state`. Until then
+    /// the transfer fails with `CannotInitUserState` unless
+    /// `allow_stateless` is on.
+    #[account(
+        mut,
+        seeds = [b"user-state", owner.key().as_ref(), mint.key().as_ref()],
+        bump
+    )]
+    pub user_state: UncheckedAccount<'info>,
+    
+    /// Receiver's user state, needed to enforce their incoming block and
+    /// required under strict-pair whitelisting
+    #[account(
+        seeds = [b"user-state", destination_token.owner.as_ref(), mint.key().as_ref()],
+        bump
+    )]
+    pub destination_user_state: Option<Account<'info, UserState>>,
+    
+    /// CHECK: Root identity's user state, required while
+    /// `user_state.linked_root` is set and resolved from it while
+    /// `linked_roots_enabled` is on; checked in `transfer_hook`
+    pub linked_root_state: Option<UncheckedAccount<'info>>,
+    
+    /// CHECK: Receipt for this transfer, required while `issue_receipts` is
+    /// on. Resolved from the user's `receipt_nonce`, so it follows
+    /// `user_state`; created beforehand with `prepare_receipt` and checked
+    /// in `transfer_hook`
+    #[account(mut)]
+    pub fee_receipt: Option<UncheckedAccount<'info>>,
+    
+    /// CHECK: Instructions sysvar, scanned for a memo while `require_memo` is
+    /// on and for the paired receiver fee payment in Receiver and Split mode
+    #[account(address = sysvar::instructions::ID)]
+    pub instructions_sysvar: Option<UncheckedAccount<'info>>,
+    
+    /// Derives `receiver_fee_account` and `fee_token_account` in the
+    /// extra account metas, resolved while either is
+    pub associated_token_program: Option<Program<'info, AssociatedToken>>,
+    
+    /// Receiver's associated token account, which pays the receiver's share
+    /// of the fee in the transfer paired after this one
+    #[account(
+        token::mint = mint,
+        token::authority = destination_token.owner
+    )]
+    pub receiver_fee_account: Option<InterfaceAccount<'info, TokenAccount>>,
+    
+    /// CHECK: Receives SOL fees; must be `hook_config.sol_fee_collector`,
+    /// required while `fee_in_sol` is on
+    #[account(mut)]
+    pub sol_fee_collector: Option<UncheckedAccount<'info>>,
+    
+    /// Owner's prefunded SOL fee escrow, required while `fee_in_sol` is on
+    #[account(
+        mut,
+        seeds = [b"sol-escrow", owner.key().as_ref(), mint.key().as_ref()],
+        bump
+    )]
+    pub sol_fee_escrow: Option<Account<'info, SolFeeEscrow>>,
+    
+    /// Sender's associated token account in `fee_mint`, balance-gated
+    /// (never debited) when fees use a second token
+    pub fee_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+    
+    /// Mint-wide lock against nested invocations, required while
+    /// `require_reentrancy_guard` is on
+    #[account(
+        mut,
+        seeds = [b"reentrancy-guard", mint.key().as_ref()],
+        bump
+    )]
+    pub reentrancy_guard: Option<Account<'info, ReentrancyGuard>>,
+    
+    /// Sender's stake in this mint's stake pool, required while `min_stake` is set
+    #[account(
+        seeds = [b"stake", owner.key().as_ref(), mint.key().as_ref()],
+        bump
+    )]
+    pub stake_account: Option<Account<'info, StakeAccount>>,
+    
+    /// CHECK: The destination owner's `FeeSink` PDA, resolved while
+    /// `sink_exemption` is on; for owners that are not sinks it does not exist
+    #[account(
+        seeds = [b"sink", destination_token.owner.as_ref(), mint.key().as_ref()],
+        bump
+    )]
+    pub fee_sink: Option<UncheckedAccount<'info>>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeUserState<'info> {
+    #[account(
+        mut,
+        seeds = [b"hook-config", mint.key().as_ref()],
+        bump
+    )]
+    pub hook_config: Account<'info, HookConfig>,
+    
+    pub mint: InterfaceAccount<'info, Mint>,
+    
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + UserState::INIT_SPACE,
+        seeds = [b"user-state", user.key().as_ref(), mint.key().as_ref()],
+        bump
+    )]
+    pub user_state: Account<'info, UserState>,
+    
+    /// CHECK: Owner of the new user state
+    pub user: UncheckedAccount<'info>,
+    
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct AdminAction<'info> {
+    #[account(
+        mut,
+        seeds = [b"hook-config", mint.key().as_ref()],
+        bump,
+        has_one = authority,
+        constraint = upgrade_acknowledged(&hook_config, program_data.as_deref())
+            @ ErrorCode::UpgradeNotAcknowledged
+    )]
+    pub hook_config: Account<'info, HookConfig>,
+    
+    pub mint: InterfaceAccount<'info, Mint>,
+    
+    pub authority: Signer<'info>,
+    
+    /// This program's program data, required once an upgrade has been acknowledged
+    #[account(
+        seeds = [crate::ID.as_ref()],
+        bump,
+        seeds::program = bpf_loader_upgradeable::ID
+    )]
+    pub program_data: Option<Account<'info, ProgramData>>,
+}
+
+#[derive(Accounts)]
+pub struct AcknowledgeUpgrade<'info> {
+    #[account(
+        mut,
+        seeds = [b"hook-config", mint.key().as_ref()],
+        bump,
+        has_one = authority
+    )]
+    pub hook_config: Account<'info, HookConfig>,
+    
+    pub mint: InterfaceAccount<'info, Mint>,
+    
+    pub authority: Signer<'info>,
+    
+    #[account(
+        seeds = [crate::ID.as_ref()],
+        bump,
+        seeds::program = bpf_loader_upgradeable::ID
+    )]
+    pub program_data: Account<'info, ProgramData>,
+}
+
+#[derive(Accounts)]
+pub struct SetUserStatus<'info> {
+    #[account(
+        seeds = [b"hook-config", mint.key().as_ref()],
+        bump,
+        has_one = authority
+    )]
+    pub hook_config: Account<'info, HookConfig>,
+    
+    pub mint: InterfaceAccount<'info, Mint>,
+    
+    #[account(
+        mut,
+        seeds = [b"user-state", user.key().as_ref(), mint.key().as_ref()],
+        bump
+    )]
+    pub user_state: Account<'info, UserState>,
+    
+    /// CHECK: User being blacklisted/whitelisted
+    pub user: UncheckedAccount<'info>,
+    
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetLinkedRoot<'info> {
+    #[account(
+        mut,
+        seeds = [b"hook-config", mint.key().as_ref()],
+        bump
+    )]
+    pub hook_config: Account<'info, HookConfig>,
+    
+    pub mint: InterfaceAccount<'info, Mint>,
+    
+    #[account(
+        mut,
+        seeds = [b"user-state", user.key().as_ref(), mint.key().as_ref()],
+        bump
+    )]
+    pub user_state: Account<'info, UserState>,
+    
+    /// CHECK: User being linked
+    pub user: UncheckedAccount<'info>,
+    
+    /// CHECK: Root identity's user state, validated in the handler
+    pub root_state: Option<UncheckedAccount<'info>>,
+    
+    /// The authority, or the user linking their own account
+    pub signer: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct RefundFee<'info> {
+    #[account(
+        mut,
+        seeds = [b"hook-config", mint.key().as_ref()],
+        bump,
+        has_one = authority
+    )]
+    pub hook_config: Account<'info, HookConfig>,
+    
+    pub mint: InterfaceAccount<'info, Mint>,
+    
+    #[account(
+        mut,
+        seeds = [b"user-state", user.key().as_ref(), mint.key().as_ref()],
+        bump
+    )]
+    pub user_state: Account<'info, UserState>,
+    
+    /// CHECK: User being refunded
+    pub user: UncheckedAccount<'info>,
+    
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ExpireRebate<'info> {
+    #[account(
+        seeds = [b"hook-config", mint.key().as_ref()],
+        bump
+    )]
+    pub hook_config: Account<'info, HookConfig>,
+    
+    pub mint: InterfaceAccount<'info, Mint>,
+    
+    #[account(
+        mut,
+        seeds = [b"user-state", user.key().as_ref(), mint.key().as_ref()],
+        bump
+    )]
+    pub user_state: Account<'info, UserState>,
+    
+    /// CHECK: Owner of the rebate credit
+    pub user: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct FinalizeEscrowedFee<'info> {
+    #[account(
+        mut,
+        seeds = [b"hook-config", mint.key().as_ref()],
+        bump
+    )]
+    pub hook_config: Account<'info, HookConfig>,
+    
+    pub mint: InterfaceAccount<'info, Mint>,
+    
+    #[account(
+        mut,
+        seeds = [b"user-state", user.key().as_ref(), mint.key().as_ref()],
+        bump
+    )]
+    pub user_state: Account<'info, UserState>,
+    
+    /// CHECK: Owner of the escrowed fee
+    pub user: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ReclaimEscrowedFee<'info> {
+    #[account(
+        mut,
+        seeds = [b"hook-config", mint.key().as_ref()],
+        bump
+    )]
+    pub hook_config: Account<'info, HookConfig>,
+    
+    pub mint: InterfaceAccount<'info, Mint>,
+    
+    #[account(
+        mut,
+        seeds = [b"user-state", owner.key().as_ref(), mint.key().as_ref()],
+        bump
+    )]
+    pub user_state: Account<'info, UserState>,
+    
+    pub owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct RotateFeeCollector<'info> {
+    #[account(
+        mut,
+        seeds = [b"hook-config", mint.key().as_ref()],
+        bump,
+        has_one = authority,
+        has_one = fee_collector
+    )]
+    pub hook_config: Account<'info, HookConfig>,
+    
+    pub mint: InterfaceAccount<'info, Mint>,
+    
+    pub authority: Signer<'info>,
+    
+    /// Current fee collector, consents to the rotation
+    pub fee_collector: Signer<'info>,
+    
+    /// CHECK: New fee collector, only stored in the config
+    pub new_fee_collector: UncheckedAccount<'info>,
+    
+    /// Must be empty once the preceding sweep has run
+    #[account(
+        token::mint = mint,
+        token::authority = fee_collector,
+        token::token_program = token_program
+    )]
+    pub old_collector_token: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(
+        token::mint = mint,
+        token::authority = new_fee_collector,
+        token::token_program = token_program
+    )]
+    pub new_collector_token: InterfaceAccount<'info, TokenAccount>,
+    
+    /// CHECK: Address-checked; read for the preceding sweep
+    #[account(address = sysvar::instructions::ID)]
+    pub instructions_sysvar: UncheckedAccount<'info>,
+    
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct PrepareReceipt<'info> {
+    #[account(
+        seeds = [b"hook-config", mint.key().as_ref()],
+        bump
+    )]
+    pub hook_config: Account<'info, HookConfig>,
+    
+    pub mint: InterfaceAccount<'info, Mint>,
+    
+    #[account(
+        seeds = [b"user-state", owner.key().as_ref(), mint.key().as_ref()],
+        bump
+    )]
+    pub user_state: Account<'info, UserState>,
+    
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + FeeReceipt::INIT_SPACE,
+        seeds = [
+            b"receipt",
+            owner.key().as_ref(),
+            mint.key().as_ref(),
+            &user_state.receipt_nonce.to_le_bytes()
+        ],
+        bump
+    )]
+    pub fee_receipt: Account<'info, FeeReceipt>,
+    
+    /// CHECK: Owner of the receipt
+    pub owner: UncheckedAccount<'info>,
+    
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CloseReceipt<'info> {
+    #[account(
+        seeds = [b"hook-config", mint.key().as_ref()],
+        bump
+    )]
+    pub hook_config: Account<'info, HookConfig>,
+    
+    pub mint: InterfaceAccount<'info, Mint>,
+    
+    #[account(
+        mut,
+        close = owner,
+        seeds = [
+            b"receipt",
+            owner.key().as_ref(),
+            mint.key().as_ref(),
+            &fee_receipt.nonce.to_le_bytes()
+        ],
+        bump
+    )]
+    pub fee_receipt: Account<'info, FeeReceipt>,
+    
+    #[account(mut)]
+    pub owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetMaxAcceptableFee<'info> {
+    #[account(
+        seeds = [b"hook-config", mint.key().as_ref()],
+        bump
+    )]
+    pub hook_config: Account<'info, HookConfig>,
+    
+    pub mint: InterfaceAccount<'info, Mint>,
+    
+    #[account(
+        mut,
+        seeds = [b"user-state", owner.key().as_ref(), mint.key().as_ref()],
+        bump
+    )]
+    pub user_state: Account<'info, UserState>,
+    
+    pub owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetUseRebate<'info> {
+    #[account(
+        seeds = [b"hook-config", mint.key().as_ref()],
+        bump
+    )]
+    pub hook_config: Account<'info, HookConfig>,
+    
+    pub mint: InterfaceAccount<'info, Mint>,
+    
+    #[account(
+        mut,
+        seeds = [b"user-state", owner.key().as_ref(), mint.key().as_ref()],
+        bump
+    )]
+    pub user_state: Account<'info, UserState>,
+    
+    pub owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetLoyaltyOptOut<'info> {
+    #[account(
+        seeds = [b"hook-config", mint.key().as_ref()],
+        bump
+    )]
+    pub hook_config: Account<'info, HookConfig>,
+    
+    pub mint: InterfaceAccount<'info, Mint>,
+    
+    #[account(
+        mut,
+        seeds = [b"user-state", owner.key().as_ref(), mint.key().as_ref()],
+        bump
+    )]
+    pub user_state: Account<'info, UserState>,
+    
+    pub owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct RefreshTier<'info> {
+    #[account(
+        seeds = [b"hook-config", mint.key().as_ref()],
+        bump
+    )]
+    pub hook_config: Account<'info, HookConfig>,
+    
+    pub mint: InterfaceAccount<'info, Mint>,
+    
+    #[account(
+        mut,
+        seeds = [b"user-state", user.key().as_ref(), mint.key().as_ref()],
+        bump
+    )]
+    pub user_state: Account<'info, UserState>,
+    
+    /// CHECK: User whose tier is refreshed
+    pub user: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ComputePercentile<'info> {
+    #[account(
+        seeds = [b"hook-config", mint.key().as_ref()],
+        bump
+    )]
+    pub hook_config: Account<'info, HookConfig>,
+    
+    pub mint: InterfaceAccount<'info, Mint>,
+    
+    #[account(
+        mut,
+        seeds = [b"user-state", user.key().as_ref(), mint.key().as_ref()],
+        bump
+    )]
+    pub user_state: Account<'info, UserState>,
+    
+    /// CHECK: User whose percentile is computed
+    pub user: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(owner: Pubkey)]
+pub struct AddSink<'info> {
+    #[account(
+        seeds = [b"hook-config", mint.key().as_ref()],
+        bump,
+        has_one = authority
+    )]
+    pub hook_config: Account<'info, HookConfig>,
+    
+    pub mint: InterfaceAccount<'info, Mint>,
+    
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + FeeSink::INIT_SPACE,
+        seeds = [b"sink", owner.as_ref(), mint.key().as_ref()],
+        bump
+    )]
+    pub fee_sink: Account<'info, FeeSink>,
+    
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RemoveSink<'info> {
+    #[account(
+        seeds = [b"hook-config", mint.key().as_ref()],
+        bump,
+        has_one = authority
+    )]
+    pub hook_config: Account<'info, HookConfig>,
+    
+    pub mint: InterfaceAccount<'info, Mint>,
+    
+    #[account(
+        mut,
+        close = authority,
+        seeds = [b"sink", fee_sink.owner.as_ref(), mint.key().as_ref()],
+        bump
+    )]
+    pub fee_sink: Account<'info, FeeSink>,
+    
+    #[account(mut)]
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(code_hash: [u8; 32])]
+pub struct CreateDiscountCode<'info> {
+    #[account(
+        seeds = [b"hook-config", mint.key().as_ref()],
+        bump,
+        has_one = authority
+    )]
+    pub hook_config: Account<'info, HookConfig>,
+    
+    pub mint: InterfaceAccount<'info, Mint>,
+    
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + DiscountCode::INIT_SPACE,
+        seeds = [b"code", mint.key().as_ref(), code_hash.as_ref()],
+        bump
+    )]
+    pub discount_code: Account<'info, DiscountCode>,
+    
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RedeemDiscountCode<'info> {
+    #[account(
+        seeds = [b"hook-config", mint.key().as_ref()],
+        bump
+    )]
+    pub hook_config: Account<'info, HookConfig>,
+    
+    pub mint: InterfaceAccount<'info, Mint>,
+    
+    #[account(mut, has_one = mint @ ErrorCode::InvalidDiscountCode)]
+    pub discount_code: Account<'info, DiscountCode>,
+    
+    #[account(
+        mut,
+        seeds = [b"user-state", owner.key().as_ref(), mint.key().as_ref()],
+        bump
+    )]
+    pub user_state: Account<'info, UserState>,
+    
+    pub owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeReentrancyGuard<'info> {
+    #[account(
+        seeds = [b"hook-config", mint.key().as_ref()],
+        bump,
+        has_one = authority
+    )]
+    pub hook_config: Account<'info, HookConfig>,
+    
+    pub mint: InterfaceAccount<'info, Mint>,
+    
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + ReentrancyGuard::INIT_SPACE,
+        seeds = [b"reentrancy-guard", mint.key().as_ref()],
+        bump
+    )]
+    pub reentrancy_guard: Account<'info, ReentrancyGuard>,
+    
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeRewardsVault<'info> {
+    #[account(
+        seeds = [b"hook-config", mint.key().as_ref()],
+        bump,
+        has_one = authority
+    )]
+    pub hook_config: Account<'info, HookConfig>,
+    
+    pub mint: InterfaceAccount<'info, Mint>,
+    
+    #[account(
+        init,
+        payer = authority,
+        seeds = [b"rewards-vault", mint.key().as_ref()],
+        bump,
+        token::mint = mint,
+        token::authority = hook_config,
+        token::token_program = token_program
+    )]
+    pub rewards_vault: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimRewards<'info> {
+    #[account(
+        seeds = [b"hook-config", mint.key().as_ref()],
+        bump
+    )]
+    pub hook_config: Account<'info, HookConfig>,
+    
+    pub mint: InterfaceAccount<'info, Mint>,
+    
+    #[account(
+        mut,
+        seeds = [b"user-state", owner.key().as_ref(), mint.key().as_ref()],
+        bump
+    )]
+    pub user_state: Account<'info, UserState>,
+    
+    #[account(
+        mut,
+        seeds = [b"rewards-vault", mint.key().as_ref()],
+        bump
+    )]
+    pub rewards_vault: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(
+        token::mint = mint,
+        token::authority = owner,
+        token::token_program = token_program
+    )]
+    pub owner_token: InterfaceAccount<'info, TokenAccount>,
+    
+    pub owner: Signer<'info>,
+    
+    /// CHECK: Address-checked; read for the paired transfer
+    #[account(address = sysvar::instructions::ID)]
+    pub instructions_sysvar: UncheckedAccount<'info>,
+    
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct FundRewards<'info> {
+    #[account(
+        mut,
+        seeds = [b"hook-config", mint.key().as_ref()],
+        bump
+    )]
+    pub hook_config: Account<'info, HookConfig>,
+    
+    pub mint: InterfaceAccount<'info, Mint>,
+    
+    #[account(
+        seeds = [b"rewards-vault", mint.key().as_ref()],
+        bump
+    )]
+    pub rewards_vault: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(
+        token::mint = mint,
+        token::authority = funder,
+        token::token_program = token_program
+    )]
+    pub funder_token: InterfaceAccount<'info, TokenAccount>,
+    
+    pub funder: Signer<'info>,
+    
+    /// CHECK: Address-checked; read for the paired transfer
+    #[account(address = sysvar::instructions::ID)]
+    pub instructions_sysvar: UncheckedAccount<'info>,
+    
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeStakePool<'info> {
+    #[account(
+        seeds = [b"hook-config", mint.key().as_ref()],
+        bump,
+        has_one = authority
+    )]
+    pub hook_config: Account<'info, HookConfig>,
+    
+    pub mint: InterfaceAccount<'info, Mint>,
+    
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + StakePool::INIT_SPACE,
+        seeds = [b"stake-pool", mint.key().as_ref()],
+        bump
+    )]
+    pub stake_pool: Account<'info, StakePool>,
+    
+    #[account(
+        init,
+        payer = authority,
+        seeds = [b"stake-vault", mint.key().as_ref()],
+        bump,
+        token::mint = mint,
+        token::authority = hook_config,
+        token::token_program = token_program
+    )]
+    pub stake_vault: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ManageSolFeeEscrow<'info> {
+    #[account(
+        seeds = [b"hook-config", mint.key().as_ref()],
+        bump
+    )]
+    pub hook_config: Account<'info, HookConfig>,
+    
+    pub mint: InterfaceAccount<'info, Mint>,
+    
+    #[account(
+        init_if_needed,
+        payer = owner,
+        space = 8 + SolFeeEscrow::INIT_SPACE,
+        seeds = [b"sol-escrow", owner.key().as_ref(), mint.key().as_ref()],
+        bump
+    )]
+    pub sol_fee_escrow: Account<'info, SolFeeEscrow>,
+    
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ManageStake<'info> {
+    #[account(
+        mut,
+        seeds = [b"hook-config", mint.key().as_ref()],
+        bump
+    )]
+    pub hook_config: Account<'info, HookConfig>,
+    
+    pub mint: InterfaceAccount<'info, Mint>,
+    
+    #[account(
+        mut,
+        seeds = [b"stake-pool", mint.key().as_ref()],
+        bump
+    )]
+    pub stake_pool: Account<'info, StakePool>,
+    
+    #[account(
+        init_if_needed,
+        payer = owner,
+        space = 8 + StakeAccount::INIT_SPACE,
+        seeds = [b"stake", owner.key().as_ref(), mint.key().as_ref()],
+        bump
+    )]
+    pub stake_account: Account<'info, StakeAccount>,
+    
+    #[account(
+        mut,
+        seeds = [b"stake-vault", mint.key().as_ref()],
+        bump
+    )]
+    pub stake_vault: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(
+        token::mint = mint,
+        token::authority = owner,
+        token::token_program = token_program
+    )]
+    pub owner_token: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    
+    /// CHECK: Address-checked; read for the paired transfer
+    #[account(address = sysvar::instructions::ID)]
+    pub instructions_sysvar: UncheckedAccount<'info>,
+    
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct PurchaseCooldownExemption<'info> {
+    #[account(
+        seeds = [b"hook-config", mint.key().as_ref()],
+        bump,
+        has_one = fee_collector
+    )]
+    pub hook_config: Account<'info, HookConfig>,
+    
+    pub mint: InterfaceAccount<'info, Mint>,
+    
+    #[account(
+        mut,
+        seeds = [b"user-state", owner.key().as_ref(), mint.key().as_ref()],
+        bump
+    )]
+    pub user_state: Account<'info, UserState>,
+    
+    /// CHECK: Receives the payment; must be `hook_config.fee_collector`
+    #[account(mut)]
+    pub fee_collector: UncheckedAccount<'info>,
+    
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ManagePoints<'info> {
+    #[account(
+        seeds = [b"hook-config", mint.key().as_ref()],
+        bump
+    )]
+    pub hook_config: Account<'info, HookConfig>,
+    
+    pub mint: InterfaceAccount<'info, Mint>,
+    
+    #[account(
+        mut,
+        seeds = [b"user-state", owner.key().as_ref(), mint.key().as_ref()],
+        bump
+    )]
+    pub user_state: Account<'info, UserState>,
+    
+    pub owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CreateProposal<'info> {
+    #[account(
+        mut,
+        seeds = [b"hook-config", mint.key().as_ref()],
+        bump
+    )]
+    pub hook_config: Account<'info, HookConfig>,
+    
+    pub mint: InterfaceAccount<'info, Mint>,
+    
+    #[account(
+        init,
+        payer = proposer,
+        space = 8 + Proposal::INIT_SPACE,
+        seeds = [
+            b"proposal",
+            mint.key().as_ref(),
+            &hook_config.proposal_count.to_le_bytes()
+        ],
+        bump
+    )]
+    pub proposal: Account<'info, Proposal>,
+    
+    #[account(mut)]
+    pub proposer: Signer<'info>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CastVote<'info> {
+    #[account(
+        seeds = [b"hook-config", mint.key().as_ref()],
+        bump
+    )]
+    pub hook_config: Account<'info, HookConfig>,
+    
+    pub mint: InterfaceAccount<'info, Mint>,
+    
+    #[account(
+        mut,
+        seeds = [b"proposal", mint.key().as_ref(), &proposal.id.to_le_bytes()],
+        bump
+    )]
+    pub proposal: Account<'info, Proposal>,
+    
+    /// One vote per voter per proposal; `init` fails on a second vote
+    #[account(
+        init,
+        payer = voter,
+        space = 8 + VoteRecord::INIT_SPACE,
+        seeds = [b"vote", proposal.key().as_ref(), voter.key().as_ref()],
+        bump
+    )]
+    pub vote_record: Account<'info, VoteRecord>,
+    
+    #[account(
+        seeds = [b"user-state", voter.key().as_ref(), mint.key().as_ref()],
+        bump
+    )]
+    pub user_state: Account<'info, UserState>,
+    
+    #[account(mut)]
+    pub voter: Signer<'info>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ExecuteProposal<'info> {
+    #[account(
+        mut,
+        seeds = [b"hook-config", mint.key().as_ref()],
+        bump
+    )]
+    pub hook_config: Account<'info, HookConfig>,
+    
+    pub mint: InterfaceAccount<'info, Mint>,
+    
+    #[account(
+        mut,
+        seeds = [b"proposal", mint.key().as_ref(), &proposal.id.to_le_bytes()],
+        bump
+    )]
+    pub proposal: Account<'info, Proposal>,
+}
+
+#[derive(Accounts)]
+pub struct ReadConfig<'info> {
+    #[account(
+        seeds = [b"hook-config", mint.key().as_ref()],
+        bump
+    )]
+    pub hook_config: Account<'info, HookConfig>,
+    
+    pub mint: InterfaceAccount<'info, Mint>,
+}
+
+// ============================================================================
+// State Accounts
+// ============================================================================
+
+#[account]
+#[derive(InitSpace, Default)]
+pub struct HookConfig {
+    pub authority: Pubkey,
+    /// Primary collector, always `fee_collectors[0]`
+    pub fee_collector: Pubkey,
+    pub is_paused: bool,
+    pub total_transfers: u64,
+    pub total_volume: u64,
+    pub total_fees_collected: u64,
+    /// Cumulative fees forgone to discounts (base fee - final fee)
+    pub total_discounts_given: u64,
+    /// Share of each fee routed to the rewards vault
+    pub rewards_bps: u16,
+    /// Rewards per unit of volume, scaled by `REWARD_PRECISION`
+    pub reward_per_volume: u128,
+    pub total_rewards_accrued: u64,
+    /// Number of active entries in the loyalty ladder
+    pub loyalty_tier_count: u8,
+    /// Transfer count required for each tier, strictly increasing
+    pub loyalty_tier_thresholds: [u64; MAX_LOYALTY_TIERS],
+    pub loyalty_tier_discount_bps: [u16; MAX_LOYALTY_TIERS],
+    /// Per amount tier; a waived tier charges no base fee
+    pub tier_fees_enabled: [bool; FEE_TIER_COUNT],
+    /// Minimum age of a user state before it may transfer (0 = disabled)
+    pub min_account_age_seconds: i64,
+    /// `FEE_PAYER_SENDER`, `FEE_PAYER_RECEIVER` or `FEE_PAYER_SPLIT`; the
+    /// receiver's share is debited through a paired fee payment
+    pub fee_payer_mode: u8,
+    /// Receiver's share of the fee in Split mode
+    pub receiver_fee_share_bps: u16,
+    /// `msg!` verbosity, `LOG_NONE`..=`LOG_DEBUG`
+    pub log_level: u8,
+    /// Fee-free volume per user per daily window (0 = disabled)
+    pub free_allowance_per_period: u64,
+    /// How long a cached loyalty tier stays valid (0 = always recompute)
+    pub tier_cache_seconds: i64,
+    /// Lifetime transfer cap per user (0 = unlimited)
+    pub max_transfers_per_user: u64,
+    /// Whether holders may propose and vote on config changes
+    pub governance_enabled: bool,
+    pub proposal_voting_seconds: i64,
+    /// Id of the next proposal
+    pub proposal_count: u64,
+    /// Transfers per amount tier
+    pub tier_transfer_counts: [u64; FEE_TIER_COUNT],
+    /// Net fees (after discounts) per amount tier
+    pub tier_fees: [u64; FEE_TIER_COUNT],
+    /// Fee on an account's first transfer, replacing the tier fee (0 = disabled)
+    pub first_transfer_fee_bps: u16,
+    /// Upper bounds (exclusive) of amount tiers 1-3, strictly increasing
+    pub fee_tier_thresholds: [u64; FEE_TIER_THRESHOLD_COUNT],
+    pub fee_tier_bps: [u16; FEE_TIER_COUNT],
+    /// Cap on the final fee per transfer (0 = uncapped)
+    pub max_fee: u64,
+    /// Select tiers on the interest-bearing scaled amount
+    pub use_scaled_amount: bool,
+    /// Reject transfers whose source or destination owner is the fee collector
+    pub block_collector_transfers: bool,
+    /// Final fees are rounded to a multiple of this (0 or 1 = base units)
+    pub fee_granularity: u64,
+    /// `FEE_ROUND_NEAREST`, `FEE_ROUND_DOWN` or `FEE_ROUND_UP`
+    pub fee_rounding_mode: u8,
+    /// Emit `TransferExecuted` via self-CPI instead of `emit!`
+    pub use_cpi_events: bool,
+    /// Minimum seconds between a user's transfers (0 = disabled)
+    pub cooldown_seconds: i64,
+    /// Active users per lifetime-volume bucket (see `VOLUME_BUCKET_COUNT`)
+    pub volume_histogram: [u64; VOLUME_BUCKET_COUNT],
+    /// Gap between transfers that resets loyalty progress (0 = no decay)
+    pub loyalty_decay_seconds: i64,
+    /// Gapped transfers that only flag pending decay before it applies
+    pub decay_grace_transfers: u64,
+    /// Largest single transfer amount seen
+    pub largest_transfer: u64,
+    /// Surcharge transfers above `largest_transfer`
+    pub surcharge_new_highs: bool,
+    pub new_high_surcharge_bps: u16,
+    /// Skip fees and statistics for transfers involving the mint authority
+    pub exempt_mint_authority: bool,
+    /// When a pause lapses without `set_pause(false)` (0 = manual only)
+    pub auto_unpause_at: i64,
+    /// Gap below which a transfer extends the rapid streak (0 = no escalation)
+    pub cooldown_calm_seconds: i64,
+    /// Cap on the escalated cooldown (0 = uncapped)
+    pub max_cooldown_seconds: i64,
+    /// Write a `FeeReceipt` per transfer
+    pub issue_receipts: bool,
+    /// `TIER_BENEFIT_*` mask per loyalty tier, indexed from tier 1
+    pub tier_benefits: [u8; MAX_LOYALTY_TIERS],
+    /// Maintain `UserState::distinct_dest_estimate`
+    pub track_counterparties: bool,
+    /// Revenue-sharing collectors and their weights (bps, summing to
+    /// 10000), as split by `get_collector_shares`; the first is `fee_collector`
+    pub fee_collectors: [Pubkey; MAX_FEE_COLLECTORS],
+    pub fee_collector_weights: [u16; MAX_FEE_COLLECTORS],
+    pub fee_collector_count: u8,
+    /// Per-mint cap on `fee_collector_count`, at most `MAX_FEE_COLLECTORS`
+    pub max_fee_collectors: u8,
+    /// Reject transfers without an SPL Memo in the transaction
+    pub require_memo: bool,
+    /// Use the continuous volume curve instead of the loyalty tier discount
+    pub continuous_discount: bool,
+    /// Lifetime volume per bp of continuous discount
+    pub volume_per_bp: u64,
+    pub max_continuous_discount_bps: u16,
+    /// `HOLDING_DISCOUNT_OFF`, `HOLDING_DISCOUNT_STACK` or `HOLDING_DISCOUNT_REPLACE`
+    pub holding_discount_mode: u8,
+    pub holding_discount_per_day_bps: u16,
+    pub max_holding_discount_bps: u16,
+    /// Whether a recount is open, and its id (see `recount_begin`)
+    pub recount_active: bool,
+    pub recount_session: u64,
+    /// Scratch tallies of the open recount
+    pub recount_transfers: u64,
+    pub recount_volume: u64,
+    pub recount_users: u64,
+    /// Only whitelisted users may send
+    pub whitelist_mode: bool,
+    /// In whitelist mode, receivers must be whitelisted as well
+    pub strict_pairs: bool,
+    /// Mint fees are denominated in (default = this mint)
+    pub fee_mint: Pubkey,
+    /// `fee_mint` base units per whole token of this mint
+    pub fee_token_rate: u64,
+    /// Floor on a converted, non-zero fee
+    pub min_fee_in_fee_token: u64,
+    /// User states created for this mint
+    pub unique_users: u64,
+    /// Exempt authority and fee collector transfers from the pause
+    pub allow_admin_during_pause: bool,
+    /// Share of each fee routed to stakers
+    pub staking_bps: u16,
+    /// Stakers' fee share not yet synced into the stake pool
+    pub pending_staking_rewards: u64,
+    /// Reject transfers that do not pass the reentrancy guard
+    pub require_reentrancy_guard: bool,
+    /// Fee-free period after a user's first transfer (0 = disabled)
+    pub onboarding_free_seconds: i64,
+    /// Highest balance a transfer may leave the destination with (0 = uncapped)
+    pub max_account_balance: u64,
+    /// Charge fees in lamports to `sol_fee_collector`
+    pub fee_in_sol: bool,
+    pub sol_fee_collector: Pubkey,
+    /// Flat lamports per transfer in SOL fee mode
+    pub sol_fee_per_transfer: u64,
+    /// Admin-set lamports per whole token for converting the token fee
+    pub lamports_per_token: u64,
+    pub total_sol_fees_collected: u64,
+    /// Window for pricing a user's transfers on their cumulative volume (0 = off)
+    pub aggregation_seconds: i64,
+    /// Irreversible: fee-setting instructions are rejected
+    pub fee_schedule_locked: bool,
+    /// While locked, still accept changes that can only lower fees
+    pub allow_decrease_when_locked: bool,
+    /// Sum of effective fee bps times amount (see `get_average_fee_bps`)
+    pub cumulative_fee_bps_weighted: u128,
+    /// Fee on transfers to or from a listed DeFi owner, replacing the tier bps
+    pub defi_fee_bps: u16,
+    pub defi_owner_count: u8,
+    /// Admin-maintained program-derived owners (pools, vaults)
+    pub defi_owners: [Pubkey; MAX_DEFI_OWNERS],
+    /// Credit `tier_up_bonus_amount` as rebate when a transfer reaches a new loyalty tier
+    pub tier_up_bonus: bool,
+    pub tier_up_bonus_amount: u64,
+    /// Printable-ASCII tag for logs and events, zero-padded (set at init)
+    pub label: [u8; LABEL_LEN],
+    /// Run transfers without the owner's user state on mint-wide rules only
+    pub allow_stateless: bool,
+    /// Program `authority` is a PDA of (default = a keypair authority)
+    pub authority_program: Pubkey,
+    /// Minimum slots between a user's transfers (0 = disabled)
+    pub min_slot_spacing: u64,
+    /// Escrow window for fees (0 = fees are final immediately)
+    pub fee_escrow_seconds: i64,
+    /// Fees recorded but still escrowed (an upper bound: lapsed escrows
+    /// count until finalized)
+    pub total_fees_escrowed: u64,
+    /// Bps of the relative supply change added to the tier bps (0 = off)
+    pub supply_sensitivity_bps: u16,
+    /// Bound on the supply adjustment either way
+    pub max_supply_adjustment_bps: u16,
+    /// Length of a supply observation window
+    pub supply_window_seconds: i64,
+    /// Supply reference taken at the start of the current window
+    pub last_observed_supply: u64,
+    pub supply_observed_at: i64,
+    /// Tokens a sender must have staked to transfer (0 = disabled)
+    pub min_stake: u64,
+    /// Flag about one in this many transfers for audit (0 = none)
+    pub audit_sample_rate: u32,
+    /// Rebate credit lapses this long after the balance last grew (0 = never)
+    pub rebate_validity_seconds: i64,
+    /// Keccak of every fee-setting field, recomputed by each instruction
+    /// that changes one, so clients can pin the pricing they signed up for
+    pub schedule_hash: [u8; 32],
+    /// Cap on the summed discount bps (0 = up to the base fee)
+    pub max_total_discount_bps: u16,
+    /// Mint-wide volume per spike window that trips the circuit breaker (0 = disabled)
+    pub spike_threshold: u64,
+    /// Length of a circuit breaker window
+    pub spike_window_seconds: i64,
+    /// Start of the current circuit breaker window
+    pub spike_window_start: i64,
+    /// Volume seen in the current circuit breaker window
+    pub spike_window_volume: u64,
+    /// Paused by the circuit breaker rather than the admin
+    pub auto_paused: bool,
+    /// Program data `slot` of the deployment admin actions run under (0 = unchecked)
+    pub acknowledged_deploy_slot: u64,
+    /// Discount for a sub-tier-1 transfer that empties its source (0 = disabled)
+    pub dust_consolidation_discount_bps: u16,
+    /// `metas_version` the extra account meta list was last written under (0 = unversioned)
+    pub metas_version: u64,
+    /// Lifetime transfer counts that emit a `Milestone` (0 = unused)
+    pub milestone_transfers: [u64; MAX_MILESTONES],
+    /// Lifetime volumes that emit a `Milestone` (0 = unused)
+    pub milestone_volumes: [u64; MAX_MILESTONES],
+    /// Points per `POINTS_RATE_SCALE` of volume (0 = points off)
+    pub points_rate: u64,
+    /// Waive fees on transfers into owners with a `FeeSink`
+    pub sink_exemption: bool,
+    /// Start of the launch fee ramp
+    pub launch_timestamp: i64,
+    /// Fee bps at `launch_timestamp`, ramping to the tier bps
+    pub launch_fee_bps: u16,
+    /// Length of the launch fee ramp (0 = disabled)
+    pub ramp_seconds: i64,
+    /// Emit an `HourlyStats` event per hour with transfers
+    pub hourly_stats: bool,
+    /// Current hour bucket (unix time / `HOUR_SECONDS`)
+    pub current_hour: i64,
+    pub hour_transfers: u64,
+    pub hour_volume: u64,
+    pub hour_fees: u64,
+    /// Lamports an owner pays for a permanent cooldown exemption (0 = not for sale)
+    pub cooldown_exemption_price: u64,
+    /// Add a balance-based component to the tier bps
+    pub balance_fee_enabled: bool,
+    /// Sender balance per bp of balance component
+    pub balance_per_bp: u64,
+    /// Cap on the balance component
+    pub max_balance_fee_bps: u16,
+    pub authorized_minter_count: u8,
+    /// Senders whose transfers are left out of user stats and loyalty
+    pub authorized_minters: [Pubkey; MAX_AUTHORIZED_MINTERS],
+    /// Fail transfers, rather than skip time-based rules, without the clock
+    pub require_clock: bool,
+    /// Distinct destinations needed per loyalty tier (0 = no minimum)
+    pub min_counterparties_per_tier: [u32; MAX_LOYALTY_TIERS],
+    /// Governance-declared promotion overriding the schedule while it runs
+    pub active_event: ActiveEvent,
+    /// Distinct destinations per user per daily window (0 = unlimited)
+    pub max_dests_per_window: u32,
+    /// Rewards share of fees not yet paid into the rewards vault
+    pub unfunded_rewards: u64,
+    /// Volume that earns rewards: the sum of every tracked user's volume
+    pub claimable_volume: u64,
+    /// Accounts may be linked to root identities (see `set_linked_root`)
+    pub linked_roots_enabled: bool,
+    /// Accounts currently linked to a root identity
+    pub linked_user_count: u64,
+    /// Token program of `fee_mint`, for its associated token accounts
+    pub fee_token_program: Pubkey,
+    /// Share of claimable volume a proposal's votes must reach (0 = no quorum)
+    pub governance_quorum_bps: u16,
+    /// Transfers and volume no user state records: stateless transfers, and
+    /// the volume of opted-out users and authorized minters
+    pub untracked_transfers: u64,
+    pub untracked_volume: u64,
+}
+
+#[account]
+#[derive(InitSpace, Default)]
+pub struct UserState {
+    pub owner: Pubkey,
+    pub transfer_count: u64,
+    pub total_volume: u64,
+    pub first_transfer_timestamp: i64,
+    pub last_transfer_timestamp: i64,
+    pub is_blacklisted: bool,
+    /// Settled, unclaimed rewards
+    pub reward_share: u64,
+    /// Scaled rewards already settled or accrued before the user's volume
+    pub reward_debt: u128,
+    /// When `initialize_user_state` created the state
+    pub user_created_at: i64,
+    /// Fees attributed to this user as sender, net of refunds
+    pub total_fees_paid: u64,
+    /// Credit owed to the user from fee refunds
+    pub rebate_balance: u64,
+    /// Start of the user's current daily window
+    pub window_start: i64,
+    /// Volume sent in the current daily window
+    pub window_volume: u64,
+    /// Free allowance consumed in the current daily window
+    pub free_used_this_window: u64,
+    /// Last computed loyalty tier
+    pub cached_tier: u8,
+    pub cached_tier_valid_until: i64,
+    /// Never throttled by the transfer cooldown
+    pub is_cooldown_exempt: bool,
+    /// Last approximate volume percentile stored by `compute_percentile`
+    pub volume_percentile_bps: u16,
+    /// `transfer_count` at the last loyalty decay; tiers count from here
+    pub loyalty_count_base: u64,
+    /// Consecutive gapped transfers spent from the decay grace
+    pub decay_grace_used: u64,
+    /// Consecutive transfers inside the cooldown calm period
+    pub rapid_streak: u16,
+    /// Nonce of the user's next `FeeReceipt`
+    pub receipt_nonce: u64,
+    /// Owner-set ceiling on the final fee of a transfer (0 = no limit)
+    pub max_acceptable_fee: u64,
+    /// Bloom filter of destination owners sent to
+    pub counterparty_filter: [u8; COUNTERPARTY_FILTER_BYTES],
+    /// Approximate number of distinct destination owners
+    pub distinct_dest_estimate: u32,
+    /// Reject transfers sent by this user
+    pub block_outgoing: bool,
+    /// Reject transfers to this user (needs `destination_user_state`)
+    pub block_incoming: bool,
+    /// Admin-attested pre-hook transfers counted toward loyalty until decay
+    pub loyalty_credit: u64,
+    /// Whether `seed_loyalty` has run for this user
+    pub loyalty_seeded: bool,
+    /// Last recount session that tallied this state
+    pub recount_session: u64,
+    /// May transfer while `whitelist_mode` is on
+    pub is_whitelisted: bool,
+    /// Root identity whose blacklist this account inherits (default = none)
+    pub linked_root: Pubkey,
+    /// Owner opted out of loyalty: count frozen, None-tier pricing
+    pub loyalty_opt_out: bool,
+    /// Owner opted out of per-user volume and counterparty statistics
+    pub stats_opt_out: bool,
+    /// Start of the user's current aggregation window
+    pub aggregation_window_start: i64,
+    /// Priced volume in the current aggregation window
+    pub aggregation_window_volume: u64,
+    /// Highest loyalty tier the user has ever held (tier-up bonus bookkeeping)
+    pub highest_tier_reached: u8,
+    /// Slot of the user's last transfer (slot spacing)
+    pub last_transfer_slot: u64,
+    /// Fee of the user's latest transfer while held in escrow
+    pub escrowed_fee: u64,
+    /// End of the window for reclaiming `escrowed_fee`
+    pub escrow_expires_at: i64,
+    /// When `rebate_balance` expires (0 = never)
+    pub rebate_expires_at: i64,
+    /// Spendable loyalty points
+    pub points_balance: u64,
+    /// Lifetime volume already converted into points
+    pub volume_claimed_for_points: u64,
+    /// Per-transfer fee ceiling for this user (0 = only the global `max_fee`)
+    pub user_max_fee: u64,
+    /// Owner opted to pay fees out of `rebate_balance` first
+    pub use_rebate: bool,
+    /// Bloom filter of destination owners sent to in the current window
+    pub window_dest_filter: [u8; COUNTERPARTY_FILTER_BYTES],
+    /// Approximate distinct destinations in the current window
+    pub window_dest_count: u32,
+    /// Discount of a redeemed code awaiting the next eligible transfer
+    pub pending_code_discount_bps: u16,
+    /// Expiry of that code (0 = never)
+    pub pending_code_expires_at: i64,
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct Proposal {
+    pub mint: Pubkey,
+    pub id: u64,
+    pub proposer: Pubkey,
+    pub change: ConfigChange,
+    pub voting_ends_at: i64,
+    /// Volume-weighted tallies
+    pub yes_votes: u64,
+    pub no_votes: u64,
+    pub executed: bool,
+    pub passed: bool,
+    /// Weight that must be cast, either way, for the proposal to pass
+    pub quorum_votes: u64,
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct VoteRecord {
+    pub proposal: Pubkey,
+    pub voter: Pubkey,
+    pub weight: u64,
+    pub approve: bool,
+}
+
+/// Durable proof of the fee charged on one transfer
+#[account]
+#[derive(InitSpace)]
+pub struct FeeReceipt {
+    pub owner: Pubkey,
+    pub mint: Pubkey,
+    pub nonce: u64,
+    pub amount: u64,
+    /// Final fee across both paying sides
+    pub fee: u64,
+    pub fee_tier: u8,
+    pub timestamp: i64,
+}
+
+/// Set only while a `transfer_hook` invocation that passed it is running
+#[account]
+#[derive(InitSpace)]
+pub struct ReentrancyGuard {
+    pub locked: bool,
+}
+
+/// Marks a destination owner as fee-free while `sink_exemption` is on
+#[account]
+#[derive(InitSpace)]
+pub struct FeeSink {
+    pub mint: Pubkey,
+    pub owner: Pubkey,
+}
+
+/// Limited-use campaign discount, stacked on the loyalty discount
+#[account]
+#[derive(InitSpace)]
+pub struct DiscountCode {
+    pub mint: Pubkey,
+    /// Hash of the code string, so the code itself never appears on-chain
+    pub code_hash: [u8; 32],
+    pub discount_bps: u16,
+    pub remaining_uses: u64,
+    /// No longer redeemable from this time (0 = never expires)
+    pub expires_at: i64,
+}
+
+/// Lamports an owner set aside for SOL fees; `transfer_hook` debits them
+#[account]
+#[derive(InitSpace)]
+pub struct SolFeeEscrow {
+    pub owner: Pubkey,
+    pub mint: Pubkey,
+}
+
+/// Per-mint staking accumulator; stakes and funded rewards sit in the stake vault
+#[account]
+#[derive(InitSpace, Default)]
+pub struct StakePool {
+    pub mint: Pubkey,
+    pub total_staked: u64,
+    /// Rewards per staked unit, scaled by `REWARD_PRECISION`
+    pub reward_per_stake: u128,
+    pub total_rewards_accrued: u64,
+}
+
+/// A holder's stake in the mint's pool
+#[account]
+#[derive(InitSpace, Default)]
+pub struct StakeAccount {
+    pub owner: Pubkey,
+    pub amount: u64,
+    /// Scaled rewards already settled or accrued before the current stake
+    pub reward_debt: u128,
+    /// Settled, unclaimed rewards
+    pub pending_rewards: u64,
+}
+
+/// Complete fee and loyalty schedule, validated and written as a unit
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq, Eq, InitSpace)]
+pub struct FeeSchedule {
+    pub fee_tier_thresholds: [u64; FEE_TIER_THRESHOLD_COUNT],
+    pub fee_tier_bps: [u16; FEE_TIER_COUNT],
+    pub loyalty_tier_count: u8,
+    pub loyalty_tier_thresholds: [u64; MAX_LOYALTY_TIERS],
+    pub loyalty_tier_discount_bps: [u16; MAX_LOYALTY_TIERS],
+    pub first_transfer_fee_bps: u16,
+    pub max_fee: u64,
+}
+
+/// A config change a governance proposal can apply
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq, Eq, InitSpace)]
+pub enum ConfigChange {
+    RewardsBps(u16),
+    TierFeeEnabled { tier: u8, enabled: bool },
+    FeePayerMode { mode: u8, receiver_share_bps: u16 },
+    FreeAllowance(u64),
+    MaxTransfersPerUser(u64),
+    MinAccountAge(i64),
+    Schedule(FeeSchedule),
+    /// Replace the active event; a default (all-zero) event cancels it
+    DeclareEvent(ActiveEvent),
+}
+
+/// A named promotion window declared by governance. Only one exists at a
+/// time: declaring a new one replaces it, even mid-window, so overlapping
+/// events are expressed by declaring the later one when the earlier ends.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, Default, PartialEq, Eq, InitSpace)]
+pub struct ActiveEvent {
+    /// Printable ASCII name, zero padded
+    pub name: [u8; LABEL_LEN],
+    pub starts_at: i64,
+    /// End of the window, exclusive
+    pub ends_at: i64,
+    /// Rate used in place of the tier (or DeFi) rate and the launch ramp
+    pub fee_bps: u16,
+    /// Discount used in place of the loyalty, holding, code and dust discounts
+    pub discount_bps: u16,
+}
+
+/// Global statistics returned by `get_config_stats`
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct ConfigStats {
+    pub total_transfers: u64,
+    pub total_volume: u64,
+    pub total_fees_collected: u64,
+    pub total_discounts_given: u64,
+    pub tier_transfer_counts: [u64; FEE_TIER_COUNT],
+    pub tier_fees: [u64; FEE_TIER_COUNT],
+    pub unique_users: u64,
+    pub schedule_hash: [u8; 32],
+}
+
+/// One collector's part of an amount, as returned by `get_collector_shares`
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct CollectorShare {
+    pub collector: Pubkey,
+    pub amount: u64,
+}
+
+/// Outcome of `validate_config`
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ConfigValidation {
+    pub valid: bool,
+    /// Error number of the first failing check (0 when valid)
+    pub error_code: u32,
+}
+
+/// Key fields of one user state, as emitted by `get_user_states`
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct UserStateSummary {
+    pub owner: Pubkey,
+    pub transfer_count: u64,
+    pub total_volume: u64,
+    pub total_fees_paid: u64,
+    /// Loyalty tier recomputed from the current ladder
+    pub loyalty_tier: u8,
+    pub is_blacklisted: bool,
+    pub last_transfer_timestamp: i64,
+}
+
+// ============================================================================
+// Events
+// ============================================================================
+
+#[event]
+pub struct TransferExecuted {
+    pub mint: Pubkey,
+    pub owner: Pubkey,
+    pub amount: u64,
+    /// Amount the tier was selected on (scaled for interest-bearing mints,
+    /// and including earlier volume in the aggregation window)
+    pub priced_amount: u64,
+    pub fee_tier: u8,
+    pub fee_bps: u16,
+    pub base_fee: u64,
+    pub loyalty_tier: u8,
+    /// Fee forgone to discounts on this transfer
+    pub discount: u64,
+    /// Portion of the amount covered by the free allowance
+    pub free_amount: u64,
+    /// Whether the first-transfer fee replaced the tier fee
+    pub first_transfer_cliff: bool,
+    pub final_fee: u64,
+    pub sender_fee: u64,
+    pub receiver_fee: u64,
+    /// Whether the sender is exempt from the transfer cooldown
+    pub cooldown_exempt: bool,
+    /// Whether this transfer used decay grace instead of losing loyalty
+    pub decay_pending: bool,
+    /// Whether `fee_bps` includes the new all-time-high surcharge
+    pub new_high_surcharge: bool,
+    /// Whether the source or destination owner is the mint authority
+    pub mint_authority_transfer: bool,
+    /// Whether a discount code's bps were added to the loyalty discount
+    pub discount_code_applied: bool,
+    /// Owner's asserted fee ceiling (0 = none)
+    pub max_acceptable_fee: u64,
+    /// Fee in `fee_mint` base units (0 unless fees use a second token)
+    pub fee_in_fee_token: u64,
+    /// Whether this admin transfer went through while the hook was paused
+    pub pause_bypassed: bool,
+    /// Whether the fee was waived by the onboarding window
+    pub onboarding_free: bool,
+    /// Lamports charged to the owner in SOL fee mode
+    pub sol_fee: u64,
+    /// Whether the owner has opted out of the loyalty program
+    pub loyalty_opt_out: bool,
+    /// Priced at the DeFi rate (source or destination owner is listed)
+    pub defi_fee: bool,
+    /// The mint's label (all zeros = none)
+    pub label: [u8; LABEL_LEN],
+    /// Supply-driven change applied to the tier bps
+    pub supply_adjustment_bps: i32,
+    /// Sampled for compliance review (see `set_audit_sample_rate`)
+    pub audit_flag: bool,
+    /// Stacked discount after the `max_total_discount_bps` clamp
+    pub total_discount_bps: u16,
+    /// Whether the dust consolidation discount applied
+    pub dust_consolidation: bool,
+    /// Fee waived because the destination owner is a listed sink
+    pub sink_destination: bool,
+    /// Cap applied to the fee, the tighter of the global and user caps (0 = uncapped)
+    pub applied_max_fee: u64,
+    /// Amount-based component of `fee_bps`: the tier (or DeFi) rate after
+    /// any launch ramp and supply adjustment
+    pub amount_fee_bps: u16,
+    /// Balance-based component added to it (see `set_balance_fee`)
+    pub balance_fee_bps: u16,
+    /// Sent by an authorized minter, so left out of user stats and loyalty
+    pub minter_transfer: bool,
+    /// Part of the fee paid out of the owner's rebate balance
+    pub rebate_applied: u64,
+    /// `TIER_GATE_*` reason the loyalty tier was held back, if any
+    pub tier_gate: u8,
+    /// Priced by the governance-declared `active_event`
+    pub event_active: bool,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct ConfigSnapshot {
+    pub mint: Pubkey,
+    pub slot: u64,
+    pub timestamp: i64,
+    pub total_transfers: u64,
+    pub total_volume: u64,
+    pub total_fees_collected: u64,
+    pub total_discounts_given: u64,
+    pub tier_transfer_counts: [u64; FEE_TIER_COUNT],
+    pub tier_fees: [u64; FEE_TIER_COUNT],
+    pub rewards_bps: u16,
+    pub reward_per_volume: u128,
+    pub total_rewards_accrued: u64,
+    pub loyalty_tier_count: u8,
+    pub loyalty_tier_thresholds: [u64; MAX_LOYALTY_TIERS],
+    pub loyalty_tier_discount_bps: [u16; MAX_LOYALTY_TIERS],
+    pub min_account_age_seconds: i64,
+    pub fee_payer_mode: u8,
+    pub receiver_fee_share_bps: u16,
+    pub log_level: u8,
+    pub free_allowance_per_period: u64,
+    pub tier_cache_seconds: i64,
+    pub max_transfers_per_user: u64,
+    pub proposal_voting_seconds: i64,
+    pub proposal_count: u64,
+    pub first_transfer_fee_bps: u16,
+    pub fee_tier_thresholds: [u64; FEE_TIER_THRESHOLD_COUNT],
+    pub fee_tier_bps: [u16; FEE_TIER_COUNT],
+    pub max_fee: u64,
+    pub fee_granularity: u64,
+    pub fee_rounding_mode: u8,
+    pub cooldown_seconds: i64,
+    pub loyalty_decay_seconds: i64,
+    pub decay_grace_transfers: u64,
+    pub largest_transfer: u64,
+    pub surcharge_new_highs: bool,
+    pub new_high_surcharge_bps: u16,
+    pub exempt_mint_authority: bool,
+    pub auto_unpause_at: i64,
+    pub cooldown_calm_seconds: i64,
+    pub max_cooldown_seconds: i64,
+    pub tier_benefits: [u8; MAX_LOYALTY_TIERS],
+    pub volume_per_bp: u64,
+    pub max_continuous_discount_bps: u16,
+    pub holding_discount_mode: u8,
+    pub holding_discount_per_day_bps: u16,
+    pub max_holding_discount_bps: u16,
+    pub fee_token_rate: u64,
+    pub min_fee_in_fee_token: u64,
+    pub unique_users: u64,
+    pub onboarding_free_seconds: i64,
+    pub max_account_balance: u64,
+    pub sol_fee_per_transfer: u64,
+    pub lamports_per_token: u64,
+    pub total_sol_fees_collected: u64,
+    pub aggregation_seconds: i64,
+    pub cumulative_fee_bps_weighted: u128,
+    pub defi_fee_bps: u16,
+    pub defi_owner_count: u8,
+    pub label: [u8; LABEL_LEN],
+    pub min_slot_spacing: u64,
+    pub fee_escrow_seconds: i64,
+    pub total_fees_escrowed: u64,
+    pub supply_sensitivity_bps: u16,
+    pub max_supply_adjustment_bps: u16,
+    pub last_observed_supply: u64,
+    pub min_stake: u64,
+    pub audit_sample_rate: u32,
+    pub max_total_discount_bps: u16,
+    pub spike_threshold: u64,
+    pub spike_window_seconds: i64,
+    pub dust_consolidation_discount_bps: u16,
+    pub launch_timestamp: i64,
+    pub launch_fee_bps: u16,
+    pub ramp_seconds: i64,
+    pub cooldown_exemption_price: u64,
+    pub balance_per_bp: u64,
+    pub max_balance_fee_bps: u16,
+    pub max_dests_per_window: u32,
+    pub unfunded_rewards: u64,
+    pub claimable_volume: u64,
+    pub linked_user_count: u64,
+    pub governance_quorum_bps: u16,
+    pub untracked_transfers: u64,
+    pub untracked_volume: u64,
+}
+
+#[event]
+pub struct UserStatesRead {
+    pub mint: Pubkey,
+    pub states: Vec<UserStateSummary>,
+    /// Invalid accounts passed over with `skip_invalid`
+    pub skipped: Vec<Pubkey>,
+}
+
+#[event]
+pub struct ScheduleConfigured {
+    pub mint: Pubkey,
+    pub schedule_hash: [u8; 32],
+    pub schedule: FeeSchedule,
+}
+
+#[event]
+pub struct FeeScheduleChanged {
+    pub mint: Pubkey,
+    pub schedule_hash: [u8; 32],
+}
+
+#[event]
+pub struct UpgradeAcknowledged {
+    pub mint: Pubkey,
+    pub previous_slot: u64,
+    /// Acknowledged deployment slot (0 = check disabled)
+    pub deploy_slot: u64,
+}
+
+#[event]
+pub struct HourlyStats {
+    pub mint: Pubkey,
+    /// Unix time the bucket's hour began
+    pub hour_start: i64,
+    pub transfers: u64,
+    pub volume: u64,
+    pub fees: u64,
+}
+
+#[event]
+pub struct CircuitBreakerTripped {
+    pub mint: Pubkey,
+    pub window_volume: u64,
+    pub spike_threshold: u64,
+    pub window_start: i64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct PauseChanged {
+    pub mint: Pubkey,
+    pub paused: bool,
+    /// Scheduled un-pause time (0 = paused until manually lifted)
+    pub auto_unpause_at: i64,
+}
+
+#[event]
+pub struct ProgramAuthoritySet {
+    pub mint: Pubkey,
+    pub previous_authority: Pubkey,
+    pub authority: Pubkey,
+    pub authority_program: Pubkey,
+}
+
+#[event]
+pub struct StatelessTransferExecuted {
+    pub mint: Pubkey,
+    pub owner: Pubkey,
+    pub amount: u64,
+    pub fee_tier: u8,
+    pub fee_bps: u16,
+    pub final_fee: u64,
+    pub pause_bypassed: bool,
+    pub label: [u8; LABEL_LEN],
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct TransferRejected {
+    pub mint: Pubkey,
+    pub owner: Pubkey,
+    pub amount: u64,
+    /// Anchor error number of the rejection (the `ErrorCode` value)
+    pub reason_code: u32,
+    pub label: [u8; LABEL_LEN],
+}
+
+#[event]
+pub struct LoyaltySeeded {
+    pub mint: Pubkey,
+    pub user: Pubkey,
+    pub historical_transfers: u64,
+    /// Tier after the credit
+    pub loyalty_tier: u8,
+}
+
+#[event]
+pub struct TotalsRecounted {
+    pub mint: Pubkey,
+    pub session: u64,
+    pub users_counted: u64,
+    pub before_transfers: u64,
+    pub before_volume: u64,
+    pub after_transfers: u64,
+    pub after_volume: u64,
+}
+
+#[event]
+pub struct Milestone {
+    pub mint: Pubkey,
+    pub owner: Pubkey,
+    /// `MILESTONE_TRANSFERS` or `MILESTONE_VOLUME`
+    pub counter: u8,
+    pub threshold: u64,
+    /// The counter's value after the transfer
+    pub value: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct TierUp {
+    pub mint: Pubkey,
+    pub owner: Pubkey,
+    pub previous_tier: u8,
+    pub new_tier: u8,
+    pub bonus: u64,
+    pub rebate_balance: u64,
+}
+
+#[event]
+pub struct EscrowedFeeSettled {
+    pub mint: Pubkey,
+    pub user: Pubkey,
+    pub amount: u64,
+    /// Reclaimed by the owner rather than released to the collector
+    pub reclaimed: bool,
+}
+
+#[event]
+pub struct RebateForfeited {
+    pub mint: Pubkey,
+    pub user: Pubkey,
+    pub amount: u64,
+}
+
+#[event]
+pub struct CooldownExemptionPurchased {
+    pub mint: Pubkey,
+    pub owner: Pubkey,
+    /// Lamports paid to the fee collector
+    pub price: u64,
+}
+
+#[event]
+pub struct PointsClaimed {
+    pub mint: Pubkey,
+    pub owner: Pubkey,
+    pub points: u64,
+    pub volume_converted: u64,
+    pub points_balance: u64,
+}
+
+#[event]
+pub struct PointsSpent {
+    pub mint: Pubkey,
+    pub owner: Pubkey,
+    pub points: u64,
+    pub points_balance: u64,
+    pub rebate_balance: u64,
+}
+
+#[event]
+pub struct FeeRefunded {
+    pub mint: Pubkey,
+    pub user: Pubkey,
+    pub amount: u64,
+    pub rebate_balance: u64,
+}
+
+#[event]
+pub struct FeeCollectorRotated {
+    pub mint: Pubkey,
+    pub old_collector: Pubkey,
+    pub new_collector: Pubkey,
+    pub swept_amount: u64,
+}
+
+#[event]
+pub struct FeeCollectorAdded {
+    pub mint: Pubkey,
+    pub collector: Pubkey,
+    pub collector_count: u8,
+}
+
+#[event]
+pub struct FeeCollectorRemoved {
+    pub mint: Pubkey,
+    pub collector: Pubkey,
+    /// 1 with the authority as sole collector after removing the last one
+    pub collector_count: u8,
+}
+
+#[event]
+pub struct StakeChanged {
+    pub mint: Pubkey,
+    pub owner: Pubkey,
+    pub amount: u64,
+    /// Stake (true) or unstake (false)
+    pub staked: bool,
+    pub owner_stake: u64,
+    pub total_staked: u64,
+}
+
+#[event]
+pub struct StakingRewardsClaimed {
+    pub mint: Pubkey,
+    pub owner: Pubkey,
+    pub amount: u64,
+    pub remaining: u64,
+}
+
+#[event]
+pub struct RewardsFunded {
+    pub mint: Pubkey,
+    pub funder: Pubkey,
+    pub amount: u64,
+    pub unfunded_rewards: u64,
+}
+
+#[event]
+pub struct RewardsClaimed {
+    pub mint: Pubkey,
+    pub owner: Pubkey,
+    pub amount: u64,
+    pub remaining: u64,
+}
+
+#[event]
+pub struct ProposalCreated {
+    pub mint: Pubkey,
+    pub proposal_id: u64,
+    pub proposer: Pubkey,
+    pub change: ConfigChange,
+    pub voting_ends_at: i64,
+}
+
+#[event]
+pub struct VoteCast {
+    pub mint: Pubkey,
+    pub proposal_id: u64,
+    pub voter: Pubkey,
+    pub approve: bool,
+    pub weight: u64,
+}
+
+#[event]
+pub struct ProposalExecuted {
+    pub mint: Pubkey,
+    pub proposal_id: u64,
+    pub passed: bool,
+    pub yes_votes: u64,
+    pub no_votes: u64,
+    pub quorum_votes: u64,
+}
+
+// ============================================================================
+// Errors
+// ============================================================================
+
+/// Numbers are part of the client interface: new errors are appended with
+/// the next number, and existing variants are never renumbered or reused
+#[error_code(offset = 0)]
+pub enum ErrorCode {
+    #[msg("Transfer hook is currently paused")]
+    HookPaused = 6000,
     
-    pub mint: InterfaceAccount<'info, Mint>,
+    #[msg("User is blacklisted from transfers")]
+    UserBlacklisted = 6001,
     
-    pub authority: Signer<'info>,
+    #[msg("Invalid fee configuration")]
+    InvalidFeeConfig = 6002,
+    
+    #[msg("Arithmetic overflow")]
+    ArithmeticOverflow = 6003,
+    
+    #[msg("Nothing to claim")]
+    NothingToClaim = 6004,
+    
+    #[msg("User state owner does not match the transfer owner")]
+    OwnerMismatch = 6005,
+    
+    #[msg("Invalid loyalty tier configuration")]
+    InvalidLoyaltyConfig = 6006,
+    
+    #[msg("Account is too new to transfer")]
+    AccountTooNew = 6007,
+    
+    #[msg("Fee balance gate: account does not hold its attributed fee share")]
+    FeeBalanceGateFailed = 6008,
+    
+    #[msg("Receiver fee account is required for this fee payer mode")]
+    ReceiverFeeAccountMissing = 6009,
+    
+    #[msg("Refund exceeds recorded fees")]
+    RefundExceedsFees = 6010,
+    
+    #[msg("Transfer count limit reached for this account")]
+    TransferCountExceeded = 6011,
+    
+    #[msg("Governance is disabled for this mint")]
+    GovernanceDisabled = 6012,
+    
+    #[msg("Voting on this proposal has closed")]
+    VotingClosed = 6013,
+    
+    #[msg("Voting on this proposal is still open")]
+    VotingOpen = 6014,
+    
+    #[msg("Proposal has already been executed")]
+    ProposalAlreadyExecuted = 6015,
+    
+    #[msg("Voter has no transfer volume")]
+    NoVotingWeight = 6016,
+    
+    #[msg("Transfers to or from the fee collector are blocked")]
+    CollectorTransferBlocked = 6017,
+    
+    #[msg("Transfer cooldown has not elapsed")]
+    CooldownActive = 6018,
+    
+    #[msg("Account is not a user state for this mint")]
+    InvalidUserState = 6019,
+    
+    #[msg("Discount code belongs to another mint")]
+    InvalidDiscountCode = 6020,
+    
+    #[msg("Fee receipt account is required while receipts are issued")]
+    ReceiptAccountMissing = 6021,
+    
+    #[msg("Fee exceeds the maximum the owner accepts")]
+    FeeExceedsMax = 6022,
+    
+    #[msg("Fee collector is already listed")]
+    DuplicateFeeCollector = 6023,
+    
+    #[msg("Fee collector list is full")]
+    TooManyFeeCollectors = 6024,
+    
+    #[msg("Fee collector is not listed")]
+    FeeCollectorNotFound = 6025,
+    
+    #[msg("Transfer requires a memo instruction")]
+    MemoRequired = 6026,
+    
+    #[msg("Outgoing transfers are blocked for this user")]
+    OutgoingBlocked = 6027,
+    
+    #[msg("Incoming transfers are blocked for the receiver")]
+    IncomingBlocked = 6028,
+    
+    #[msg("Loyalty has already been seeded for this user")]
+    LoyaltyAlreadySeeded = 6029,
+    
+    #[msg("No recount is in progress")]
+    RecountNotActive = 6030,
+    
+    #[msg("Sender is not whitelisted")]
+    NotWhitelisted = 6031,
+    
+    #[msg("Receiver is not whitelisted")]
+    ReceiverNotWhitelisted = 6032,
+    
+    #[msg("Fee token account is missing or not the sender's fee-mint account")]
+    InvalidFeeTokenAccount = 6033,
+    
+    #[msg("Fee token balance gate: sender does not hold the converted fee")]
+    FeeTokenBalanceGateFailed = 6034,
+    
+    #[msg("Stake amount is zero or exceeds the staked balance")]
+    InvalidStakeAmount = 6035,
+    
+    #[msg("Linked root's user state is required")]
+    LinkedRootStateMissing = 6036,
+    
+    #[msg("Root must be another, unlinked user")]
+    InvalidLinkedRoot = 6037,
+    
+    #[msg("Only the authority may change an existing link")]
+    UnauthorizedLink = 6038,
+    
+    #[msg("Transfer hook invoked while already running")]
+    ReentrancyDetected = 6039,
+    
+    #[msg("Reentrancy guard account is required")]
+    ReentrancyGuardMissing = 6040,
+    
+    #[msg("Transfer would exceed the maximum account balance")]
+    MaxBalanceExceeded = 6041,
+    
+    #[msg("SOL fee collector is missing or does not match the config")]
+    InvalidSolFeeCollector = 6042,
+    
+    #[msg("SOL fee escrow cannot cover the amount and stay rent-exempt")]
+    InsufficientSolBalance = 6043,
+    
+    #[msg("SOL fee collector would not be rent-exempt")]
+    SolFeeCollectorNotRentExempt = 6044,
+    
+    #[msg("Fee schedule is permanently locked")]
+    FeeScheduleLocked = 6045,
+    
+    #[msg("DeFi owner already listed")]
+    DuplicateDefiOwner = 6046,
+    
+    #[msg("DeFi owner list is full")]
+    TooManyDefiOwners = 6047,
+    
+    #[msg("DeFi owner not listed")]
+    DefiOwnerNotFound = 6048,
+    
+    #[msg("Label must be printable ASCII followed by zero padding")]
+    InvalidLabel = 6049,
+    
+    #[msg("User state does not exist and the hook cannot create it; call initialize_user_state first")]
+    CannotInitUserState = 6050,
+    
+    #[msg("Seeds do not derive a valid authority PDA of the controller program")]
+    InvalidProgramAuthority = 6051,
+    
+    #[msg("Too few slots since the user's last transfer")]
+    SlotSpacingViolation = 6052,
+    
+    #[msg("No escrowed fee")]
+    NoEscrowedFee = 6053,
+    
+    #[msg("Escrow window has passed")]
+    EscrowExpired = 6054,
+    
+    #[msg("Sender has less than the minimum stake")]
+    InsufficientStake = 6055,
+    
+    #[msg("No expired rebate to forfeit")]
+    RebateNotExpired = 6056,
+    
+    #[msg("Program was upgraded: acknowledge the new deployment first")]
+    UpgradeNotAcknowledged = 6057,
+    
+    #[msg("Extra account metas are out of date: run sync_extra_account_metas")]
+    ExtraAccountMetasStale = 6058,
+    
+    #[msg("Not enough unclaimed volume for a point")]
+    NoPointsToClaim = 6059,
+    
+    #[msg("Insufficient points balance")]
+    InsufficientPoints = 6060,
+    
+    #[msg("Fee collector token account missing or not the collector's ATA for this mint")]
+    InvalidFeeCollectorAccount = 6061,
+    
+    #[msg("Cooldown exemptions are not for sale")]
+    CooldownExemptionNotForSale = 6062,
+    
+    #[msg("User is already exempt from the cooldown")]
+    AlreadyCooldownExempt = 6063,
+    
+    #[msg("Minter is already authorized")]
+    DuplicateAuthorizedMinter = 6064,
+    
+    #[msg("Authorized minter list is full")]
+    TooManyAuthorizedMinters = 6065,
+    
+    #[msg("Minter is not authorized")]
+    AuthorizedMinterNotFound = 6066,
+    
+    #[msg("Too many distinct destinations this window")]
+    TooManyDestinations = 6067,
+    
+    #[msg("Sweep the old fee collector's balance before rotating")]
+    CollectorBalanceNotSwept = 6068,
+    
+    #[msg("Matching top-level token transfer not found")]
+    PairedTransferMissing = 6069,
+    
+    #[msg("Rewards vault cannot cover the claim")]
+    RewardsVaultUnderfunded = 6070,
+    
+    #[msg("Rewards funding needs a nonzero amount and claimable volume")]
+    InvalidRewardsFunding = 6071,
+    
+    #[msg("SOL fee escrow is required while SOL fees are on")]
+    SolFeeEscrowMissing = 6073,
+    
+    #[msg("Stateless transfers cannot be combined with receipts or linked roots")]
+    StatelessConflict = 6074,
+    
+    #[msg("Linked roots are disabled for this mint")]
+    LinkedRootsDisabled = 6075,
+    
+    #[msg("Accounts are still linked to root identities")]
+    LinkedAccountsRemain = 6076,
+    
+    #[msg("Discount code is expired or used up, or another code is still pending")]
+    DiscountCodeUnavailable = 6077,
+    
+    #[msg("Receiver's fee share must be paid to the collector by the next instruction")]
+    ReceiverFeeUnpaid = 6078,
 }
 
-#[derive(Accounts)]
-pub struct SetUserStatus<'info> {
-    #[account(
-        seeds = [b"hook-config", mint.key().as_ref()],
-        bump,
-        has_one = authority
-    )]
-    pub hook_config: Account<'info, HookConfig>,
-    
-    pub mint: InterfaceAccount<'info, Mint>,
-    
-    #[account(
-        mut,
-        seeds = [b"user-state", user.key().as_ref(), mint.key().as_ref()],
-        bump
-    )]
-    pub user_state: Account<'info, UserState>,
-    
-    /// CHECK: User being blacklisted/whitelisted
-    pub user: UncheckedAccount<'info>,
-    
-    pub authority: Signer<'info>,
-}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BOUNDARY_AMOUNTS: [u64; 14] = [
+        0,
+        1,
+        9_999,
+        10_000,
+        TIER_1_THRESHOLD - 1,
+        TIER_1_THRESHOLD,
+        TIER_2_THRESHOLD - 1,
+        TIER_2_THRESHOLD,
+        TIER_3_THRESHOLD - 1,
+        TIER_3_THRESHOLD,
+        u64::MAX / 10_000,
+        u64::MAX / 2,
+        u64::MAX - 1,
+        u64::MAX,
+    ];
+
+    const BOUNDARY_BPS: [u16; 9] = [0, 1, 10, 25, 50, 100, 5_000, 9_999, 10_000];
+
+    /// Deterministic xorshift so the sweep is reproducible without extra deps
+    fn xorshift(state: &mut u64) -> u64 {
+        *state ^= *state << 13;
+        *state ^= *state >> 7;
+        *state ^= *state << 17;
+        *state
+    }
+
+    fn assert_fee_invariants(amount: u64, fee_bps: u16, discount_bps: u16) {
+        let fee = compute_final_fee(amount, fee_bps, discount_bps)
+            .expect("bps <= 10000 must never overflow");
+
+        assert!(fee.final_fee <= amount, "final fee above amount: {amount} {fee_bps} {discount_bps}");
+        assert!(fee.final_fee <= fee.base_fee);
+        assert!(fee.discount <= fee.base_fee);
+        assert_eq!(fee.base_fee - fee.discount, fee.final_fee);
+    }
+
+    #[test]
+    fn fee_pipeline_boundaries() {
+        for &amount in BOUNDARY_AMOUNTS.iter() {
+            for &fee_bps in BOUNDARY_BPS.iter() {
+                for &discount_bps in BOUNDARY_BPS.iter() {
+                    assert_fee_invariants(amount, fee_bps, discount_bps);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn fee_pipeline_pseudo_random_sweep() {
+        let mut state = 0x9E37_79B9_7F4A_7C15;
+        for _ in 0..20_000 {
+            let amount = xorshift(&mut state);
+            let fee_bps = (xorshift(&mut state) % 10_001) as u16;
+            let discount_bps = (xorshift(&mut state) % 10_001) as u16;
+            assert_fee_invariants(amount, fee_bps, discount_bps);
+        }
+    }
+
+    #[test]
+    fn fee_pipeline_u64_max_regressions() {
+        let fee = compute_final_fee(u64::MAX, 10_000, 0).unwrap();
+        assert_eq!(fee.base_fee, u64::MAX);
+        assert_eq!(fee.final_fee, u64::MAX);
+
+        let fee = compute_final_fee(u64::MAX, TIER_4_FEE_BPS, 50).unwrap();
+        assert_eq!(fee.final_fee, 0);
+        assert_eq!(fee.discount, fee.base_fee);
+
+        let fee = compute_final_fee(u64::MAX, 100, 10).unwrap();
+        assert_eq!(fee.base_fee, (u64::MAX as u128 * 100 / 10_000) as u64);
+        assert_eq!(fee.final_fee, fee.base_fee - (u64::MAX as u128 * 10 / 10_000) as u64);
+    }
+
+    #[test]
+    fn bps_to_fee_edges() {
+        assert_eq!(bps_to_fee(1_000_000, 0), Some(0));
+        assert_eq!(bps_to_fee(u64::MAX, 0), Some(0));
+        assert_eq!(bps_to_fee(1_000_000, 10000), Some(1_000_000));
+        assert_eq!(bps_to_fee(u64::MAX, 10000), Some(u64::MAX));
+        assert_eq!(bps_to_fee(u64::MAX, 10001), None);
+        // Rounds down to the base unit
+        assert_eq!(bps_to_fee(199, 50), Some(0));
+        assert_eq!(bps_to_fee(200, 50), Some(1));
+    }
+
+    #[test]
+    fn fee_to_bps_inverts_bps_to_fee() {
+        for &bps in BOUNDARY_BPS.iter() {
+            let fee = bps_to_fee(TIER_3_THRESHOLD, bps).unwrap();
+            assert_eq!(fee_to_bps(TIER_3_THRESHOLD, fee), Some(bps));
+        }
+        assert_eq!(fee_to_bps(u64::MAX, u64::MAX), Some(10000));
+        assert_eq!(fee_to_bps(0, 0), None);
+        assert_eq!(fee_to_bps(1, u64::MAX), None);
+    }
+
+    #[test]
+    fn schedule_validation() {
+        let schedule = default_schedule();
+        assert!(validate_schedule(&schedule).is_ok());
+
+        let mut bad = schedule.clone();
+        bad.fee_tier_thresholds = [TIER_2_THRESHOLD, TIER_1_THRESHOLD, TIER_3_THRESHOLD];
+        assert!(validate_schedule(&bad).is_err());
+
+        let mut bad = schedule.clone();
+        bad.fee_tier_bps[3] = 10001;
+        assert!(validate_schedule(&bad).is_err());
+
+        let mut bad = schedule.clone();
+        bad.loyalty_tier_discount_bps[2] = TIER_1_FEE_BPS + 1;
+        assert!(validate_schedule(&bad).is_err());
+
+        let mut bad = schedule;
+        bad.loyalty_tier_thresholds[1] = LOYALTY_BRONZE;
+        assert!(validate_schedule(&bad).is_err());
+    }
+
+    #[test]
+    fn max_fee_cap_keeps_breakdown_consistent() {
+        let fee = cap_fee(compute_final_fee(TIER_3_THRESHOLD, 100, 10).unwrap(), 1_000);
+        assert_eq!(fee.final_fee, 1_000);
+        assert_eq!(fee.base_fee - fee.discount, fee.final_fee);
+
+        let uncapped = compute_final_fee(TIER_3_THRESHOLD, 100, 10).unwrap();
+        assert_eq!(cap_fee(uncapped, 0), uncapped);
+    }
+
+    fn default_config() -> HookConfig {
+        let mut config = HookConfig::default();
+        apply_schedule(&mut config, &default_schedule());
+        config.tier_fees_enabled = [true; FEE_TIER_COUNT];
+        config
+    }
+
+    #[test]
+    fn annual_fee_estimate_matches_per_transfer_walk() {
+        let mut config = default_config();
+        for first_transfer_fee_bps in [0, 200] {
+            config.first_transfer_fee_bps = first_transfer_fee_bps;
+            for amount in [TIER_1_THRESHOLD - 1, TIER_3_THRESHOLD * 3] {
+                let mut expected = 0;
+                for count in 0..150u64 {
+                    let (fee_bps, discount_bps) = if count == 0 && first_transfer_fee_bps > 0 {
+                        (first_transfer_fee_bps, 0)
+                    } else {
+                        let tier = get_loyalty_tier(&config, count);
+                        (calculate_fee_tier(&config, amount), loyalty_discount_bps(&config, tier))
+                    };
+                    expected += compute_final_fee(amount, fee_bps, discount_bps).unwrap().final_fee;
+                    assert_eq!(estimate_fees(&config, amount, count + 1).unwrap(), expected);
+                }
+            }
+        }
+        assert_eq!(estimate_fees(&config, TIER_2_THRESHOLD, 0).unwrap(), 0);
+    }
+
+    fn fee_of(final_fee: u64) -> FeeBreakdown {
+        FeeBreakdown { base_fee: final_fee, discount: 0, final_fee }
+    }
+
+    #[test]
+    fn fee_rounding_boundaries() {
+        let round = |fee, mode, ceiling| round_fee(fee_of(fee), 1_000, mode, ceiling).final_fee;
+
+        assert_eq!(round(1_499, FEE_ROUND_NEAREST, u64::MAX), 1_000);
+        assert_eq!(round(1_500, FEE_ROUND_NEAREST, u64::MAX), 2_000);
+        assert_eq!(round(2_000, FEE_ROUND_NEAREST, u64::MAX), 2_000);
+        assert_eq!(round(1_999, FEE_ROUND_DOWN, u64::MAX), 1_000);
+        assert_eq!(round(1_001, FEE_ROUND_UP, u64::MAX), 2_000);
+        assert_eq!(round(1_000, FEE_ROUND_UP, u64::MAX), 1_000);
+        assert_eq!(round(0, FEE_ROUND_UP, u64::MAX), 0);
+
+        // Never rounds above the ceiling (amount or max fee)
+        assert_eq!(round(1_001, FEE_ROUND_UP, 1_500), 1_000);
+        assert_eq!(round(900, FEE_ROUND_NEAREST, 900), 0);
+        assert_eq!(round(u64::MAX, FEE_ROUND_UP, u64::MAX), u64::MAX - u64::MAX % 1_000);
+
+        // Granularity 0 or 1 keeps base-unit precision
+        assert_eq!(round_fee(fee_of(1_234), 1, FEE_ROUND_UP, u64::MAX).final_fee, 1_234);
+        assert_eq!(round_fee(fee_of(1_234), 0, FEE_ROUND_UP, u64::MAX).final_fee, 1_234);
+    }
+
+    #[test]
+    fn volume_percentile_uses_bucket_midpoints() {
+        assert_eq!(volume_bucket(0), 0);
+        assert_eq!(volume_bucket(9), 0);
+        assert_eq!(volume_bucket(10), 1);
+        assert_eq!(volume_bucket(u64::MAX), VOLUME_BUCKET_COUNT - 1);
+
+        let mut config = HookConfig::default();
+        assert_eq!(volume_percentile_bps(&config.volume_histogram, 500), 0);
+
+        record_volume_bucket(&mut config, None, 5);
+        record_volume_bucket(&mut config, None, 50);
+        record_volume_bucket(&mut config, None, 500);
+        record_volume_bucket(&mut config, Some(500), 5_000);
+        assert_eq!(config.volume_histogram[..4], [1, 1, 0, 1]);
+
+        assert_eq!(volume_percentile_bps(&config.volume_histogram, 5), 1666);
+        assert_eq!(volume_percentile_bps(&config.volume_histogram, 50), 5000);
+        assert_eq!(volume_percentile_bps(&config.volume_histogram, 5_000), 8333);
+        assert_eq!(volume_percentile_bps(&config.volume_histogram, u64::MAX), 10000);
+    }
+
+    #[test]
+    fn loyalty_decay_waits_out_grace_transfers() {
+        let mut config = default_config();
+        config.loyalty_decay_seconds = 1_000;
+        config.decay_grace_transfers = 2;
+
+        let mut user = UserState {
+            transfer_count: LOYALTY_GOLD,
+            last_transfer_timestamp: 0,
+            ..UserState::default()
+        };
+
+        // A prompt follow-up closes the gap and clears the grace count
+        assert!(apply_loyalty_decay(&config, &mut user, 5_000));
+        user.last_transfer_timestamp = 5_000;
+        assert!(!apply_loyalty_decay(&config, &mut user, 5_500));
+        assert_eq!(user.decay_grace_used, 0);
+        user.last_transfer_timestamp = 5_500;
+
+        assert!(apply_loyalty_decay(&config, &mut user, 10_000));
+        user.last_transfer_timestamp = 10_000;
+        assert!(apply_loyalty_decay(&config, &mut user, 20_000));
+        user.last_transfer_timestamp = 20_000;
+        assert_eq!(get_loyalty_tier(&config, loyalty_count(&user)), 3);
+
+        user.loyalty_credit = LOYALTY_SILVER;
+        assert!(!apply_loyalty_decay(&config, &mut user, 30_000));
+        assert_eq!(loyalty_count(&user), 0);
+        assert_eq!(get_loyalty_tier(&config, loyalty_count(&user)), 0);
+    }
+
+    #[test]
+    fn cooldown_escalates_with_rapid_streak_up_to_cap() {
+        let mut config = HookConfig {
+            cooldown_seconds: 60,
+            cooldown_calm_seconds: 600,
+            max_cooldown_seconds: 200,
+            ..HookConfig::default()
+        };
+        let mut user = UserState::default();
+
+        assert_eq!(effective_cooldown(&config, 0), 60);
+        update_rapid_streak(&config, &mut user, 60);
+        update_rapid_streak(&config, &mut user, 120);
+        assert_eq!(user.rapid_streak, 2);
+        assert_eq!(effective_cooldown(&config, user.rapid_streak), 180);
+        assert_eq!(effective_cooldown(&config, 3), 200);
+        assert_eq!(effective_cooldown(&config, u16::MAX), 200);
+
+        update_rapid_streak(&config, &mut user, 600);
+        assert_eq!(user.rapid_streak, 0);
+
+        config.cooldown_calm_seconds = 0;
+        update_rapid_streak(&config, &mut user, 1);
+        assert_eq!(user.rapid_streak, 0);
+    }
+
+    #[test]
+    fn counterparty_filter_counts_repeats_once() {
+        let mut filter = [0u8; COUNTERPARTY_FILTER_BYTES];
+        let alice = Pubkey::new_from_array([1; 32]);
+        let bob = Pubkey::new_from_array([200; 32]);
+
+        assert!(record_counterparty(&mut filter, &alice));
+        assert!(!record_counterparty(&mut filter, &alice));
+        assert!(record_counterparty(&mut filter, &bob));
+        assert!(!record_counterparty(&mut filter, &bob));
+        assert_eq!(filter.iter().map(|byte| byte.count_ones()).sum::<u32>(), 2);
+
+        // A destination whose bits are all already set is a false positive
+        let mut mixed = [0u8; 32];
+        mixed[..3].copy_from_slice(&[1, 200, 1]);
+        assert!(!record_counterparty(&mut filter, &Pubkey::new_from_array(mixed)));
+    }
+
+    #[test]
+    fn fee_collector_list_management() {
+        let authority = Pubkey::new_unique();
+        let (a, b, c) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let mut config = HookConfig {
+            authority,
+            max_fee_collectors: 3,
+            ..HookConfig::default()
+        };
+
+        add_collector(&mut config, a, &[10000]).unwrap();
+        assert!(add_collector(&mut config, b, &[5000, 4000]).is_err());
+        add_collector(&mut config, b, &[5000, 5000]).unwrap();
+        assert!(add_collector(&mut config, a, &[4000, 3000, 3000]).is_err());
+        add_collector(&mut config, c, &[4000, 3000, 3000]).unwrap();
+        assert!(add_collector(&mut config, Pubkey::new_unique(), &[2500; 4]).is_err());
+
+        assert_eq!(config.fee_collector, a);
+
+        remove_collector(&mut config, a, &[2000, 8000]).unwrap();
+        assert_eq!(config.fee_collectors[..2], [b, c]);
+        assert_eq!(config.fee_collector_weights[..3], [2000, 8000, 0]);
+        assert_eq!(config.fee_collector, b);
+        assert!(remove_collector(&mut config, a, &[10000]).is_err());
+
+        // Replacing the primary keeps its weight and may not duplicate another
+        assert!(set_primary_collector(&mut config, c).is_err());
+        set_primary_collector(&mut config, a).unwrap();
+        assert_eq!((config.fee_collector, config.fee_collectors[0]), (a, a));
+        assert!(is_fee_collector(&config, &c) && !is_fee_collector(&config, &b));
+
+        remove_collector(&mut config, a, &[10000]).unwrap();
+        remove_collector(&mut config, c, &[]).unwrap();
+        assert_eq!(config.fee_collector_count, 1);
+        assert_eq!(config.fee_collectors[0], authority);
+        assert_eq!(config.fee_collector_weights[0], 10000);
+        assert_eq!(config.fee_collector, authority);
+    }
+
+    #[test]
+    fn collector_shares_follow_weights() {
+        let (a, b) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let mut config = HookConfig {
+            max_fee_collectors: 2,
+            ..HookConfig::default()
+        };
+        add_collector(&mut config, a, &[10000]).unwrap();
+        add_collector(&mut config, b, &[3333, 6667]).unwrap();
+
+        let shares = collector_shares(&config, 100).unwrap();
+        assert_eq!(shares, vec![
+            CollectorShare { collector: a, amount: 34 },
+            CollectorShare { collector: b, amount: 66 },
+        ]);
+        assert_eq!(collector_shares(&config, u64::MAX).unwrap().iter().map(|share| share.amount).sum::<u64>(), u64::MAX);
+    }
+
+    #[test]
+    fn continuous_discount_follows_curve_up_to_cap() {
+        let mut config = HookConfig {
+            volume_per_bp: TIER_2_THRESHOLD,
+            max_continuous_discount_bps: 40,
+            ..HookConfig::default()
+        };
+
+        assert_eq!(continuous_discount_bps(&config, 0), 0);
+        assert_eq!(continuous_discount_bps(&config, TIER_2_THRESHOLD - 1), 0);
+        assert_eq!(continuous_discount_bps(&config, TIER_3_THRESHOLD + 1), 10);
+        assert_eq!(continuous_discount_bps(&config, u64::MAX), 40);
+
+        config.volume_per_bp = 0;
+        assert_eq!(continuous_discount_bps(&config, u64::MAX), 0);
+    }
+
+    #[test]
+    fn holding_discount_counts_full_days_up_to_cap() {
+        let mut config = HookConfig {
+            holding_discount_mode: HOLDING_DISCOUNT_STACK,
+            holding_discount_per_day_bps: 2,
+            max_holding_discount_bps: 30,
+            ..HookConfig::default()
+        };
+        let mut user = UserState {
+            first_transfer_timestamp: 1_000,
+            ..UserState::default()
+        };
+        let day = DAILY_WINDOW_SECONDS;
+
+        // A user state that has not transferred yet holds for 0 days
+        assert_eq!(holding_discount_bps(&config, &user, 1_000 + 10 * day), 0);
+
+        user.transfer_count = 1;
+        assert_eq!(holding_discount_bps(&config, &user, 1_000 + day - 1), 0);
+        assert_eq!(holding_discount_bps(&config, &user, 1_000 + 3 * day), 6);
+        assert_eq!(holding_discount_bps(&config, &user, i64::MAX), 30);
+        assert_eq!(holding_discount_bps(&config, &user, 0), 0);
+
+        config.holding_discount_mode = HOLDING_DISCOUNT_OFF;
+        assert_eq!(holding_discount_bps(&config, &user, 1_000 + 3 * day), 0);
+    }
+
+    #[test]
+    fn native_mints_are_detected() {
+        assert!(is_native_mint(&spl_token::native_mint::ID));
+        assert!(is_native_mint(&spl_token_2022::native_mint::ID));
+        assert!(!is_native_mint(&Pubkey::new_unique()));
+    }
+
+    #[test]
+    fn rewards_are_never_over_claimed() {
+        let mut config = HookConfig::default();
+        let mut alice = UserState::default();
+        let mut bob = UserState::default();
+
+        for (i, amount) in [7_777u64, 1_000_003, 13, 999_999_937, 42].iter().enumerate() {
+            let user = if i % 2 == 0 { &mut alice } else { &mut bob };
+            settle_rewards(&config, user).unwrap();
+            user.total_volume += amount;
+            add_reward_volume(&mut config, user, *amount).unwrap();
+            accrue_rewards(&mut config, amount / 3).unwrap();
+        }
+
+        settle_rewards(&config, &mut alice).unwrap();
+        settle_rewards(&config, &mut bob).unwrap();
+        assert!(alice.reward_share + bob.reward_share <= config.total_rewards_accrued);
+        assert!(alice.reward_share > 0 && bob.reward_share > 0);
+    }
+
+    #[test]
+    fn fee_token_conversion_floors_and_caps() {
+        // 9-decimal token priced at 2.5 units of a 6-decimal fee token
+        let mut config = HookConfig {
+            fee_token_rate: 2_500_000,
+            ..HookConfig::default()
+        };
+        assert_eq!(to_fee_token(1_000_000_000, config.fee_token_rate, 9), 2_500_000);
+        assert_eq!(fee_in_fee_token(&config, 4_000_000, 1_000_000_000, 9), 10_000);
+
+        // Small fees round to zero without a floor
+        assert_eq!(fee_in_fee_token(&config, 300, 30_000, 9), 0);
+        config.min_fee_in_fee_token = 5;
+        assert_eq!(fee_in_fee_token(&config, 300, 30_000, 9), 5);
+        assert_eq!(fee_in_fee_token(&config, 0, 30_000, 9), 0);
+
+        // The floor never charges more than the transfer is worth
+        assert_eq!(fee_in_fee_token(&config, 1, 1_000, 9), 2);
+
+        // Nor more than the converted max fee
+        config.min_fee_in_fee_token = 5_000;
+        config.max_fee = 1_000_000;
+        assert_eq!(fee_in_fee_token(&config, 300, 1_000_000_000, 9), 2_500);
+
+        // 2-decimal token priced at 0.5 units of an 18-decimal fee token
+        let config = HookConfig {
+            fee_token_rate: 500_000_000_000_000_000,
+            ..HookConfig::default()
+        };
+        assert_eq!(fee_in_fee_token(&config, 1, 100, 2), 5_000_000_000_000_000);
+        assert_eq!(to_fee_token(u64::MAX, u64::MAX, 0), u64::MAX);
+        assert_eq!(to_fee_token(u64::MAX, 1, u8::MAX), 0);
+    }
+
+    #[test]
+    fn rent_liability_scales_with_users() {
+        let rent = Rent::default();
+        let per_state = rent.minimum_balance(8 + UserState::INIT_SPACE);
+
+        assert_eq!(rent_liability(&rent, 0), 0);
+        assert_eq!(rent_liability(&rent, 1_000), per_state * 1_000);
+        assert_eq!(rent_liability(&rent, u64::MAX), u64::MAX);
+    }
+
+    #[test]
+    fn staking_rewards_follow_stake_time() {
+        let mut config = HookConfig::default();
+        let mut pool = StakePool::default();
+        let mut alice = StakeAccount::default();
+        let mut bob = StakeAccount::default();
+
+        // Fees earned with nothing staked are not handed to the first staker
+        config.pending_staking_rewards = 1_000;
+        sync_stake_pool(&mut config, &mut pool).unwrap();
+        alice.amount = 300;
+        pool.total_staked = 300;
+        assert_eq!(pool.reward_per_stake, 0);
+
+        config.pending_staking_rewards = 900;
+        sync_stake_pool(&mut config, &mut pool).unwrap();
+
+        // Bob joins after those rewards and sees none of them
+        settle_stake(&pool, &mut bob).unwrap();
+        bob.amount = 600;
+        bob.reward_debt = scaled_rewards(bob.amount, pool.reward_per_stake).unwrap();
+        pool.total_staked += 600;
+
+        config.pending_staking_rewards = 901;
+        sync_stake_pool(&mut config, &mut pool).unwrap();
+
+        // Alice fully unstakes; her settled rewards stay claimable
+        settle_stake(&pool, &mut alice).unwrap();
+        pool.total_staked -= alice.amount;
+        alice.amount = 0;
+        alice.reward_debt = 0;
+        config.pending_staking_rewards = 50;
+        sync_stake_pool(&mut config, &mut pool).unwrap();
+        settle_stake(&pool, &mut alice).unwrap();
+        settle_stake(&pool, &mut bob).unwrap();
+
+        assert_eq!(alice.pending_rewards, 900 + 300);
+        assert_eq!(bob.pending_rewards, 600 + 50);
+        assert!(alice.pending_rewards + bob.pending_rewards <= pool.total_rewards_accrued);
+    }
+
+    #[test]
+    fn onboarding_window_starts_at_first_transfer() {
+        let mut config = HookConfig::default();
+        let user = UserState {
+            first_transfer_timestamp: 1_000,
+            ..UserState::default()
+        };
+        assert!(!in_onboarding_window(&config, &user, 1_000));
+
+        config.onboarding_free_seconds = 3_600;
+        assert!(in_onboarding_window(&config, &user, 1_000));
+        assert!(in_onboarding_window(&config, &user, 4_599));
+        assert!(!in_onboarding_window(&config, &user, 4_600));
+    }
+
+    #[test]
+    fn max_balance_allows_reaching_the_cap() {
+        let mut config = HookConfig::default();
+        assert!(!exceeds_max_balance(&config, u64::MAX));
 
-// ============================================================================
-// State Accounts
-// ============================================================================
+        config.max_account_balance = 1_000_000;
+        assert!(!exceeds_max_balance(&config, 0));
+        assert!(!exceeds_max_balance(&config, 999_999));
+        assert!(!exceeds_max_balance(&config, 1_000_000));
+        assert!(exceeds_max_balance(&config, 1_000_001));
+        assert!(exceeds_max_balance(&config, u64::MAX));
+    }
 
-#[account]
-#[derive(InitSpace)]
-pub struct HookConfig {
-    pub authority: Pubkey,
-    pub fee_collector: Pubkey,
-    pub is_paused: bool,
-    pub total_transfers: u64,
-    pub total_volume: u64,
-    pub total_fees_collected: u64,
-}
+    #[test]
+    fn sol_fee_adds_converted_fee_to_flat_fee() {
+        let mut config = HookConfig {
+            sol_fee_per_transfer: 5_000,
+            ..HookConfig::default()
+        };
+        assert_eq!(sol_fee(&config, 1_000_000_000, 9), 5_000);
 
-#[account]
-#[derive(InitSpace)]
-pub struct UserState {
-    pub owner: Pubkey,
-    pub transfer_count: u64,
-    pub total_volume: u64,
-    pub first_transfer_timestamp: i64,
-    pub last_transfer_timestamp: i64,
-    pub is_blacklisted: bool,
-}
+        // 0.01 SOL per whole token
+        config.lamports_per_token = 10_000_000;
+        assert_eq!(sol_fee(&config, 1_000_000_000, 9), 10_005_000);
+        assert_eq!(sol_fee(&config, 50, 9), 5_000);
+        assert_eq!(sol_fee(&config, 0, 9), 5_000);
 
-// ============================================================================
-// Errors
-// ============================================================================
+        config.sol_fee_per_transfer = u64::MAX;
+        assert_eq!(sol_fee(&config, 1_000_000_000, 9), u64::MAX);
+    }
 
-#[error_code]
-pub enum ErrorCode {
-    #[msg("Transfer hook is currently paused")]
-    HookPaused,
-    
-    #[msg("User is blacklisted from transfers")]
-    UserBlacklisted,
-    
-    #[msg("Invalid fee configuration")]
-    InvalidFeeConfig,
-    
-    #[msg("Arithmetic overflow")]
-    ArithmeticOverflow,
+    #[test]
+    fn extra_account_metas_only_resolve_enabled_features() {
+        let unused = ExtraAccountMeta::new_with_pubkey(&crate::ID, false, false).unwrap();
+        let mut config = HookConfig::default();
+
+        let metas = extra_account_metas(&config).unwrap();
+        assert_eq!(metas.len(), EXTRA_ACCOUNT_META_COUNT);
+        let unused_slots = metas.iter().filter(|meta| **meta == unused).count();
+
+        config.whitelist_mode = true;
+        config.strict_pairs = true;
+        config.require_memo = true;
+        config.fee_in_sol = true;
+        config.sol_fee_collector = Pubkey::new_unique();
+        config.require_reentrancy_guard = true;
+        config.min_stake = 1;
+        let metas = extra_account_metas(&config).unwrap();
+        assert_eq!(metas.len(), EXTRA_ACCOUNT_META_COUNT);
+        assert_eq!(metas.iter().filter(|meta| **meta == unused).count(), unused_slots - 6);
+        assert_eq!(
+            metas[8],
+            ExtraAccountMeta::new_with_pubkey(&config.sol_fee_collector, false, true).unwrap()
+        );
+        assert!(bool::from(metas[9].is_writable));
+
+        // Per-user and per-transfer accounts resolve from account data
+        config.linked_roots_enabled = true;
+        config.issue_receipts = true;
+        config.fee_payer_mode = FEE_PAYER_SPLIT;
+        config.fee_mint = Pubkey::new_unique();
+        config.fee_token_program = spl_token::ID;
+        let metas = extra_account_metas(&config).unwrap();
+        assert_eq!(metas.len(), EXTRA_ACCOUNT_META_COUNT);
+        // Only `program`, appended by `#[event_cpi]`, is the program id itself
+        assert_eq!(metas.iter().filter(|meta| **meta == unused).count(), 1);
+        assert!(bool::from(metas[4].is_writable));
+        assert_eq!(metas[6], ExtraAccountMeta::new_with_pubkey(&associated_token::ID, false, false).unwrap());
+    }
+
+    #[test]
+    fn user_state_seed_offsets_match_layout() {
+        let state = UserState {
+            receipt_nonce: 0x0102_0304_0506_0708,
+            linked_root: Pubkey::new_unique(),
+            ..UserState::default()
+        };
+        let mut data = Vec::new();
+        state.try_serialize(&mut data).unwrap();
+
+        let nonce_at = USER_STATE_RECEIPT_NONCE_OFFSET as usize;
+        assert_eq!(data[nonce_at..nonce_at + 8], state.receipt_nonce.to_le_bytes());
+        let root_at = USER_STATE_LINKED_ROOT_OFFSET as usize;
+        assert_eq!(data[root_at..root_at + 32], state.linked_root.to_bytes());
+    }
+
+
+    #[test]
+    fn aggregation_window_accumulates_until_rollover() {
+        let mut config = HookConfig::default();
+        let mut user = UserState::default();
+
+        // Off: each transfer prices alone and nothing is tracked
+        assert_eq!(aggregate_priced_amount(&config, &mut user, 500, 1_000), 500);
+        assert_eq!(user.aggregation_window_volume, 0);
+
+        config.aggregation_seconds = 60;
+        assert_eq!(aggregate_priced_amount(&config, &mut user, 500, 1_000), 500);
+        assert_eq!(aggregate_priced_amount(&config, &mut user, 700, 1_030), 1_200);
+        assert_eq!(aggregate_priced_amount(&config, &mut user, 300, 1_059), 1_500);
+
+        // The window does not slide: it restarts at the first transfer after it
+        assert_eq!(aggregate_priced_amount(&config, &mut user, 200, 1_060), 200);
+        assert_eq!(user.aggregation_window_start, 1_060);
+        assert_eq!(aggregate_priced_amount(&config, &mut user, u64::MAX, 1_061), u64::MAX);
+    }
+
+    #[test]
+    fn locked_schedule_only_accepts_decreases_when_allowed() {
+        let current = default_schedule();
+        assert!(schedule_is_decrease(&current, &current));
+
+        let mut cheaper = current.clone();
+        cheaper.fee_tier_bps[0] -= 1;
+        cheaper.loyalty_tier_discount_bps[0] += 1;
+        cheaper.max_fee = 1_000;
+        assert!(schedule_is_decrease(&current, &cheaper));
+
+        let mut pricier = current.clone();
+        pricier.fee_tier_bps[3] += 1;
+        assert!(!schedule_is_decrease(&current, &pricier));
+
+        let mut moved = current.clone();
+        moved.fee_tier_thresholds[0] += 1;
+        assert!(!schedule_is_decrease(&current, &moved));
+
+        let mut uncapped = cheaper.clone();
+        uncapped.max_fee = 0;
+        assert!(!schedule_is_decrease(&cheaper, &uncapped));
+
+        let mut config = HookConfig::default();
+        assert!(require_fee_change_allowed(&config, false).is_ok());
+        config.fee_schedule_locked = true;
+        assert!(require_fee_change_allowed(&config, true).is_err());
+        config.allow_decrease_when_locked = true;
+        assert!(require_fee_change_allowed(&config, true).is_ok());
+        assert!(require_fee_change_allowed(&config, false).is_err());
+    }
+
+    #[test]
+    fn average_fee_is_volume_weighted() {
+        assert_eq!(average_fee_bps(0, 0), 0);
+
+        // 1% on 1_000 and 0.5% on 3_000: (10 + 15) / 4_000 = 62.5 bps
+        let cumulative = effective_bps_weight(10) + effective_bps_weight(15);
+        assert_eq!(average_fee_bps(cumulative, 4_000), 62_500_000);
+
+        // Fees too small to show as a whole bp still count
+        assert_eq!(average_fee_bps(effective_bps_weight(1), 1_000_000), 10_000);
+        assert_eq!(average_fee_bps(u128::MAX, 1), u64::MAX);
+    }
+
+    #[test]
+    fn defi_owner_list() {
+        let (pool, vault) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let mut config = HookConfig::default();
+        assert!(!is_defi_owner(&config, &Pubkey::default()));
+
+        list_defi_owner(&mut config, pool).unwrap();
+        list_defi_owner(&mut config, vault).unwrap();
+        assert!(list_defi_owner(&mut config, pool).is_err());
+        assert!(is_defi_owner(&config, &pool) && is_defi_owner(&config, &vault));
+
+        unlist_defi_owner(&mut config, pool).unwrap();
+        assert!(!is_defi_owner(&config, &pool));
+        assert_eq!(config.defi_owners[0], vault);
+        assert!(unlist_defi_owner(&mut config, pool).is_err());
+
+        for _ in 1..MAX_DEFI_OWNERS {
+            list_defi_owner(&mut config, Pubkey::new_unique()).unwrap();
+        }
+        assert!(list_defi_owner(&mut config, Pubkey::new_unique()).is_err());
+
+        config.fee_tier_bps = [100, 50, 25, 10];
+        config.tier_fees_enabled = [true; FEE_TIER_COUNT];
+        config.defi_fee_bps = 5;
+        assert!(defi_fee_is_decrease(&config, true));
+        assert!(!defi_fee_is_decrease(&config, false));
+    }
+
+    #[test]
+    fn tier_up_fires_once_per_crossing() {
+        let config = default_config();
+        let mut user = UserState {
+            transfer_count: LOYALTY_SILVER - 1,
+            ..Default::default()
+        };
+        let previous = get_loyalty_tier(&config, loyalty_count(&user));
+        assert_eq!(detect_tier_up(&config, &mut user, previous), None);
+
+        user.transfer_count += 1;
+        assert_eq!(detect_tier_up(&config, &mut user, previous), Some(2));
+
+        // Decay back to None and climb to Silver again: no second bonus
+        user.loyalty_count_base = user.transfer_count;
+        user.transfer_count += LOYALTY_SILVER;
+        assert_eq!(detect_tier_up(&config, &mut user, 0), None);
+        assert_eq!(user.highest_tier_reached, 2);
+    }
+
+    #[test]
+    fn labels_are_zero_padded_ascii() {
+        let mut label = [0u8; LABEL_LEN];
+        assert!(is_valid_label(&label));
+        assert_eq!(label_str(&label), "");
+
+        label[..7].copy_from_slice(b"USDV-01");
+        assert!(is_valid_label(&label));
+        assert_eq!(label_str(&label), "USDV-01");
+        assert!(is_valid_label(&[b'A'; LABEL_LEN]));
+
+        label[9] = b'X';
+        assert!(!is_valid_label(&label));
+        label[9] = 0;
+        label[0] = 0xC3;
+        assert!(!is_valid_label(&label));
+        label[0] = b'\n';
+        assert!(!is_valid_label(&label));
+    }
+
+    #[test]
+    fn user_state_export_layout() {
+        let mint = Pubkey::new_unique();
+        let state = UserState {
+            owner: Pubkey::new_unique(),
+            transfer_count: 12,
+            total_volume: 3_400,
+            total_fees_paid: 56,
+            is_whitelisted: true,
+            first_transfer_timestamp: 1_000,
+            last_transfer_timestamp: 2_000,
+            ..Default::default()
+        };
+        let blob = encode_user_state_export(&mint, &state, 1, 3_000);
+
+        assert_eq!(blob[0], USER_STATE_EXPORT_VERSION);
+        assert_eq!(&blob[1..33], mint.as_ref());
+        assert_eq!(&blob[33..65], state.owner.as_ref());
+        assert_eq!(u64::from_le_bytes(blob[65..73].try_into().unwrap()), 12);
+        assert_eq!(u64::from_le_bytes(blob[73..81].try_into().unwrap()), 3_400);
+        assert_eq!(u64::from_le_bytes(blob[81..89].try_into().unwrap()), 56);
+        assert_eq!(blob[89], 1);
+        assert_eq!(blob[90], EXPORT_FLAG_WHITELISTED);
+        assert_eq!(i64::from_le_bytes(blob[91..99].try_into().unwrap()), 1_000);
+        assert_eq!(i64::from_le_bytes(blob[99..107].try_into().unwrap()), 2_000);
+        assert_eq!(i64::from_le_bytes(blob[107..115].try_into().unwrap()), 3_000);
+    }
+
+    #[test]
+    fn program_authority_is_a_pda_of_the_controller() {
+        let dao = Pubkey::new_unique();
+        let (pda, bump) = Pubkey::find_program_address(&[b"dao-authority"], &dao);
+        let seeds = vec![b"dao-authority".to_vec(), vec![bump]];
+        assert_eq!(program_authority_address(&dao, &seeds).unwrap(), pda);
+
+        // Wrong program, or this program itself
+        assert_ne!(program_authority_address(&Pubkey::new_unique(), &seeds).ok(), Some(pda));
+        let (_, own_bump) = Pubkey::find_program_address(&[b"dao-authority"], &crate::ID);
+        assert!(program_authority_address(&crate::ID, &[b"dao-authority".to_vec(), vec![own_bump]]).is_err());
+    }
+
+    #[test]
+    fn slot_spacing() {
+        let mut config = HookConfig::default();
+        let mut user = UserState::default();
+        assert!(!violates_slot_spacing(&config, &user, 100));
+
+        config.min_slot_spacing = 3;
+        assert!(!violates_slot_spacing(&config, &user, 100));
+
+        user.last_transfer_slot = 100;
+        assert!(violates_slot_spacing(&config, &user, 100));
+        assert!(violates_slot_spacing(&config, &user, 102));
+        assert!(!violates_slot_spacing(&config, &user, 103));
+    }
+
+    #[test]
+    fn releasing_escrow_routes_fee_shares_once() {
+        let mut config = HookConfig {
+            staking_bps: 1_000,
+            total_fees_escrowed: 500,
+            ..HookConfig::default()
+        };
+        let mut user = UserState {
+            escrowed_fee: 500,
+            escrow_expires_at: 2_000,
+            ..Default::default()
+        };
+
+        release_escrow(&mut config, &mut user).unwrap();
+        assert_eq!(config.pending_staking_rewards, 50);
+        assert_eq!(config.total_fees_escrowed, 0);
+        assert_eq!((user.escrowed_fee, user.escrow_expires_at), (0, 0));
+
+        release_escrow(&mut config, &mut user).unwrap();
+        assert_eq!(config.pending_staking_rewards, 50);
+    }
+
+    #[test]
+    fn supply_adjustment_is_bounded() {
+        let mut config = HookConfig::default();
+        observe_supply(&mut config, 1_000_000, 100);
+        assert_eq!(supply_adjustment_bps(&config, 2_000_000), 0);
+
+        config.supply_sensitivity_bps = 1_000;
+        config.max_supply_adjustment_bps = 50;
+        config.supply_window_seconds = 3_600;
+        observe_supply(&mut config, 1_000_000, 100);
+
+        // +2% supply at 10% sensitivity: +20 bps; -2%: -20 bps
+        assert_eq!(supply_adjustment_bps(&config, 1_020_000), 20);
+        assert_eq!(supply_adjustment_bps(&config, 980_000), -20);
+        // Doubling is capped
+        assert_eq!(supply_adjustment_bps(&config, 2_000_000), 50);
+
+        // The reference holds within the window, then moves on
+        observe_supply(&mut config, 2_000_000, 3_699);
+        assert_eq!(config.last_observed_supply, 1_000_000);
+        observe_supply(&mut config, 2_000_000, 3_700);
+        assert_eq!(supply_adjustment_bps(&config, 2_000_000), 0);
+
+        assert_eq!(apply_supply_adjustment(10, -50), 0);
+        assert_eq!(apply_supply_adjustment(9_990, 50), 10000);
+        assert_eq!(apply_supply_adjustment(100, 20), 120);
+    }
+
+    #[test]
+    fn min_stake_gate() {
+        let owner = Pubkey::new_unique();
+        let mut config = HookConfig::default();
+        assert!(meets_min_stake(&config, None, &owner));
+
+        config.min_stake = 100;
+        let stake = StakeAccount { owner, amount: 100, reward_debt: 0, pending_rewards: 0 };
+        assert!(meets_min_stake(&config, Some(&stake), &owner));
+        assert!(!meets_min_stake(&config, None, &owner));
+        assert!(!meets_min_stake(&config, Some(&stake), &Pubkey::new_unique()));
+
+        let short = StakeAccount { amount: 99, ..stake };
+        assert!(!meets_min_stake(&config, Some(&short), &owner));
+    }
+
+    #[test]
+    fn audit_sampling_rate() {
+        let owner = Pubkey::new_unique();
+        assert!(!(0..100).any(|slot| is_audit_sample(0, slot, 0, &owner, 1)));
+        assert!((0..100).all(|slot| is_audit_sample(1, slot, 0, &owner, 1)));
+
+        // Deterministic, and roughly 1 in 10
+        assert_eq!(is_audit_sample(10, 7, 1, &owner, 5), is_audit_sample(10, 7, 1, &owner, 5));
+        let flagged = (0..10_000).filter(|&slot| is_audit_sample(10, slot, 0, &owner, 1)).count();
+        assert!((800..1_200).contains(&flagged));
+    }
+
+    #[test]
+    fn rebate_expiry_is_rolling() {
+        let (mint, user) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let mut config = HookConfig::default();
+        let mut state = UserState::default();
+
+        // Without a validity credit never expires
+        credit_rebate(&config, &mut state, mint, user, 100, 1_000);
+        assert_eq!((state.rebate_balance, state.rebate_expires_at), (100, 0));
+        assert_eq!(forfeit_expired_rebate(&mut state, mint, user, i64::MAX), 0);
+
+        config.rebate_validity_seconds = 60;
+        credit_rebate(&config, &mut state, mint, user, 50, 1_000);
+        assert_eq!((state.rebate_balance, state.rebate_expires_at), (150, 1_060));
+        credit_rebate(&config, &mut state, mint, user, 10, 1_050);
+        assert_eq!((state.rebate_balance, state.rebate_expires_at), (160, 1_110));
+
+        assert_eq!(forfeit_expired_rebate(&mut state, mint, user, 1_109), 0);
+        credit_rebate(&config, &mut state, mint, user, 5, 1_110);
+        assert_eq!((state.rebate_balance, state.rebate_expires_at), (5, 1_170));
+    }
+
+    #[test]
+    fn schedule_hash_tracks_fee_fields() {
+        let mint = Pubkey::new_unique();
+        let mut config = default_config();
+        refresh_schedule_hash(&mut config, mint).unwrap();
+        let initial = config.schedule_hash;
+        assert_ne!(initial, [0; 32]);
+
+        // Non-fee fields leave it alone
+        config.cooldown_seconds = 30;
+        config.rebate_validity_seconds = 60;
+        assert_eq!(effective_schedule_hash(&config).unwrap(), initial);
+
+        // Schedule and off-schedule fee fields both move it
+        config.max_fee = 1_000;
+        let capped = effective_schedule_hash(&config).unwrap();
+        assert_ne!(capped, initial);
+        config.defi_fee_bps = 5;
+        refresh_schedule_hash(&mut config, mint).unwrap();
+        assert_ne!(config.schedule_hash, capped);
+
+        config.max_fee = 0;
+        config.defi_fee_bps = 0;
+        refresh_schedule_hash(&mut config, mint).unwrap();
+        assert_eq!(config.schedule_hash, initial);
+    }
+
+    #[test]
+    fn total_discount_is_clamped() {
+        let mut config = HookConfig::default();
+        assert_eq!(clamp_total_discount(&config, 50, 30), 30);
+        assert_eq!(clamp_total_discount(&config, 50, 80), 50);
+
+        config.max_total_discount_bps = 20;
+        assert_eq!(clamp_total_discount(&config, 50, 30), 20);
+        assert_eq!(clamp_total_discount(&config, 10, 30), 10);
+
+        let fee = compute_final_fee(TIER_3_THRESHOLD, 50, clamp_total_discount(&config, 50, 45)).unwrap();
+        assert_eq!(fee.discount, bps_to_fee(TIER_3_THRESHOLD, 20).unwrap());
+    }
+
+    #[test]
+    fn circuit_breaker_trips_on_spike() {
+        let mint = Pubkey::new_unique();
+        let mut config = HookConfig::default();
+        track_volume_spike(&mut config, mint, u64::MAX, 0);
+        assert!(!config.is_paused);
+
+        config.spike_threshold = 1_000;
+        config.spike_window_seconds = 60;
+        track_volume_spike(&mut config, mint, 600, 100);
+        track_volume_spike(&mut config, mint, 400, 130);
+        assert!(!config.is_paused);
+
+        // A new window starts from zero
+        track_volume_spike(&mut config, mint, 600, 160);
+        assert_eq!(config.spike_window_volume, 600);
+        track_volume_spike(&mut config, mint, 401, 170);
+        assert!(config.is_paused && config.auto_paused);
+        assert_eq!(config.auto_unpause_at, 0);
+    }
+
+    #[test]
+    fn admin_actions_follow_acknowledged_deployment() {
+        let mut config = HookConfig::default();
+        let deployed = |slot| ProgramData { slot, upgrade_authority_address: None };
+        assert!(upgrade_acknowledged(&config, None));
+
+        config.acknowledged_deploy_slot = 100;
+        assert!(upgrade_acknowledged(&config, Some(&deployed(100))));
+        assert!(!upgrade_acknowledged(&config, Some(&deployed(250))));
+        assert!(!upgrade_acknowledged(&config, None));
+    }
+
+    #[test]
+    fn dust_consolidation_needs_small_emptying_transfer() {
+        let mut config = default_config();
+        assert!(!is_dust_consolidation(&config, 10, 0));
+
+        config.dust_consolidation_discount_bps = 25;
+        assert!(is_dust_consolidation(&config, TIER_1_THRESHOLD - 1, 0));
+        assert!(!is_dust_consolidation(&config, TIER_1_THRESHOLD, 0));
+        assert!(!is_dust_consolidation(&config, 10, 1));
+        assert!(!is_dust_consolidation(&config, 0, 0));
+    }
+
+    #[test]
+    fn metas_version_tracks_resolved_accounts() {
+        let mut config = HookConfig::default();
+        config.min_stake = 10;
+        assert!(!metas_out_of_sync(&config));
+        config.metas_version = metas_version(&config);
+
+        // Settings the list does not depend on leave it in sync
+        config.min_stake = 20;
+        config.sol_fee_collector = Pubkey::new_unique();
+        assert!(!metas_out_of_sync(&config));
+
+        config.fee_in_sol = true;
+        assert!(metas_out_of_sync(&config));
+        config.fee_in_sol = false;
+        config.min_stake = 0;
+        assert!(metas_out_of_sync(&config));
+        config.min_stake = 20;
+        assert!(!metas_out_of_sync(&config));
+
+        // The fee token's accounts are derived from its mint and program
+        config.fee_mint = Pubkey::new_unique();
+        assert!(metas_out_of_sync(&config));
+    }
+
+    #[test]
+    fn milestones_fire_once_per_crossing() {
+        let thresholds = [100, 1_000, 10_000, 0];
+        let crossed = |before, after| crossed_milestones(&thresholds, before, after).collect::<Vec<_>>();
+
+        assert_eq!(crossed(99, 100), vec![100]);
+        assert!(crossed(100, 101).is_empty());
+        assert_eq!(crossed(50, 20_000), vec![100, 1_000, 10_000]);
+        assert!(crossed(0, 0).is_empty());
+    }
+
+    #[test]
+    fn points_conversion_keeps_remainder_unclaimed() {
+        assert_eq!(points_for_volume(0, 1_000_000).unwrap(), (0, 0));
+        assert_eq!(points_for_volume(3, 1_000_000).unwrap(), (3, 1_000_000));
+
+        // 3 points per million: 999_999 earns 2, using 666_667 of it
+        let (points, converted) = points_for_volume(3, 999_999).unwrap();
+        assert_eq!((points, converted), (2, 666_667));
+        assert_eq!(points_for_volume(3, 999_999 - converted).unwrap().0, 0);
+
+        assert!(points_for_volume(u64::MAX, u64::MAX).is_err());
+        let (points, converted) = points_for_volume(POINTS_RATE_SCALE, u64::MAX).unwrap();
+        assert_eq!((points, converted), (u64::MAX, u64::MAX));
+    }
+
+    #[test]
+    fn launch_ramp_interpolates_to_tier_fee() {
+        let mut config = HookConfig::default();
+        assert_eq!(launch_ramp_bps(&config, 30, 0), 30);
+
+        config.launch_timestamp = 1_000;
+        config.launch_fee_bps = 1_000;
+        config.ramp_seconds = 100;
+        assert_eq!(launch_ramp_bps(&config, 30, 500), 1_000);
+        assert_eq!(launch_ramp_bps(&config, 30, 1_000), 1_000);
+        assert_eq!(launch_ramp_bps(&config, 30, 1_050), 515);
+        assert_eq!(launch_ramp_bps(&config, 30, 1_099), 40);
+        assert_eq!(launch_ramp_bps(&config, 30, 1_100), 30);
+    }
+
+    #[test]
+    fn zero_supply_skips_supply_adjustment() {
+        let mut config = HookConfig::default();
+        config.supply_sensitivity_bps = 1_000;
+        config.max_supply_adjustment_bps = 50;
+        config.supply_window_seconds = 3_600;
+
+        // Before the first mint: no reference, no adjustment
+        observe_supply(&mut config, 0, 100);
+        assert_eq!((config.last_observed_supply, config.supply_observed_at), (0, 0));
+        assert_eq!(supply_adjustment_bps(&config, 0), 0);
+        assert_eq!(supply_adjustment_bps(&config, 1_000_000), 0);
+
+        // The first nonzero supply becomes the reference right away
+        observe_supply(&mut config, 1_000_000, 200);
+        assert_eq!(config.last_observed_supply, 1_000_000);
+
+        // Everything burned: skipped rather than a -100% change
+        observe_supply(&mut config, 0, 10_000);
+        assert_eq!(config.last_observed_supply, 1_000_000);
+        assert_eq!(supply_adjustment_bps(&config, 0), 0);
+    }
+
+    #[test]
+    fn user_max_fee_only_tightens_global_cap() {
+        assert_eq!(effective_max_fee(0, 0), 0);
+        assert_eq!(effective_max_fee(500, 0), 500);
+        assert_eq!(effective_max_fee(0, 200), 200);
+        assert_eq!(effective_max_fee(500, 200), 200);
+        assert_eq!(effective_max_fee(500, 900), 500);
+
+        let config = default_config();
+        let fee = compute_final_fee(TIER_3_THRESHOLD, 100, 0).unwrap();
+        let capped = finalize_fee(&config, fee, TIER_3_THRESHOLD, effective_max_fee(0, 1_000));
+        assert_eq!(capped.final_fee, 1_000);
+    }
+
+    #[test]
+    fn hourly_stats_roll_over_on_new_hour() {
+        let mint = Pubkey::new_unique();
+        let mut config = HookConfig::default();
+        record_hourly_stats(&mut config, mint, 100, 1, 7_200);
+        assert_eq!(config.hour_transfers, 0);
+
+        config.hourly_stats = true;
+        record_hourly_stats(&mut config, mint, 100, 1, 7_200);
+        record_hourly_stats(&mut config, mint, 50, 2, 10_799);
+        assert_eq!((config.current_hour, config.hour_transfers, config.hour_volume, config.hour_fees), (2, 2, 150, 3));
+
+        // A multi-hour gap closes the last bucket and starts afresh
+        record_hourly_stats(&mut config, mint, 10, 0, 30_000);
+        assert_eq!((config.current_hour, config.hour_transfers, config.hour_volume, config.hour_fees), (8, 1, 10, 0));
+    }
+
+    #[test]
+    fn balance_component_is_capped() {
+        let mut config = HookConfig::default();
+        config.balance_per_bp = 1_000;
+        config.max_balance_fee_bps = 20;
+        assert_eq!(balance_component_bps(&config, 1_000_000), 0);
+
+        config.balance_fee_enabled = true;
+        assert_eq!(balance_component_bps(&config, 999), 0);
+        assert_eq!(balance_component_bps(&config, 15_500), 15);
+        assert_eq!(balance_component_bps(&config, u64::MAX), 20);
+    }
+
+    #[test]
+    fn authorized_minter_list() {
+        let (airdrop, treasury) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let mut config = HookConfig::default();
+        assert!(!is_authorized_minter(&config, &Pubkey::default()));
+
+        list_authorized_minter(&mut config, airdrop).unwrap();
+        list_authorized_minter(&mut config, treasury).unwrap();
+        assert!(list_authorized_minter(&mut config, airdrop).is_err());
+        assert!(is_authorized_minter(&config, &airdrop));
+
+        unlist_authorized_minter(&mut config, airdrop).unwrap();
+        assert!(!is_authorized_minter(&config, &airdrop));
+        assert_eq!(config.authorized_minters[0], treasury);
+        assert!(unlist_authorized_minter(&mut config, airdrop).is_err());
+
+        for _ in 1..MAX_AUTHORIZED_MINTERS {
+            list_authorized_minter(&mut config, Pubkey::new_unique()).unwrap();
+        }
+        assert!(list_authorized_minter(&mut config, Pubkey::new_unique()).is_err());
+    }
+
+    #[test]
+    fn missing_clock_is_skipped_unless_required() {
+        let mut config = HookConfig::default();
+        let clock = Clock { slot: 7, unix_timestamp: 1_000, ..Clock::default() };
+        assert_eq!(resolve_clock(&config, Ok(clock.clone())).unwrap().unwrap().slot, 7);
+        assert!(resolve_clock(&config, Err(ProgramError::UnsupportedSysvar)).unwrap().is_none());
+
+        config.require_clock = true;
+        assert!(resolve_clock(&config, Err(ProgramError::UnsupportedSysvar)).is_err());
+        assert!(resolve_clock(&config, Ok(clock)).unwrap().is_some());
+    }
+
+    #[test]
+    fn rebate_covers_fees() {
+        let mut state = UserState { rebate_balance: 30, ..UserState::default() };
+        assert_eq!(apply_rebate(&mut state, 20, true), 20);
+        assert_eq!(state.rebate_balance, 10);
+        // Partial coverage: the remaining 15 is charged in tokens
+        assert_eq!(apply_rebate(&mut state, 25, true), 10);
+        assert_eq!(apply_rebate(&mut state, 25, true), 0);
+
+        state.rebate_balance = 50;
+        state.rebate_expires_at = 1_000;
+        assert_eq!(apply_rebate(&mut state, 20, false), 0);
+        assert_eq!(apply_rebate(&mut state, 20, true), 20);
+    }
+
+    #[test]
+    fn counterparties_cap_loyalty_tier() {
+        let mut config = HookConfig::default();
+        config.loyalty_tier_count = 3;
+        config.loyalty_tier_thresholds[..3].copy_from_slice(&[10, 50, 100]);
+        let mut user = UserState { transfer_count: 120, ..UserState::default() };
+        assert_eq!(user_loyalty_tier(&config, &user), 3);
+
+        config.min_counterparties_per_tier[1] = 3;
+        config.min_counterparties_per_tier[2] = 10;
+        assert_eq!(user_loyalty_tier(&config, &user), 1);
+        user.distinct_dest_estimate = 3;
+        assert_eq!(user_loyalty_tier(&config, &user), 2);
+        user.distinct_dest_estimate = 10;
+        assert_eq!(user_loyalty_tier(&config, &user), 3);
+
+        // The cap never lifts a tier the count has not earned
+        user.transfer_count = 20;
+        assert_eq!(user_loyalty_tier(&config, &user), 1);
+    }
+
+    #[test]
+    fn declared_event_window() {
+        let mut config = HookConfig::default();
+        config.fee_tier_bps = [100, 50, 25, 10];
+        config.tier_fees_enabled = [true; FEE_TIER_COUNT];
+        assert!(!is_event_running(&config.active_event, 0));
+
+        let mut name = [0; LABEL_LEN];
+        name[..4].copy_from_slice(b"fest");
+        let event = ActiveEvent { name, starts_at: 100, ends_at: 200, fee_bps: 5, discount_bps: 0 };
+        apply_config_change(&mut config, &ConfigChange::DeclareEvent(event)).unwrap();
+        assert!(!is_event_running(&config.active_event, 99));
+        assert!(is_event_running(&config.active_event, 100));
+        assert!(!is_event_running(&config.active_event, 200));
+
+        let backwards = ActiveEvent { starts_at: 200, ends_at: 100, ..event };
+        assert!(!dry_run_config_change(&config, &ConfigChange::DeclareEvent(backwards)).valid);
+        apply_config_change(&mut config, &ConfigChange::DeclareEvent(ActiveEvent::default())).unwrap();
+        assert!(!is_event_running(&config.active_event, 150));
+    }
+
+    #[test]
+    fn window_destinations_reset_with_window() {
+        let mut user = UserState::default();
+        let alice = Pubkey::new_from_array([1; 32]);
+        let bob = Pubkey::new_from_array([200; 32]);
+        for dest in [alice, bob, alice] {
+            if record_counterparty(&mut user.window_dest_filter, &dest) {
+                user.window_dest_count += 1;
+            }
+        }
+        assert_eq!(user.window_dest_count, 2);
+
+        roll_daily_window(&mut user, DAILY_WINDOW_SECONDS);
+        assert_eq!(user.window_dest_count, 0);
+        assert!(record_counterparty(&mut user.window_dest_filter, &alice));
+    }
+
+    #[test]
+    fn paired_transfers_match_accounts_and_amount() {
+        let (mint, source, destination, authority) =
+            (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let ix = spl_token_2022::instruction::transfer_checked(
+            &spl_token_2022::ID,
+            &source,
+            &mint,
+            &destination,
+            &authority,
+            &[],
+            1_500,
+            9,
+        )
+        .unwrap();
+        assert_eq!(transfer_checked_amount(&ix, &mint, &source, &destination), Some(1_500));
+        assert_eq!(transfer_checked_amount(&ix, &mint, &destination, &source), None);
+        assert_eq!(transfer_checked_amount(&ix, &Pubkey::new_unique(), &source, &destination), None);
+
+        let classic = Instruction { program_id: spl_token::ID, ..ix.clone() };
+        assert_eq!(transfer_checked_amount(&classic, &mint, &source, &destination), None);
+        let approve = spl_token_2022::instruction::approve(
+            &spl_token_2022::ID,
+            &source,
+            &mint,
+            &authority,
+            &[],
+            1_500,
+        )
+        .unwrap();
+        assert_eq!(transfer_checked_amount(&approve, &mint, &source, &destination), None);
+    }
+
+    #[test]
+    fn rewards_accrue_only_when_funded_and_only_to_claimable_volume() {
+        let mut config = HookConfig { rewards_bps: 1_000, ..HookConfig::default() };
+        let mut alice = UserState::default();
+
+        alice.total_volume += 1_000;
+        add_reward_volume(&mut config, &mut alice, 1_000).unwrap();
+        // Opted-out or minter volume reaches the global totals only
+        config.total_volume = 1_000_000;
+
+        route_fee_shares(&mut config, 500).unwrap();
+        assert_eq!(config.unfunded_rewards, 50);
+        assert_eq!(config.reward_per_volume, 0);
+
+        accrue_rewards(&mut config, 50).unwrap();
+        settle_rewards(&config, &mut alice).unwrap();
+        assert_eq!(alice.reward_share, 50);
+    }
+
+    #[test]
+    fn proposals_need_majority_and_quorum() {
+        assert!(proposal_passes(60, 40, 100));
+        assert!(!proposal_passes(60, 39, 100));
+        assert!(!proposal_passes(50, 50, 0));
+        assert!(proposal_passes(1, 0, 0));
+        assert!(proposal_passes(u64::MAX, 1, u64::MAX));
+    }
+
+    #[test]
+    fn locked_schedule_gates_fee_waivers() {
+        let mut config = HookConfig {
+            free_allowance_per_period: 1_000,
+            onboarding_free_seconds: 3_600,
+            fee_schedule_locked: true,
+            allow_decrease_when_locked: true,
+            ..HookConfig::default()
+        };
+
+        assert!(free_allowance_is_decrease(&config, 1_000));
+        assert!(free_allowance_is_decrease(&config, 2_000));
+        assert!(!free_allowance_is_decrease(&config, 999));
+        assert!(require_fee_change_allowed(&config, free_allowance_is_decrease(&config, 999)).is_err());
+
+        assert!(onboarding_window_is_decrease(&config, 7_200));
+        assert!(!onboarding_window_is_decrease(&config, 0));
+        assert!(require_fee_change_allowed(&config, onboarding_window_is_decrease(&config, 0)).is_err());
+
+        assert!(mint_authority_exemption_is_decrease(&config, true));
+        assert!(mint_authority_exemption_is_decrease(&config, false));
+        config.exempt_mint_authority = true;
+        assert!(mint_authority_exemption_is_decrease(&config, true));
+        assert!(!mint_authority_exemption_is_decrease(&config, false));
+        assert!(require_fee_change_allowed(&config, mint_authority_exemption_is_decrease(&config, false)).is_err());
+    }
+
+    #[test]
+    fn locked_schedule_gates_discount_settings() {
+        let mut config = HookConfig {
+            fee_schedule_locked: true,
+            allow_decrease_when_locked: true,
+            ..HookConfig::default()
+        };
+
+        // Loyalty decay: off stays off; turning it on raises fees
+        assert!(loyalty_decay_is_decrease(&config, 0, 5));
+        assert!(!loyalty_decay_is_decrease(&config, 86_400, 0));
+        config.loyalty_decay_seconds = 86_400;
+        config.decay_grace_transfers = 2;
+        assert!(loyalty_decay_is_decrease(&config, 0, 0));
+        assert!(loyalty_decay_is_decrease(&config, 172_800, 3));
+        assert!(!loyalty_decay_is_decrease(&config, 3_600, 2));
+        assert!(!loyalty_decay_is_decrease(&config, 86_400, 1));
+        assert!(require_fee_change_allowed(&config, loyalty_decay_is_decrease(&config, 3_600, 2)).is_err());
+
+        // Continuous discount: toggling is never a decrease
+        assert!(continuous_discount_is_decrease(&config, false, 0, 0));
+        assert!(!continuous_discount_is_decrease(&config, true, 1_000, 500));
+        config.continuous_discount = true;
+        config.volume_per_bp = 1_000;
+        config.max_continuous_discount_bps = 500;
+        assert!(continuous_discount_is_decrease(&config, true, 500, 600));
+        assert!(!continuous_discount_is_decrease(&config, true, 2_000, 500));
+        assert!(!continuous_discount_is_decrease(&config, true, 1_000, 400));
+        assert!(!continuous_discount_is_decrease(&config, false, 0, 0));
+        assert!(require_fee_change_allowed(&config, continuous_discount_is_decrease(&config, false, 0, 0)).is_err());
+
+        // Holding discount: stacking may be added, replace mode never touched
+        assert!(holding_discount_is_decrease(&config, HOLDING_DISCOUNT_STACK, 10, 100));
+        assert!(!holding_discount_is_decrease(&config, HOLDING_DISCOUNT_REPLACE, 10, 100));
+        config.holding_discount_mode = HOLDING_DISCOUNT_STACK;
+        config.holding_discount_per_day_bps = 10;
+        config.max_holding_discount_bps = 100;
+        assert!(holding_discount_is_decrease(&config, HOLDING_DISCOUNT_STACK, 20, 100));
+        assert!(!holding_discount_is_decrease(&config, HOLDING_DISCOUNT_STACK, 5, 100));
+        assert!(!holding_discount_is_decrease(&config, HOLDING_DISCOUNT_OFF, 0, 0));
+        assert!(!holding_discount_is_decrease(&config, HOLDING_DISCOUNT_REPLACE, 20, 200));
+        assert!(require_fee_change_allowed(&config, holding_discount_is_decrease(&config, HOLDING_DISCOUNT_OFF, 0, 0)).is_err());
+    }
+
+    #[test]
+    fn recount_covers_untracked_and_live_transfers() {
+        let mut config = HookConfig::default();
+        let tallied = UserState { recount_session: 1, ..UserState::default() };
+        let pending = UserState::default();
+
+        record_recount_flow(&mut config, None, 100);
+        record_recount_flow(&mut config, Some((&pending, false)), 40);
+        assert_eq!((config.untracked_transfers, config.untracked_volume), (1, 140));
+
+        config.recount_active = true;
+        config.recount_session = 1;
+        record_recount_flow(&mut config, Some((&tallied, true)), 25);
+        record_recount_flow(&mut config, Some((&tallied, false)), 5);
+        record_recount_flow(&mut config, Some((&pending, true)), 70);
+        assert_eq!((config.recount_transfers, config.recount_volume), (2, 25));
+        assert_eq!((config.untracked_transfers, config.untracked_volume), (1, 145));
+    }
+
+    #[test]
+    fn payer_aggregation_and_exemption_price_locks() {
+        let mut config = HookConfig::default();
+        config.fee_payer_mode = FEE_PAYER_SPLIT;
+        config.receiver_fee_share_bps = 5000;
+        assert!(fee_payer_mode_is_decrease(&config, FEE_PAYER_SPLIT, 5000));
+        assert!(!fee_payer_mode_is_decrease(&config, FEE_PAYER_SPLIT, 4000));
+        assert!(!fee_payer_mode_is_decrease(&config, FEE_PAYER_SENDER, 0));
+
+        config.fee_tier_bps = [TIER_1_FEE_BPS, TIER_2_FEE_BPS, TIER_3_FEE_BPS, TIER_4_FEE_BPS];
+        config.tier_fees_enabled = [true; FEE_TIER_COUNT];
+        config.aggregation_seconds = 60;
+        assert!(aggregation_is_decrease(&config, 60));
+        assert!(aggregation_is_decrease(&config, 120));
+        assert!(!aggregation_is_decrease(&config, 30));
+        config.fee_tier_bps[3] = 200;
+        assert!(!aggregation_is_decrease(&config, 120));
+
+        assert!(cooldown_exemption_price_is_decrease(&config, 1_000));
+        config.cooldown_exemption_price = 1_000;
+        assert!(cooldown_exemption_price_is_decrease(&config, 500));
+        assert!(!cooldown_exemption_price_is_decrease(&config, 2_000));
+        assert!(!cooldown_exemption_price_is_decrease(&config, 0));
+
+        config.fee_schedule_locked = true;
+        config.allow_decrease_when_locked = true;
+        assert!(require_fee_change_allowed(&config, fee_payer_mode_is_decrease(&config, FEE_PAYER_SENDER, 0)).is_err());
+        assert!(require_fee_change_allowed(&config, cooldown_exemption_price_is_decrease(&config, 500)).is_ok());
+    }
+
+    #[test]
+    fn event_lock_counts_discounts_and_cancellation() {
+        let mut config = HookConfig::default();
+        config.fee_tier_bps = [100, 50, 25, 10];
+        config.tier_fees_enabled = [true; FEE_TIER_COUNT];
+        config.max_total_discount_bps = 5;
+        let event = ActiveEvent { starts_at: 100, ends_at: 200, fee_bps: 5, discount_bps: 0, ..ActiveEvent::default() };
+
+        // A discounted 10bps tier can already cost 5bps
+        assert!(event_is_decrease(&config, &event));
+        config.max_total_discount_bps = 6;
+        assert!(!event_is_decrease(&config, &event));
+        assert!(event_is_decrease(&config, &ActiveEvent { discount_bps: 2, ..event }));
+        config.tier_fees_enabled[3] = false;
+        assert!(!event_is_decrease(&config, &ActiveEvent { discount_bps: 2, ..event }));
+        config.tier_fees_enabled[3] = true;
+
+        assert!(event_cancel_is_decrease(&config));
+        config.active_event = ActiveEvent { fee_bps: 2, ..event };
+        assert!(!event_cancel_is_decrease(&config));
+        assert!(!event_is_decrease(&config, &ActiveEvent { fee_bps: 3, ..event }));
+        assert!(event_is_decrease(&config, &ActiveEvent { fee_bps: 2, ..event }));
+        config.active_event.fee_bps = 100;
+        assert!(event_cancel_is_decrease(&config));
+
+        config.fee_schedule_locked = true;
+        config.allow_decrease_when_locked = true;
+        config.active_event = event;
+        let cancel = ConfigChange::DeclareEvent(ActiveEvent::default());
+        assert!(!dry_run_config_change(&config, &cancel).valid);
+    }
+
+    #[test]
+    fn error_codes_are_stable() {
+        let codes = [
+            (ErrorCode::HookPaused, 6000),
+            (ErrorCode::UserBlacklisted, 6001),
+            (ErrorCode::InvalidFeeConfig, 6002),
+            (ErrorCode::ArithmeticOverflow, 6003),
+            (ErrorCode::NothingToClaim, 6004),
+            (ErrorCode::OwnerMismatch, 6005),
+            (ErrorCode::InvalidLoyaltyConfig, 6006),
+            (ErrorCode::AccountTooNew, 6007),
+            (ErrorCode::FeeBalanceGateFailed, 6008),
+            (ErrorCode::ReceiverFeeAccountMissing, 6009),
+            (ErrorCode::RefundExceedsFees, 6010),
+            (ErrorCode::TransferCountExceeded, 6011),
+            (ErrorCode::GovernanceDisabled, 6012),
+            (ErrorCode::VotingClosed, 6013),
+            (ErrorCode::VotingOpen, 6014),
+            (ErrorCode::ProposalAlreadyExecuted, 6015),
+            (ErrorCode::NoVotingWeight, 6016),
+            (ErrorCode::CollectorTransferBlocked, 6017),
+            (ErrorCode::CooldownActive, 6018),
+            (ErrorCode::InvalidUserState, 6019),
+            (ErrorCode::InvalidDiscountCode, 6020),
+            (ErrorCode::ReceiptAccountMissing, 6021),
+            (ErrorCode::FeeExceedsMax, 6022),
+            (ErrorCode::DuplicateFeeCollector, 6023),
+            (ErrorCode::TooManyFeeCollectors, 6024),
+            (ErrorCode::FeeCollectorNotFound, 6025),
+            (ErrorCode::MemoRequired, 6026),
+            (ErrorCode::OutgoingBlocked, 6027),
+            (ErrorCode::IncomingBlocked, 6028),
+            (ErrorCode::LoyaltyAlreadySeeded, 6029),
+            (ErrorCode::RecountNotActive, 6030),
+            (ErrorCode::NotWhitelisted, 6031),
+            (ErrorCode::ReceiverNotWhitelisted, 6032),
+            (ErrorCode::InvalidFeeTokenAccount, 6033),
+            (ErrorCode::FeeTokenBalanceGateFailed, 6034),
+            (ErrorCode::InvalidStakeAmount, 6035),
+            (ErrorCode::LinkedRootStateMissing, 6036),
+            (ErrorCode::InvalidLinkedRoot, 6037),
+            (ErrorCode::UnauthorizedLink, 6038),
+            (ErrorCode::ReentrancyDetected, 6039),
+            (ErrorCode::ReentrancyGuardMissing, 6040),
+            (ErrorCode::MaxBalanceExceeded, 6041),
+            (ErrorCode::InvalidSolFeeCollector, 6042),
+            (ErrorCode::InsufficientSolBalance, 6043),
+            (ErrorCode::SolFeeCollectorNotRentExempt, 6044),
+            (ErrorCode::FeeScheduleLocked, 6045),
+            (ErrorCode::DuplicateDefiOwner, 6046),
+            (ErrorCode::TooManyDefiOwners, 6047),
+            (ErrorCode::DefiOwnerNotFound, 6048),
+            (ErrorCode::InvalidLabel, 6049),
+            (ErrorCode::CannotInitUserState, 6050),
+            (ErrorCode::InvalidProgramAuthority, 6051),
+            (ErrorCode::SlotSpacingViolation, 6052),
+            (ErrorCode::NoEscrowedFee, 6053),
+            (ErrorCode::EscrowExpired, 6054),
+            (ErrorCode::InsufficientStake, 6055),
+            (ErrorCode::RebateNotExpired, 6056),
+            (ErrorCode::UpgradeNotAcknowledged, 6057),
+            (ErrorCode::ExtraAccountMetasStale, 6058),
+            (ErrorCode::NoPointsToClaim, 6059),
+            (ErrorCode::InsufficientPoints, 6060),
+            (ErrorCode::InvalidFeeCollectorAccount, 6061),
+            (ErrorCode::CooldownExemptionNotForSale, 6062),
+            (ErrorCode::AlreadyCooldownExempt, 6063),
+            (ErrorCode::DuplicateAuthorizedMinter, 6064),
+            (ErrorCode::TooManyAuthorizedMinters, 6065),
+            (ErrorCode::AuthorizedMinterNotFound, 6066),
+            (ErrorCode::TooManyDestinations, 6067),
+            (ErrorCode::CollectorBalanceNotSwept, 6068),
+            (ErrorCode::PairedTransferMissing, 6069),
+            (ErrorCode::RewardsVaultUnderfunded, 6070),
+            (ErrorCode::InvalidRewardsFunding, 6071),
+            (ErrorCode::SolFeeEscrowMissing, 6073),
+            (ErrorCode::StatelessConflict, 6074),
+            (ErrorCode::LinkedRootsDisabled, 6075),
+            (ErrorCode::LinkedAccountsRemain, 6076),
+            (ErrorCode::DiscountCodeUnavailable, 6077),
+            (ErrorCode::ReceiverFeeUnpaid, 6078),
+        ];
+        for (code, number) in codes {
+            assert_eq!(u32::from(code), number);
+        }
+    }
+
+    #[test]
+    fn config_dry_run_reports_first_failure() {
+        let config = default_config();
+
+        let result = dry_run_config_change(&config, &ConfigChange::Schedule(default_schedule()));
+        assert_eq!(result, ConfigValidation { valid: true, error_code: 0 });
+
+        let mut bad = default_schedule();
+        bad.loyalty_tier_thresholds[1] = LOYALTY_BRONZE;
+        let result = dry_run_config_change(&config, &ConfigChange::Schedule(bad));
+        assert_eq!(result.error_code, u32::from(ErrorCode::InvalidLoyaltyConfig));
+
+        let result = dry_run_config_change(&config, &ConfigChange::RewardsBps(10001));
+        assert_eq!(result.error_code, u32::from(ErrorCode::InvalidFeeConfig));
+        assert!(!result.valid);
+    }
+
+    #[test]
+    fn fee_pipeline_rejects_overflowing_bps() {
+        assert!(compute_final_fee(u64::MAX, u16::MAX, 0).is_err());
+        assert!(compute_final_fee(u64::MAX, 0, u16::MAX).is_err());
+    }
 }
@@ -239,7 +239,7 @@ async function main() {
   // Demo 5: Pause
   console.log("\n📊 Demo 5: Pausing hook...");
   await program.methods
-    .setPause(true)
+    .setPause(true, new BN(0))
     .accounts({
       hookConfig,
       mint: mint.publicKey,
@@ -257,7 +257,7 @@ async function main() {
 
   // Unpause
   await program.methods
-    .setPause(false)
+    .setPause(false, new BN(0))
     .accounts({
       hookConfig,
       mint: mint.publicKey,
@@ -8,6 +8,7 @@ import {
   Transaction,
   sendAndConfirmTransaction,
   LAMPORTS_PER_SOL,
+  SYSVAR_INSTRUCTIONS_PUBKEY,
 } from "@solana/web3.js";
 import {
   ExtensionType,
@@ -146,7 +147,7 @@ describe("versa_transfer_hook", () => {
 
   it("Initializes the hook config", async () => {
     await program.methods
-      .initialize(feeCollector.publicKey)
+      .initialize(feeCollector.publicKey, Array(16).fill(0), false)
       .accounts({
         hookConfig,
         mint: mint.publicKey,
@@ -170,6 +171,53 @@ describe("versa_transfer_hook", () => {
     console.log("✅ Hook initialization test passed");
   });
 
+  it("Creates the extra account metas and user states", async () => {
+    const [extraAccountMetaList] = PublicKey.findProgramAddressSync(
+      [Buffer.from("extra-account-metas"), mint.publicKey.toBuffer()],
+      program.programId
+    );
+    await program.methods
+      .initializeExtraAccountMetaList()
+      .accounts({
+        payer: wallet.publicKey,
+        extraAccountMetaList,
+        mint: mint.publicKey,
+        hookConfig,
+        systemProgram: SystemProgram.programId,
+      })
+      .rpc();
+
+    // The hook cannot pay rent, so every sender's state exists beforehand;
+    // the wallet sponsors them
+    for (const user of [alice, bob, feeCollector]) {
+      const [userState] = PublicKey.findProgramAddressSync(
+        [
+          Buffer.from("user-state"),
+          user.publicKey.toBuffer(),
+          mint.publicKey.toBuffer(),
+        ],
+        program.programId
+      );
+      await program.methods
+        .initializeUserState()
+        .accounts({
+          hookConfig,
+          mint: mint.publicKey,
+          userState,
+          user: user.publicKey,
+          payer: wallet.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+    }
+
+    const userState = await program.account.userState.fetch(aliceUserState);
+    assert.equal(userState.owner.toString(), alice.publicKey.toString());
+    assert.equal(userState.transferCount.toNumber(), 0);
+
+    console.log("✅ Account setup test passed");
+  });
+
   it("Creates token accounts and mints tokens", async () => {
     // Create Alice's token account
     const createAliceATAIx =
@@ -352,7 +400,7 @@ describe("versa_transfer_hook", () => {
   it("Pauses and unpauses correctly", async () => {
     // Pause hook
     await program.methods
-      .setPause(true)
+      .setPause(true, new BN(0))
       .accounts({
         hookConfig,
         mint: mint.publicKey,
@@ -394,7 +442,7 @@ describe("versa_transfer_hook", () => {
 
     // Unpause
     await program.methods
-      .setPause(false)
+      .setPause(false, new BN(0))
       .accounts({
         hookConfig,
         mint: mint.publicKey,
@@ -456,4 +504,97 @@ describe("versa_transfer_hook", () => {
 
     console.log("✅ Analytics tracking test passed");
   });
+
+  it("Rotates the fee collector, sweeping a nonzero balance", async () => {
+    const newFeeCollector = Keypair.generate();
+    const oldCollectorToken = getAssociatedTokenAddressSync(
+      mint.publicKey,
+      feeCollector.publicKey,
+      false,
+      TOKEN_2022_PROGRAM_ID
+    );
+    const newCollectorToken = getAssociatedTokenAddressSync(
+      mint.publicKey,
+      newFeeCollector.publicKey,
+      false,
+      TOKEN_2022_PROGRAM_ID
+    );
+    const balance = 5 * 10 ** DECIMALS;
+
+    await sendAndConfirmTransaction(
+      connection,
+      new Transaction().add(
+        createAssociatedTokenAccountIdempotentInstruction(
+          wallet.publicKey,
+          oldCollectorToken,
+          feeCollector.publicKey,
+          mint.publicKey,
+          TOKEN_2022_PROGRAM_ID
+        ),
+        createAssociatedTokenAccountIdempotentInstruction(
+          wallet.publicKey,
+          newCollectorToken,
+          newFeeCollector.publicKey,
+          mint.publicKey,
+          TOKEN_2022_PROGRAM_ID
+        ),
+        createMintToInstruction(
+          mint.publicKey,
+          oldCollectorToken,
+          mintAuthority.publicKey,
+          balance,
+          [],
+          TOKEN_2022_PROGRAM_ID
+        )
+      ),
+      [wallet.payer, mintAuthority],
+      { commitment: "confirmed" }
+    );
+
+    // The program cannot transfer the hooked mint by CPI, so the sweep is
+    // a hooked transfer placed right before the rotation
+    const sweepIx = await createTransferCheckedWithTransferHookInstruction(
+      connection,
+      oldCollectorToken,
+      mint.publicKey,
+      newCollectorToken,
+      feeCollector.publicKey,
+      balance,
+      DECIMALS,
+      [],
+      "confirmed",
+      TOKEN_2022_PROGRAM_ID
+    );
+    const rotateIx = await program.methods
+      .rotateFeeCollector()
+      .accounts({
+        hookConfig,
+        mint: mint.publicKey,
+        authority: wallet.publicKey,
+        feeCollector: feeCollector.publicKey,
+        newFeeCollector: newFeeCollector.publicKey,
+        oldCollectorToken,
+        newCollectorToken,
+        instructionsSysvar: SYSVAR_INSTRUCTIONS_PUBKEY,
+        tokenProgram: TOKEN_2022_PROGRAM_ID,
+      })
+      .instruction();
+
+    await sendAndConfirmTransaction(
+      connection,
+      new Transaction().add(sweepIx, rotateIx),
+      [wallet.payer, feeCollector],
+      { commitment: "confirmed" }
+    );
+
+    const config = await program.account.hookConfig.fetch(hookConfig);
+    assert.equal(
+      config.feeCollector.toString(),
+      newFeeCollector.publicKey.toString()
+    );
+    const swept = await connection.getTokenAccountBalance(newCollectorToken);
+    assert.equal(swept.value.amount, balance.toString());
+
+    console.log("✅ Fee collector rotation test passed");
+  });
 });